@@ -1,6 +1,6 @@
 use clap::{Parser, ValueEnum};
 use oura::{
-    daemon::{run_daemon, ConfigRoot},
+    daemon::{block_until_drained, run_daemon, ConfigRoot},
     filters,
     framework::{ChainConfig, Error, IntersectConfig},
     sinks, sources,
@@ -29,6 +29,8 @@ pub fn run(args: &Args) -> Result<(), Error> {
         }),
         Bearer::Tcp => sources::Config::N2N(sources::n2n::Config {
             peers: vec![args.socket.clone()],
+            prefer_low_latency: false,
+            catch_up: None,
         }),
     };
     let filter = filters::Config::LegacyV1(filters::legacy_v1::Config {
@@ -47,23 +49,24 @@ pub fn run(args: &Args) -> Result<(), Error> {
     let config = ConfigRoot {
         source,
         filters: Some(vec![filter]),
-        sink,
+        sink: Some(sink),
+        sinks: None,
+        branches: None,
         intersect,
         finalize: None,
         chain: Some(chain),
         retries: None,
         cursor: None,
         metrics: None,
+        admin: None,
     };
 
-    let daemon = run_daemon(config)?;
+    let daemon = run_daemon(config, Default::default())?;
 
-    daemon.block();
+    block_until_drained(&daemon);
 
     info!("oura is stopping");
 
-    daemon.teardown();
-
     Ok(())
 }
 
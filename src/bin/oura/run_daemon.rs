@@ -1,9 +1,11 @@
 use gasket::daemon::Daemon;
-use oura::daemon::{run_daemon, ConfigRoot, MetricsConfig};
+use oura::daemon::{
+    admin, block_until_drained, run_daemon, AdminConfig, ConfigRoot, MetricsConfig,
+};
 use oura::framework::*;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tracing::{info, Level};
+use tracing::{error, info, Level};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 use crate::console;
@@ -41,6 +43,26 @@ async fn serve_prometheus(
     Ok(())
 }
 
+async fn serve_admin(
+    daemon: Arc<Daemon>,
+    admin: Option<AdminConfig>,
+    control: Control,
+    intersect: IntersectConfig,
+) -> Result<(), Error> {
+    if let Some(admin) = admin {
+        info!("starting admin api");
+        let result = admin::serve(admin, daemon, control, intersect).await;
+
+        if let Err(err) = &result {
+            error!(%err, "admin api failed");
+        }
+
+        result?;
+    }
+
+    Ok(())
+}
+
 pub fn run(args: &Args) -> Result<(), Error> {
     if !args.tui {
         setup_tracing();
@@ -48,8 +70,11 @@ pub fn run(args: &Args) -> Result<(), Error> {
 
     let config = ConfigRoot::new(&args.config).map_err(Error::config)?;
     let metrics = config.metrics.clone();
+    let admin = config.admin.clone();
+    let intersect = config.intersect.clone();
+    let control = Control::default();
 
-    let daemon = run_daemon(config)?;
+    let daemon = run_daemon(config, control.clone())?;
 
     info!("oura is running");
 
@@ -62,14 +87,15 @@ pub fn run(args: &Args) -> Result<(), Error> {
         .unwrap();
 
     let prometheus = tokio_rt.spawn(serve_prometheus(daemon.clone(), metrics));
+    let admin = tokio_rt.spawn(serve_admin(daemon.clone(), admin, control, intersect));
     let tui = tokio_rt.spawn(console::render(daemon.clone(), args.tui));
 
-    daemon.block();
+    block_until_drained(&daemon);
 
     info!("oura is stopping");
 
-    daemon.teardown();
     prometheus.abort();
+    admin.abort();
     tui.abort();
 
     Ok(())
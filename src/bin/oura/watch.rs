@@ -1,7 +1,13 @@
 use clap::{Parser, ValueEnum};
 use oura::{
-    daemon::{run_daemon, ConfigRoot},
-    filters,
+    daemon::{block_until_drained, run_daemon, ConfigRoot},
+    filters::{
+        self,
+        select::{
+            eval::{dsl, Predicate, StringOrStruct},
+            UncertainPolicy,
+        },
+    },
     framework::{ChainConfig, Error, IntersectConfig},
     sinks, sources,
 };
@@ -29,8 +35,21 @@ pub fn run(args: &Args) -> Result<(), Error> {
         }),
         Bearer::Tcp => sources::Config::N2N(sources::n2n::Config {
             peers: vec![args.socket.clone()],
+            prefer_low_latency: false,
+            catch_up: None,
         }),
     };
+    // blocks are evaluated before they're turned into v1 events, so every
+    // pattern is available
+    let select = args.select.clone().map(|predicate| {
+        filters::Config::Select(filters::select::Config {
+            predicate: StringOrStruct(*predicate),
+            skip_uncertain: false,
+            on_uncertain: Some(UncertainPolicy::Drop),
+            skip_irrelevant_resets: true,
+        })
+    });
+
     let filter = filters::Config::LegacyV1(filters::legacy_v1::Config {
         include_block_end_events: true,
         ..Default::default()
@@ -43,24 +62,25 @@ pub fn run(args: &Args) -> Result<(), Error> {
 
     let config = ConfigRoot {
         source,
-        filters: Some(vec![filter]),
-        sink,
+        filters: Some(select.into_iter().chain([filter]).collect()),
+        sink: Some(sink),
+        sinks: None,
+        branches: None,
         intersect,
         finalize: None,
         chain: Some(chain),
         retries: None,
         cursor: None,
         metrics: None,
+        admin: None,
     };
 
-    let daemon = run_daemon(config)?;
+    let daemon = run_daemon(config, Default::default())?;
 
-    daemon.block();
+    block_until_drained(&daemon);
 
     info!("oura is stopping");
 
-    daemon.teardown();
-
     Ok(())
 }
 
@@ -83,6 +103,10 @@ fn parse_since(since: Option<String>) -> Result<IntersectConfig, Error> {
     }
 }
 
+fn parse_select(select: &str) -> Result<Box<Predicate>, String> {
+    dsl::parse(select).map(Box::new).map_err(|err| err.render())
+}
+
 #[derive(Parser, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
@@ -105,6 +129,10 @@ pub struct Args {
     /// milliseconds to wait between output lines (for easier reading)
     #[arg(short, long, default_value_t = false)]
     wrap: bool,
+
+    /// only show the blocks that match this predicate expression (eg: `addr1... and metadata #674`)
+    #[arg(long, value_parser = parse_select)]
+    select: Option<Box<Predicate>>,
 }
 
 #[derive(ValueEnum, Clone, Default)]
@@ -36,7 +36,9 @@ pub enum Unit {
 }
 
 #[derive(Default)]
-pub struct Worker {}
+pub struct Worker {
+    drained: bool,
+}
 
 #[async_trait::async_trait(?Send)]
 impl gasket::framework::Worker<Stage> for Worker {
@@ -45,11 +47,19 @@ impl gasket::framework::Worker<Stage> for Worker {
     }
 
     async fn schedule(&mut self, stage: &mut Stage) -> Result<WorkSchedule<Unit>, WorkerError> {
+        if self.drained {
+            return Ok(WorkSchedule::Done);
+        }
+
         select! {
-            msg = stage.track.recv() => {
-                let msg = msg.or_panic()?;
-                Ok(WorkSchedule::Unit(Unit::Track(msg.payload)))
-            }
+            msg = stage.track.recv() => match msg {
+                Ok(msg) => Ok(WorkSchedule::Unit(Unit::Track(msg.payload))),
+                Err(_) => {
+                    // upstream stages are gone, flush one last time before ending
+                    self.drained = true;
+                    Ok(WorkSchedule::Unit(Unit::Flush))
+                }
+            },
             msg = stage.flush.recv() => {
                 msg.or_panic()?;
                 Ok(WorkSchedule::Unit(Unit::Flush))
@@ -59,7 +69,10 @@ impl gasket::framework::Worker<Stage> for Worker {
 
     async fn execute(&mut self, unit: &Unit, stage: &mut Stage) -> Result<(), WorkerError> {
         match unit {
-            Unit::Track(x) => stage.breadcrumbs.track(x.clone()),
+            Unit::Track(x) => {
+                stage.breadcrumbs.track(x.clone());
+                stage.control.publish_cursor(&stage.breadcrumbs);
+            }
             Unit::Flush => {
                 let file = std::fs::File::options()
                     .write(true)
@@ -85,6 +98,8 @@ pub struct Stage {
 
     breadcrumbs: Breadcrumbs,
 
+    control: Control,
+
     pub track: gasket::messaging::InputPort<Point>,
 
     pub flush: gasket::messaging::TimerPort,
@@ -142,6 +157,7 @@ impl Config {
         let stage = Stage {
             path: self.define_path()?,
             breadcrumbs: ctx.breadcrumbs.clone(),
+            control: ctx.control.clone(),
             tracked_slot: Default::default(),
             flush_count: Default::default(),
             track: Default::default(),
@@ -14,12 +14,16 @@ impl gasket::framework::Worker<Stage> for Worker {
     }
 
     async fn schedule(&mut self, stage: &mut Stage) -> Result<WorkSchedule<Point>, WorkerError> {
-        let msg = stage.track.recv().await.or_panic()?;
-        Ok(WorkSchedule::Unit(msg.payload))
+        match stage.track.recv().await {
+            Ok(msg) => Ok(WorkSchedule::Unit(msg.payload)),
+            // upstream stages are gone, nothing else to track
+            Err(_) => Ok(WorkSchedule::Done),
+        }
     }
 
     async fn execute(&mut self, unit: &Point, stage: &mut Stage) -> Result<(), WorkerError> {
         stage.breadcrumbs.track(unit.clone());
+        stage.control.publish_cursor(&stage.breadcrumbs);
         Ok(())
     }
 }
@@ -29,6 +33,8 @@ impl gasket::framework::Worker<Stage> for Worker {
 pub struct Stage {
     breadcrumbs: Breadcrumbs,
 
+    control: Control,
+
     pub track: gasket::messaging::InputPort<Point>,
 
     #[metric]
@@ -46,6 +52,7 @@ impl Config {
     pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
             breadcrumbs: ctx.breadcrumbs.clone(),
+            control: ctx.control.clone(),
             tracked_slot: Default::default(),
             track: Default::default(),
         };
@@ -38,6 +38,7 @@ pub enum Unit {
 pub struct Worker {
     client: redis::Client,
     key: String,
+    drained: bool,
 }
 
 #[async_trait::async_trait(?Send)]
@@ -48,15 +49,24 @@ impl gasket::framework::Worker<Stage> for Worker {
         Ok(Self {
             client,
             key: stage.key.clone(),
+            drained: false,
         })
     }
 
     async fn schedule(&mut self, stage: &mut Stage) -> Result<WorkSchedule<Unit>, WorkerError> {
+        if self.drained {
+            return Ok(WorkSchedule::Done);
+        }
+
         select! {
-            msg = stage.track.recv() => {
-                let msg = msg.or_panic()?;
-                Ok(WorkSchedule::Unit(Unit::Track(msg.payload)))
-            }
+            msg = stage.track.recv() => match msg {
+                Ok(msg) => Ok(WorkSchedule::Unit(Unit::Track(msg.payload))),
+                Err(_) => {
+                    // upstream stages are gone, flush one last time before ending
+                    self.drained = true;
+                    Ok(WorkSchedule::Unit(Unit::Flush))
+                }
+            },
             msg = stage.flush.recv() => {
                 msg.or_panic()?;
                 Ok(WorkSchedule::Unit(Unit::Flush))
@@ -66,7 +76,10 @@ impl gasket::framework::Worker<Stage> for Worker {
 
     async fn execute(&mut self, unit: &Unit, stage: &mut Stage) -> Result<(), WorkerError> {
         match unit {
-            Unit::Track(x) => stage.breadcrumbs.track(x.clone()),
+            Unit::Track(x) => {
+                stage.breadcrumbs.track(x.clone());
+                stage.control.publish_cursor(&stage.breadcrumbs);
+            }
             Unit::Flush => {
                 let data = breadcrumbs_to_data(&stage.breadcrumbs);
                 let mut conn = self.client.get_connection().or_restart()?;
@@ -92,6 +105,8 @@ pub struct Stage {
 
     breadcrumbs: Breadcrumbs,
 
+    control: Control,
+
     pub track: gasket::messaging::InputPort<Point>,
 
     pub flush: gasket::messaging::TimerPort,
@@ -149,6 +164,7 @@ impl Config {
             key: self.key.clone(),
             url: self.url.clone(),
             breadcrumbs: ctx.breadcrumbs.clone(),
+            control: ctx.control.clone(),
             tracked_slot: Default::default(),
             flush_count: Default::default(),
             track: Default::default(),
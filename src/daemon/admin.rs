@@ -0,0 +1,261 @@
+//! HTTP api to inspect and control a running daemon
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use gasket::daemon::Daemon;
+use gasket::metrics::Reading;
+use gasket::runtime::{StagePhase, Tether, TetherState};
+use serde_json::{json, Map, Value as JsonValue};
+use tracing::info;
+
+use crate::framework::*;
+
+use super::AdminConfig;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:9187";
+
+struct AppState {
+    daemon: Arc<Daemon>,
+    control: Control,
+    intersect: IntersectConfig,
+}
+
+type SharedState = State<Arc<AppState>>;
+
+fn json_response(status: StatusCode, value: JsonValue) -> Response {
+    let body = value.to_string();
+    (status, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+fn phase_name(phase: &StagePhase) -> &'static str {
+    match phase {
+        StagePhase::Bootstrap => "bootstrap",
+        StagePhase::Working => "working",
+        StagePhase::Teardown => "teardown",
+        StagePhase::Ended => "ended",
+    }
+}
+
+fn state_to_json(state: &TetherState) -> JsonValue {
+    match state {
+        TetherState::Dropped => json!({ "state": "dropped" }),
+        TetherState::Blocked(x) => json!({ "state": "blocked", "phase": phase_name(x) }),
+        TetherState::Alive(x) => json!({ "state": "alive", "phase": phase_name(x) }),
+    }
+}
+
+fn metrics_to_json(tether: &Tether) -> JsonValue {
+    let readings = match tether.read_metrics() {
+        Ok(x) => x,
+        Err(_) => return JsonValue::Null,
+    };
+
+    let mut metrics = Map::new();
+
+    for (key, value) in readings {
+        let value = match value {
+            Reading::Count(x) => json!(x),
+            Reading::Gauge(x) => json!(x),
+            Reading::Message(x) => json!(x),
+        };
+
+        metrics.insert(key.to_string(), value);
+    }
+
+    JsonValue::Object(metrics)
+}
+
+fn read_gauge(tether: &Tether, key: &str) -> Option<i64> {
+    tether
+        .read_metrics()
+        .ok()?
+        .into_iter()
+        .find_map(|(k, v)| match v {
+            Reading::Gauge(x) if k == key => Some(x),
+            _ => None,
+        })
+}
+
+async fn health(State(state): SharedState) -> Response {
+    let failed: Vec<_> = state
+        .daemon
+        .tethers()
+        .filter(|x| !matches!(x.check_state(), TetherState::Alive(_)))
+        .map(|x| x.name().to_string())
+        .collect();
+
+    match failed.is_empty() {
+        true => json_response(StatusCode::OK, json!({ "status": "ok" })),
+        false => json_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({ "status": "unhealthy", "stages": failed }),
+        ),
+    }
+}
+
+/// The pipeline is ready once the source caught up with the tip of the chain.
+/// This relies on the `chain_tip` and `current_slot` metrics of the source, so
+/// sources that don't report them are never considered ready.
+async fn ready(State(state): SharedState) -> Response {
+    let source = state.daemon.tethers().next();
+
+    let tip = source.and_then(|x| read_gauge(x, "chain_tip"));
+    let current = source.and_then(|x| read_gauge(x, "current_slot"));
+
+    let ready = match (tip, current) {
+        (Some(tip), Some(current)) => tip > 0 && current >= tip,
+        _ => false,
+    };
+
+    let status = match ready {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    json_response(
+        status,
+        json!({ "ready": ready, "chain_tip": tip, "current_slot": current }),
+    )
+}
+
+async fn stages(State(state): SharedState) -> Response {
+    let stages: Vec<_> = state
+        .daemon
+        .tethers()
+        .map(|tether| {
+            json!({
+                "name": tether.name(),
+                "state": state_to_json(&tether.check_state()),
+                "metrics": metrics_to_json(tether),
+            })
+        })
+        .collect();
+
+    json_response(StatusCode::OK, json!(stages))
+}
+
+async fn cursor(State(state): SharedState) -> Response {
+    let breadcrumbs: Vec<_> = state
+        .control
+        .cursor()
+        .into_iter()
+        .map(point_to_json)
+        .collect();
+
+    json_response(
+        StatusCode::OK,
+        json!({ "intersect": state.intersect, "breadcrumbs": breadcrumbs }),
+    )
+}
+
+async fn pause(State(state): SharedState) -> Response {
+    info!("pausing source by admin request");
+    state.control.pause();
+    json_response(StatusCode::OK, json!({ "paused": true }))
+}
+
+async fn resume(State(state): SharedState) -> Response {
+    info!("resuming source by admin request");
+    state.control.resume();
+    json_response(StatusCode::OK, json!({ "paused": false }))
+}
+
+/// Serves the admin api until the task is aborted
+pub async fn serve(
+    config: AdminConfig,
+    daemon: Arc<Daemon>,
+    control: Control,
+    intersect: IntersectConfig,
+) -> Result<(), Error> {
+    let addr: SocketAddr = config
+        .address
+        .as_deref()
+        .unwrap_or(DEFAULT_ADDRESS)
+        .parse()
+        .map_err(Error::parse)?;
+
+    let state = Arc::new(AppState {
+        daemon,
+        control,
+        intersect,
+    });
+
+    let app = Router::new()
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+        .route("/stages", get(stages))
+        .route("/cursor", get(cursor))
+        .route("/source/pause", post(pause))
+        .route("/source/resume", post(resume))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(Error::custom)?;
+
+    info!(%addr, "admin api listening");
+
+    axum::serve(listener, app).await.map_err(Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use pallas::network::miniprotocols::Point;
+
+    use super::*;
+
+    fn state() -> Arc<AppState> {
+        Arc::new(AppState {
+            daemon: Arc::new(Daemon(vec![])),
+            control: Control::default(),
+            intersect: IntersectConfig::Tip,
+        })
+    }
+
+    async fn body(response: Response) -> JsonValue {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn cursor_returns_published_breadcrumbs() {
+        let state = state();
+
+        let mut breadcrumbs = Breadcrumbs::new(5);
+        breadcrumbs.track(Point::Specific(10, vec![0xab]));
+        breadcrumbs.track(Point::Specific(20, vec![0xcd]));
+        state.control.publish_cursor(&breadcrumbs);
+
+        let response = cursor(State(state)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body(response).await;
+        assert_eq!(body["intersect"]["type"], "Tip");
+        assert_eq!(body["breadcrumbs"][0]["slot"], 20);
+        assert_eq!(body["breadcrumbs"][1]["hash"], "ab");
+    }
+
+    #[tokio::test]
+    async fn pause_and_resume_toggle_control() {
+        let state = state();
+
+        pause(State(state.clone())).await;
+        assert!(state.control.is_paused());
+
+        resume(State(state.clone())).await;
+        assert!(!state.control.is_paused());
+    }
+
+    #[tokio::test]
+    async fn not_ready_without_source_metrics() {
+        let response = ready(State(state())).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
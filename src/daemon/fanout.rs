@@ -0,0 +1,171 @@
+//! Wiring required to connect a single pipeline to several sinks
+
+use std::sync::Arc;
+
+use gasket::messaging::tokio::{mpsc_channel, ChannelSendAdapter};
+use gasket::messaging::{InputPort, Message, OutputPort, SendAdapter};
+use pallas::network::miniprotocols::Point;
+use tokio::sync::Mutex;
+
+/// Sends a copy of each message to every connected input
+///
+/// Messages are sent in order to each input, so the pace is set by the slowest
+/// of them.
+struct FanoutAdapter<P>(Vec<ChannelSendAdapter<P>>);
+
+#[async_trait::async_trait]
+impl<P> SendAdapter<P> for FanoutAdapter<P>
+where
+    P: Clone + Send + Sync,
+{
+    async fn send(&mut self, msg: Message<P>) -> Result<(), gasket::error::Error> {
+        for sender in self.0.iter_mut() {
+            sender.send(msg.clone()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn fanout_ports<P>(output: &mut OutputPort<P>, inputs: Vec<&mut InputPort<P>>, cap: usize)
+where
+    P: Clone + Send + Sync + 'static,
+{
+    let mut senders = vec![];
+
+    for input in inputs {
+        let (sender, receiver) = mpsc_channel(cap);
+        input.connect(receiver);
+        senders.push(sender);
+    }
+
+    output.connect(FanoutAdapter(senders));
+}
+
+struct AckState {
+    acks: Vec<Option<Point>>,
+    forwarded: Option<Point>,
+    cursor: ChannelSendAdapter<Point>,
+}
+
+impl AckState {
+    /// The lowest point acknowledged by every sink, if all of them already
+    /// acknowledged something
+    fn lowest(&self) -> Option<Point> {
+        let acks: Option<Vec<&Point>> = self.acks.iter().map(Option::as_ref).collect();
+
+        acks?
+            .into_iter()
+            .min_by_key(|x| x.slot_or_default())
+            .cloned()
+    }
+}
+
+/// Tracks the points acknowledged by one of the sinks of a fan-out and
+/// forwards the lowest point acknowledged by all of them to the cursor
+struct AckAdapter {
+    index: usize,
+    state: Arc<Mutex<AckState>>,
+}
+
+#[async_trait::async_trait]
+impl SendAdapter<Point> for AckAdapter {
+    async fn send(&mut self, msg: Message<Point>) -> Result<(), gasket::error::Error> {
+        let mut state = self.state.lock().await;
+
+        state.acks[self.index] = Some(msg.payload);
+
+        let lowest = match state.lowest() {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        if state.forwarded.as_ref() != Some(&lowest) {
+            state.forwarded = Some(lowest.clone());
+            state.cursor.send(lowest.into()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Connects the cursor ports of several sinks to a single cursor input, which
+/// only receives points once they were acknowledged by all of the sinks
+pub fn reconcile_ports(
+    outputs: Vec<&mut OutputPort<Point>>,
+    input: &mut InputPort<Point>,
+    cap: usize,
+) {
+    let (cursor, receiver) = mpsc_channel(cap);
+    input.connect(receiver);
+
+    let state = Arc::new(Mutex::new(AckState {
+        acks: vec![None; outputs.len()],
+        forwarded: None,
+        cursor,
+    }));
+
+    for (index, output) in outputs.into_iter().enumerate() {
+        output.connect(AckAdapter {
+            index,
+            state: state.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, slot.to_be_bytes().to_vec())
+    }
+
+    #[tokio::test]
+    async fn cursor_follows_slowest_sink() {
+        let mut fast = OutputPort::default();
+        let mut slow = OutputPort::default();
+        let mut cursor = InputPort::default();
+
+        reconcile_ports(vec![&mut fast, &mut slow], &mut cursor, 10);
+
+        fast.send(point(10).into()).await.unwrap();
+        fast.send(point(20).into()).await.unwrap();
+        slow.send(point(10).into()).await.unwrap();
+        fast.send(point(30).into()).await.unwrap();
+        slow.send(point(20).into()).await.unwrap();
+
+        assert_eq!(cursor.recv().await.unwrap().payload, point(10));
+        assert_eq!(cursor.recv().await.unwrap().payload, point(20));
+    }
+
+    #[tokio::test]
+    async fn cursor_follows_rollbacks() {
+        let mut first = OutputPort::default();
+        let mut second = OutputPort::default();
+        let mut cursor = InputPort::default();
+
+        reconcile_ports(vec![&mut first, &mut second], &mut cursor, 10);
+
+        first.send(point(20).into()).await.unwrap();
+        second.send(point(20).into()).await.unwrap();
+        first.send(point(15).into()).await.unwrap();
+
+        assert_eq!(cursor.recv().await.unwrap().payload, point(20));
+        assert_eq!(cursor.recv().await.unwrap().payload, point(15));
+    }
+
+    #[tokio::test]
+    async fn fanout_reaches_every_input() {
+        let mut output = OutputPort::default();
+        let mut first = InputPort::default();
+        let mut second = InputPort::default();
+
+        fanout_ports(&mut output, vec![&mut first, &mut second], 10);
+
+        output.send(point(1).into()).await.unwrap();
+
+        assert_eq!(first.recv().await.unwrap().payload, point(1));
+        assert_eq!(second.recv().await.unwrap().payload, point(1));
+    }
+}
@@ -0,0 +1,70 @@
+//! A stage that holds back the events of the source while the pipeline is
+//! paused through the admin api
+
+use std::time::Duration;
+
+use gasket::framework::*;
+
+use crate::framework::*;
+
+#[derive(Stage)]
+#[stage(name = "gate", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    control: Control,
+
+    pub input: FilterInputPort,
+    pub output: FilterOutputPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    paused: gasket::metrics::Gauge,
+}
+
+impl Stage {
+    pub fn new(control: Control) -> Self {
+        Self {
+            control,
+            input: Default::default(),
+            output: Default::default(),
+            ops_count: Default::default(),
+            paused: Default::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Worker;
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
+        Ok(Self)
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        // while paused, we stop pulling from the source. Once the buffer between
+        // both stages is full, the source stops pulling from upstream too.
+        if stage.control.is_paused() {
+            stage.paused.set(1);
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            return Ok(WorkSchedule::Idle);
+        }
+
+        stage.paused.set(0);
+
+        let msg = stage.input.recv().await.or_panic()?;
+        Ok(WorkSchedule::Unit(msg.payload))
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        stage.output.send(unit.clone().into()).await.or_panic()?;
+        stage.ops_count.inc(1);
+
+        Ok(())
+    }
+}
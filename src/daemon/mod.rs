@@ -1,25 +1,40 @@
 use gasket::daemon::Daemon;
+use gasket::runtime::{StagePhase, TetherState};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
+use tracing::debug;
 
 use crate::{cursor, filters, framework::*, sinks, sources};
 
+pub mod admin;
+mod fanout;
+mod gate;
+pub mod router;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetricsConfig {
     pub address: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminConfig {
+    pub address: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct ConfigRoot {
     pub source: sources::Config,
     pub filters: Option<Vec<filters::Config>>,
-    pub sink: sinks::Config,
+    pub sink: Option<sinks::Config>,
+    pub sinks: Option<Vec<sinks::Config>>,
+    pub branches: Option<Vec<router::BranchConfig>>,
     pub intersect: IntersectConfig,
     pub finalize: Option<FinalizeConfig>,
     pub chain: Option<ChainConfig>,
     pub retries: Option<gasket::retries::Policy>,
     pub cursor: Option<cursor::Config>,
     pub metrics: Option<MetricsConfig>,
+    pub admin: Option<AdminConfig>,
 }
 
 impl ConfigRoot {
@@ -61,29 +76,64 @@ fn define_gasket_policy(config: Option<&gasket::retries::Policy>) -> gasket::run
     }
 }
 
+/// Where the events go after the shared filters of the pipeline
+enum Outlet {
+    Sinks(Vec<sinks::Bootstrapper>),
+    Router(router::Router),
+}
+
 fn connect_stages(
     mut source: sources::Bootstrapper,
+    mut gate: Option<gate::Stage>,
     mut filters: Vec<filters::Bootstrapper>,
-    mut sink: sinks::Bootstrapper,
+    mut outlet: Outlet,
     mut cursor: cursor::Bootstrapper,
     policy: gasket::runtime::Policy,
 ) -> Result<Daemon, Error> {
     let mut prev = source.borrow_output();
 
+    if let Some(gate) = gate.as_mut() {
+        gasket::messaging::tokio::connect_ports(prev, &mut gate.input, 100);
+        prev = &mut gate.output;
+    }
+
     for filter in filters.iter_mut() {
         gasket::messaging::tokio::connect_ports(prev, filter.borrow_input(), 100);
         prev = filter.borrow_output();
     }
 
-    gasket::messaging::tokio::connect_ports(prev, sink.borrow_input(), 100);
-    let prev = sink.borrow_cursor();
-
-    gasket::messaging::tokio::connect_ports(prev, cursor.borrow_track(), 100);
+    match &mut outlet {
+        Outlet::Sinks(sinks) => match sinks.as_mut_slice() {
+            [] => return Err(Error::config("at least one sink is required")),
+            [sink] => {
+                gasket::messaging::tokio::connect_ports(prev, sink.borrow_input(), 100);
+                gasket::messaging::tokio::connect_ports(
+                    sink.borrow_cursor(),
+                    cursor.borrow_track(),
+                    100,
+                );
+            }
+            many => {
+                let inputs = many.iter_mut().map(|x| x.borrow_input()).collect();
+                fanout::fanout_ports(prev, inputs, 100);
+
+                let outputs = many.iter_mut().map(|x| x.borrow_cursor()).collect();
+                fanout::reconcile_ports(outputs, cursor.borrow_track(), 100);
+            }
+        },
+        Outlet::Router(router) => router.connect(prev, &mut cursor),
+    }
 
     let mut tethers = vec![];
     tethers.push(source.spawn(policy.clone()));
+    tethers.extend(gate.map(|x| gasket::runtime::spawn_stage(x, policy.clone())));
     tethers.extend(filters.into_iter().map(|x| x.spawn(policy.clone())));
-    tethers.push(sink.spawn(policy.clone()));
+
+    match outlet {
+        Outlet::Sinks(sinks) => tethers.extend(sinks.into_iter().map(|x| x.spawn(policy.clone()))),
+        Outlet::Router(router) => tethers.extend(router.spawn(policy.clone())),
+    }
+
     tethers.push(cursor.spawn(policy));
 
     let runtime = Daemon(tethers);
@@ -91,30 +141,88 @@ fn connect_stages(
     Ok(runtime)
 }
 
-pub fn run_daemon(config: ConfigRoot) -> Result<Daemon, Error> {
+/// Bootstraps and spawns every stage of the pipeline
+///
+/// The `control` handle gives access to the runtime state of the pipeline,
+/// which is what the admin api uses to inspect and pause it.
+pub fn run_daemon(config: ConfigRoot, control: Control) -> Result<Daemon, Error> {
     let chain = config.chain.unwrap_or_default();
     let intersect = config.intersect;
     let finalize = config.finalize;
     let current_dir = std::env::current_dir().unwrap();
+    // mempool events carry hash-less points, which can't be used to intersect
+    if matches!(config.source, sources::Config::Mempool(_)) && config.cursor.is_some() {
+        return Err(Error::config(
+            "the mempool source can't be resumed, remove the cursor section",
+        ));
+    }
     let cursor = config.cursor.unwrap_or_default();
     let breadcrumbs = cursor.initial_load()?;
+    control.publish_cursor(&breadcrumbs);
     let ctx = Context {
         chain,
         intersect,
         finalize,
         current_dir,
         breadcrumbs,
+        control,
     };
     let source = config.source.bootstrapper(&ctx)?;
+    let gate = config
+        .admin
+        .as_ref()
+        .map(|_| gate::Stage::new(ctx.control.clone()));
     let filters = config
         .filters
         .into_iter()
         .flatten()
         .map(|x| x.bootstrapper(&ctx))
         .collect::<Result<_, _>>()?;
-    let sink = config.sink.bootstrapper(&ctx)?;
+    let sinks: Vec<_> = config
+        .sink
+        .into_iter()
+        .chain(config.sinks.into_iter().flatten())
+        .collect();
+    let outlet = match config.branches {
+        Some(_) if !sinks.is_empty() => {
+            return Err(Error::config("sinks and branches can't be used together"))
+        }
+        Some(branches) => Outlet::Router(router::Router::bootstrapper(branches, &ctx)?),
+        None => Outlet::Sinks(
+            sinks
+                .into_iter()
+                .map(|x| x.bootstrapper(&ctx))
+                .collect::<Result<_, _>>()?,
+        ),
+    };
     let cursor = cursor.bootstrapper(&ctx)?;
     let retries = define_gasket_policy(config.retries.as_ref());
-    let daemon = connect_stages(source, filters, sink, cursor, retries)?;
+    let daemon = connect_stages(source, gate, filters, outlet, cursor, retries)?;
     Ok(daemon)
 }
+
+/// Blocks the current thread until the pipeline is drained
+///
+/// Unlike `Daemon::block`, which stops everything as soon as any stage ends,
+/// this waits for the cursor stage to end. Stages are chained through ports, so
+/// when a stage ends (eg: a source reaching its finalize condition) the next
+/// one processes whatever is left in its input and then ends too. The cursor is
+/// the last link of that chain, which means every event emitted upstream has
+/// already been acknowledged by the sink and the cursor had a chance to flush.
+pub fn block_until_drained(daemon: &Daemon) {
+    let cursor = daemon.tethers().last().expect("cursor stage");
+
+    loop {
+        match cursor.check_state() {
+            TetherState::Dropped | TetherState::Alive(StagePhase::Ended) => break,
+            _ => std::thread::sleep(Duration::from_millis(1500)),
+        }
+    }
+
+    for tether in daemon.tethers() {
+        // stages that already ended on their own are dropped and can't be dismissed
+        if tether.dismiss_stage().is_err() {
+            debug!(stage = tether.name(), "stage already ended");
+        }
+    }
+}
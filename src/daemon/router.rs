@@ -0,0 +1,577 @@
+//! Content-based routing of events into independent branches
+//!
+//! Each branch has its own predicate, filters and sink. The router evaluates
+//! the predicate of every branch for each event and sends a copy to each of the
+//! branches that match. Events that don't match any branch are dropped.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use gasket::framework::*;
+use gasket::messaging::tokio::{mpsc_channel, ChannelRecvAdapter, ChannelSendAdapter};
+use gasket::messaging::{Message, OutputPort, RecvAdapter, SendAdapter};
+use gasket::runtime::Tether;
+use pallas::network::miniprotocols::Point;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::filters::select::eval::{EventPredicate, MatchOutcome, Predicate, StringOrStruct};
+use crate::filters::select::UncertainPolicy;
+use crate::framework::*;
+use crate::{cursor, filters, sinks};
+
+#[derive(Deserialize)]
+pub struct BranchConfig {
+    /// Events that match this predicate are sent to the branch. If omitted,
+    /// the branch receives every event.
+    pub predicate: Option<StringOrStruct<Predicate>>,
+
+    /// Same as setting `on_uncertain` to `drop`, kept for compatibility
+    #[serde(default)]
+    pub skip_uncertain: bool,
+
+    pub on_uncertain: Option<UncertainPolicy>,
+
+    pub filters: Option<Vec<filters::Config>>,
+    pub sink: sinks::Config,
+}
+
+/// Keeps track of the events sent to each branch that weren't acknowledged yet
+///
+/// The cursor can only move up to the point of the oldest event that is still
+/// in-flight, otherwise a restart would skip it. Branches receive different
+/// subsets of the events, so their acks can't be compared directly like in a
+/// plain fan-out.
+struct Ledger {
+    pending: VecDeque<(Point, Vec<bool>)>,
+    forwarded: Option<Point>,
+    cursor: ChannelSendAdapter<Point>,
+}
+
+impl Ledger {
+    fn dispatch(&mut self, point: Point, targets: Vec<bool>) {
+        self.pending.push_back((point, targets));
+    }
+
+    fn ack(&mut self, branch: usize, point: &Point) {
+        // sinks acknowledge events in the same order they were dispatched, so an
+        // ack also covers any earlier event of the branch (eg: events dropped by
+        // one of the filters of the branch)
+        let found = self
+            .pending
+            .iter()
+            .position(|(p, targets)| targets[branch] && p == point);
+
+        if let Some(last) = found {
+            for (_, targets) in self.pending.iter_mut().take(last + 1) {
+                targets[branch] = false;
+            }
+        }
+    }
+
+    /// Clears the events of the branch that its filters dropped. Filters report
+    /// each run of consecutive events with the same point once.
+    fn discard(&mut self, branch: usize, point: &Point) {
+        let run = self
+            .pending
+            .iter_mut()
+            .filter(|(_, targets)| targets[branch])
+            .skip_while(|(p, _)| p != point)
+            .take_while(|(p, _)| p == point);
+
+        for (_, targets) in run {
+            targets[branch] = false;
+        }
+    }
+
+    /// Removes the events that were acknowledged by all of their branches and
+    /// returns the new point to forward to the cursor, if it changed
+    fn settle(&mut self) -> Option<Point> {
+        let mut settled = None;
+
+        while let Some((point, targets)) = self.pending.front() {
+            if targets.iter().any(|x| *x) {
+                break;
+            }
+
+            settled = Some(point.clone());
+            self.pending.pop_front();
+        }
+
+        let settled = settled?;
+
+        if self.forwarded.as_ref() == Some(&settled) {
+            return None;
+        }
+
+        self.forwarded = Some(settled.clone());
+
+        Some(settled)
+    }
+
+    async fn forward(&mut self) -> Result<(), gasket::error::Error> {
+        if let Some(point) = self.settle() {
+            self.cursor.send(point.into()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Receives the points acknowledged by the sink of one of the branches
+struct AckAdapter {
+    branch: usize,
+    ledger: Arc<Mutex<Ledger>>,
+}
+
+#[async_trait::async_trait]
+impl SendAdapter<Point> for AckAdapter {
+    async fn send(&mut self, msg: Message<Point>) -> Result<(), gasket::error::Error> {
+        let mut ledger = self.ledger.lock().await;
+
+        ledger.ack(self.branch, &msg.payload);
+        ledger.forward().await
+    }
+}
+
+/// Follows the events going through one of the filters of a branch to find
+/// the ones it dropped
+///
+/// Filters process their input in order, so once a filter receives an event
+/// with a new point, it's done with the events of the previous point. If none
+/// of them produced an output, nothing of that point can reach the sink and
+/// the router acknowledges it on behalf of the branch. The last point is only
+/// settled when the next event reaches the filter.
+#[derive(Default)]
+struct DropTracker {
+    current: Option<Point>,
+    forwarded: bool,
+}
+
+impl DropTracker {
+    /// Returns the previous point if all of its events were dropped
+    fn received(&mut self, point: &Point) -> Option<Point> {
+        if self.current.as_ref() == Some(point) {
+            return None;
+        }
+
+        let previous = self.current.replace(point.clone());
+        let forwarded = std::mem::replace(&mut self.forwarded, false);
+
+        previous.filter(|_| !forwarded)
+    }
+}
+
+/// Input of a branch filter, reports the events dropped by the filter
+struct TrackedRecv {
+    inner: ChannelRecvAdapter<ChainEvent>,
+    tracker: Arc<std::sync::Mutex<DropTracker>>,
+    branch: usize,
+    ledger: Arc<Mutex<Ledger>>,
+}
+
+#[async_trait::async_trait]
+impl RecvAdapter<ChainEvent> for TrackedRecv {
+    async fn recv(&mut self) -> Result<Message<ChainEvent>, gasket::error::Error> {
+        let msg = self.inner.recv().await?;
+
+        let dropped = self.tracker.lock().unwrap().received(msg.payload.point());
+
+        if let Some(point) = dropped {
+            let mut ledger = self.ledger.lock().await;
+
+            ledger.discard(self.branch, &point);
+            ledger.forward().await?;
+        }
+
+        Ok(msg)
+    }
+}
+
+/// Output of a branch filter, flags the current point as forwarded
+struct TrackedSend {
+    inner: ChannelSendAdapter<ChainEvent>,
+    tracker: Arc<std::sync::Mutex<DropTracker>>,
+}
+
+#[async_trait::async_trait]
+impl SendAdapter<ChainEvent> for TrackedSend {
+    async fn send(&mut self, msg: Message<ChainEvent>) -> Result<(), gasket::error::Error> {
+        self.tracker.lock().unwrap().forwarded = true;
+        self.inner.send(msg).await
+    }
+}
+
+struct Route {
+    predicate: Option<EventPredicate>,
+    on_uncertain: UncertainPolicy,
+}
+
+impl Route {
+    fn matches(&self, unit: &ChainEvent) -> Result<bool, WorkerError> {
+        let predicate = match &self.predicate {
+            Some(x) => x,
+            None => return Ok(true),
+        };
+
+        let outcome = predicate.eval(unit).unwrap_or(MatchOutcome::Positive);
+
+        match outcome {
+            MatchOutcome::Positive => Ok(true),
+            MatchOutcome::Negative => Ok(false),
+            MatchOutcome::Uncertain => match self.on_uncertain {
+                UncertainPolicy::Drop => Ok(false),
+                UncertainPolicy::Forward => Ok(true),
+                UncertainPolicy::Fail => Err(WorkerError::Panic),
+            },
+        }
+    }
+}
+
+#[derive(Stage)]
+#[stage(name = "router", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    routes: Vec<Route>,
+    ledger: Arc<Mutex<Ledger>>,
+
+    pub input: FilterInputPort,
+    pub outputs: Vec<FilterOutputPort>,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    dropped_count: gasket::metrics::Counter,
+}
+
+#[derive(Default)]
+pub struct Worker;
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
+        Ok(Default::default())
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        let msg = stage.input.recv().await.or_panic()?;
+
+        Ok(WorkSchedule::Unit(msg.payload))
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        let targets = stage
+            .routes
+            .iter()
+            .map(|x| x.matches(unit))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !targets.iter().any(|x| *x) {
+            stage.dropped_count.inc(1);
+        }
+
+        // the event needs to be in the ledger before any branch has a chance to
+        // acknowledge it
+        {
+            let mut ledger = stage.ledger.lock().await;
+            ledger.dispatch(unit.point().clone(), targets.clone());
+            ledger.forward().await.or_panic()?;
+        }
+
+        for (output, target) in stage.outputs.iter_mut().zip(targets) {
+            if target {
+                output.send(unit.clone().into()).await.or_panic()?;
+            }
+        }
+
+        stage.ops_count.inc(1);
+
+        Ok(())
+    }
+}
+
+fn connect_sender(
+    output: &mut FilterOutputPort,
+    sender: ChannelSendAdapter<ChainEvent>,
+    tracker: Option<Arc<std::sync::Mutex<DropTracker>>>,
+) {
+    match tracker {
+        Some(tracker) => output.connect(TrackedSend {
+            inner: sender,
+            tracker,
+        }),
+        None => output.connect(sender),
+    }
+}
+
+pub struct Branch {
+    filters: Vec<filters::Bootstrapper>,
+    sink: sinks::Bootstrapper,
+}
+
+pub struct Router {
+    stage: Stage,
+    branches: Vec<Branch>,
+    cursor: Option<ChannelRecvAdapter<Point>>,
+}
+
+impl Router {
+    pub fn bootstrapper(configs: Vec<BranchConfig>, ctx: &Context) -> Result<Self, Error> {
+        if configs.is_empty() {
+            return Err(Error::config("at least one branch is required"));
+        }
+
+        let mut routes = vec![];
+        let mut branches = vec![];
+
+        for config in configs {
+            info!(predicate = ?config.predicate, "router branch predicate");
+
+            routes.push(Route {
+                predicate: config.predicate.map(|x| x.0.into()),
+                on_uncertain: UncertainPolicy::from_config(
+                    config.on_uncertain,
+                    config.skip_uncertain,
+                ),
+            });
+
+            branches.push(Branch {
+                filters: config
+                    .filters
+                    .into_iter()
+                    .flatten()
+                    .map(|x| x.bootstrapper(ctx))
+                    .collect::<Result<_, _>>()?,
+                sink: config.sink.bootstrapper(ctx)?,
+            });
+        }
+
+        let (sender, receiver) = mpsc_channel(100);
+
+        let ledger = Ledger {
+            pending: Default::default(),
+            forwarded: None,
+            cursor: sender,
+        };
+
+        let stage = Stage {
+            outputs: routes.iter().map(|_| OutputPort::default()).collect(),
+            routes,
+            ledger: Arc::new(Mutex::new(ledger)),
+            input: Default::default(),
+            ops_count: Default::default(),
+            dropped_count: Default::default(),
+        };
+
+        Ok(Self {
+            stage,
+            branches,
+            cursor: Some(receiver),
+        })
+    }
+
+    pub fn connect(&mut self, input: &mut FilterOutputPort, cursor: &mut cursor::Bootstrapper) {
+        gasket::messaging::tokio::connect_ports(input, &mut self.stage.input, 100);
+
+        let outputs = self.stage.outputs.iter_mut();
+
+        for (index, (output, branch)) in outputs.zip(self.branches.iter_mut()).enumerate() {
+            let mut prev = output;
+            let mut prev_tracker = None;
+
+            for filter in branch.filters.iter_mut() {
+                let (sender, receiver) = mpsc_channel(100);
+                connect_sender(prev, sender, prev_tracker.take());
+
+                // the rollback buffer holds events instead of dropping them, so
+                // an event without output doesn't mean it won't reach the sink
+                if let filters::Bootstrapper::RollbackBuffer(_) = filter {
+                    filter.borrow_input().connect(receiver);
+                } else {
+                    let tracker = Arc::new(std::sync::Mutex::new(DropTracker::default()));
+
+                    filter.borrow_input().connect(TrackedRecv {
+                        inner: receiver,
+                        tracker: tracker.clone(),
+                        branch: index,
+                        ledger: self.stage.ledger.clone(),
+                    });
+
+                    prev_tracker = Some(tracker);
+                }
+
+                prev = filter.borrow_output();
+            }
+
+            let (sender, receiver) = mpsc_channel(100);
+            connect_sender(prev, sender, prev_tracker);
+            branch.sink.borrow_input().connect(receiver);
+
+            branch.sink.borrow_cursor().connect(AckAdapter {
+                branch: index,
+                ledger: self.stage.ledger.clone(),
+            });
+        }
+
+        if let Some(receiver) = self.cursor.take() {
+            cursor.borrow_track().connect(receiver);
+        }
+    }
+
+    pub fn spawn(self, policy: gasket::runtime::Policy) -> Vec<Tether> {
+        let mut tethers = vec![gasket::runtime::spawn_stage(self.stage, policy.clone())];
+
+        for branch in self.branches {
+            tethers.extend(branch.filters.into_iter().map(|x| x.spawn(policy.clone())));
+            tethers.push(branch.sink.spawn(policy.clone()));
+        }
+
+        tethers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, slot.to_be_bytes().to_vec())
+    }
+
+    fn ledger() -> (Ledger, ChannelRecvAdapter<Point>) {
+        let (cursor, receiver) = mpsc_channel(10);
+
+        let ledger = Ledger {
+            pending: Default::default(),
+            forwarded: None,
+            cursor,
+        };
+
+        (ledger, receiver)
+    }
+
+    #[test]
+    fn cursor_waits_for_pending_branch() {
+        let (mut ledger, _) = ledger();
+
+        ledger.dispatch(point(10), vec![true, false]);
+        ledger.dispatch(point(20), vec![false, true]);
+        assert_eq!(ledger.settle(), None);
+
+        ledger.ack(1, &point(20));
+        assert_eq!(ledger.settle(), None);
+
+        ledger.ack(0, &point(10));
+        assert_eq!(ledger.settle(), Some(point(20)));
+    }
+
+    #[test]
+    fn unrouted_events_move_cursor() {
+        let (mut ledger, _) = ledger();
+
+        ledger.dispatch(point(10), vec![false, false]);
+        assert_eq!(ledger.settle(), Some(point(10)));
+        assert_eq!(ledger.settle(), None);
+    }
+
+    #[test]
+    fn ack_covers_filtered_events() {
+        let (mut ledger, _) = ledger();
+
+        ledger.dispatch(point(10), vec![true]);
+        ledger.dispatch(point(20), vec![true]);
+        ledger.dispatch(point(30), vec![true]);
+
+        ledger.ack(0, &point(20));
+        assert_eq!(ledger.settle(), Some(point(20)));
+    }
+
+    #[test]
+    fn discard_clears_run_of_point() {
+        let (mut ledger, _) = ledger();
+
+        ledger.dispatch(point(10), vec![true, true]);
+        ledger.dispatch(point(20), vec![true, true]);
+        ledger.dispatch(point(20), vec![false, true]);
+        ledger.dispatch(point(20), vec![true, true]);
+        ledger.dispatch(point(30), vec![true, true]);
+
+        ledger.ack(1, &point(30));
+        ledger.discard(0, &point(20));
+        assert_eq!(ledger.settle(), None);
+
+        ledger.ack(0, &point(10));
+        assert_eq!(ledger.settle(), Some(point(20)));
+    }
+
+    #[test]
+    fn drop_tracker_reports_points_without_output() {
+        let mut tracker = DropTracker::default();
+
+        assert_eq!(tracker.received(&point(10)), None);
+        assert_eq!(tracker.received(&point(10)), None);
+        tracker.forwarded = true;
+        assert_eq!(tracker.received(&point(20)), None);
+        assert_eq!(tracker.received(&point(30)), Some(point(20)));
+    }
+
+    #[tokio::test]
+    async fn branch_without_matches_moves_cursor() {
+        let config: Vec<BranchConfig> = serde_json::from_value(serde_json::json!([
+            {
+                "filters": [{ "type": "Select", "predicate": "mempool" }],
+                "sink": { "type": "Noop" }
+            },
+            {
+                "sink": { "type": "Noop" }
+            }
+        ]))
+        .unwrap();
+
+        let ctx = Context {
+            chain: Default::default(),
+            intersect: IntersectConfig::Origin,
+            finalize: None,
+            current_dir: ".".into(),
+            breadcrumbs: Breadcrumbs::new(10),
+            control: Default::default(),
+        };
+
+        let mut router = Router::bootstrapper(config, &ctx).unwrap();
+
+        let mut track = gasket::messaging::InputPort::default();
+        track.connect(router.cursor.take().unwrap());
+
+        let mut source = OutputPort::default();
+        let mut cursor = cursor::Config::default().bootstrapper(&ctx).unwrap();
+        router.connect(&mut source, &mut cursor);
+
+        let ledger = router.stage.ledger.clone();
+        let _tethers = router.spawn(Default::default());
+
+        for slot in 1..=5 {
+            let evt = ChainEvent::Apply(point(slot), Record::GenericJson(serde_json::json!({})));
+            source.send(evt.into()).await.unwrap();
+        }
+
+        // the last point of the branch is settled once the next event reaches
+        // its select filter
+        let settled = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                let msg = track.recv().await.unwrap();
+
+                if msg.payload == point(4) {
+                    break;
+                }
+            }
+        })
+        .await;
+
+        assert!(settled.is_ok());
+        assert!(ledger.lock().await.pending.len() <= 1);
+    }
+}
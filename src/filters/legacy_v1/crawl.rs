@@ -17,6 +17,7 @@ impl From<pallas::ledger::traverse::Era> for Era {
             pallas::ledger::traverse::Era::Mary => Era::Mary,
             pallas::ledger::traverse::Era::Alonzo => Era::Alonzo,
             pallas::ledger::traverse::Era::Babbage => Era::Babbage,
+            pallas::ledger::traverse::Era::Conway => Era::Conway,
             _ => Era::Unknown,
         }
     }
@@ -97,6 +98,37 @@ impl EventWriter<'_> {
         Ok(())
     }
 
+    fn crawl_governance(&mut self, tx: &MultiEraTx) -> Result<(), WorkerError> {
+        let conway = match tx.as_conway() {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let body = &conway.transaction_body;
+
+        for (idx, proposal) in body
+            .proposal_procedures
+            .iter()
+            .flat_map(|x| x.iter())
+            .enumerate()
+        {
+            let mut child = self.child_writer(EventContext {
+                proposal_idx: Some(idx),
+                ..EventContext::default()
+            });
+
+            child.append_from(child.to_gov_proposal_record(tx, idx, proposal))?;
+        }
+
+        for (voter, votes) in body.voting_procedures.iter().flat_map(|x| x.iter()) {
+            for (action, procedure) in votes.iter() {
+                self.append_from(self.to_vote_record(voter, action, procedure))?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn crawl_transaction(&mut self, tx: &MultiEraTx) -> Result<(), WorkerError> {
         let record = self.to_transaction_record(tx);
         self.append_from(record.clone())?;
@@ -132,6 +164,8 @@ impl EventWriter<'_> {
             }
         }
 
+        self.crawl_governance(tx)?;
+
         for collateral in tx.collateral().iter() {
             // TODO: collateral context?
             self.crawl_collateral(collateral)?;
@@ -56,6 +56,59 @@ impl From<&alonzo::StakeCredential> for StakeCredential {
     }
 }
 
+impl From<&conway::DRep> for DRep {
+    fn from(other: &conway::DRep) -> Self {
+        match other {
+            conway::DRep::Key(x) => DRep::AddrKeyhash(x.to_hex()),
+            conway::DRep::Script(x) => DRep::Scripthash(x.to_hex()),
+            conway::DRep::Abstain => DRep::Abstain,
+            conway::DRep::NoConfidence => DRep::NoConfidence,
+        }
+    }
+}
+
+impl From<&conway::Voter> for Voter {
+    fn from(other: &conway::Voter) -> Self {
+        match other {
+            conway::Voter::ConstitutionalCommitteeKey(x) => Voter::CommitteeKeyhash(x.to_hex()),
+            conway::Voter::ConstitutionalCommitteeScript(x) => {
+                Voter::CommitteeScripthash(x.to_hex())
+            }
+            conway::Voter::DRepKey(x) => Voter::DRepKeyhash(x.to_hex()),
+            conway::Voter::DRepScript(x) => Voter::DRepScripthash(x.to_hex()),
+            conway::Voter::StakePoolKey(x) => Voter::StakePoolKeyhash(x.to_hex()),
+        }
+    }
+}
+
+impl From<&conway::Anchor> for AnchorRecord {
+    fn from(other: &conway::Anchor) -> Self {
+        AnchorRecord {
+            url: other.url.clone(),
+            data_hash: other.content_hash.to_hex(),
+        }
+    }
+}
+
+fn anchor_to_record(anchor: &Nullable<conway::Anchor>) -> Option<AnchorRecord> {
+    match anchor {
+        Nullable::Some(x) => Some(x.into()),
+        _ => None,
+    }
+}
+
+fn gov_action_kind(action: &conway::GovAction) -> &'static str {
+    match action {
+        conway::GovAction::ParameterChange(..) => "parameter_change",
+        conway::GovAction::HardForkInitiation(..) => "hard_fork_initiation",
+        conway::GovAction::TreasuryWithdrawals(..) => "treasury_withdrawals",
+        conway::GovAction::NoConfidence(..) => "no_confidence",
+        conway::GovAction::UpdateCommittee(..) => "update_committee",
+        conway::GovAction::NewConstitution(..) => "new_constitution",
+        conway::GovAction::Information => "information",
+    }
+}
+
 fn ip_string_from_bytes(bytes: &[u8]) -> String {
     format!("{}.{}.{}.{}", bytes[0], bytes[1], bytes[2], bytes[3])
 }
@@ -462,6 +515,10 @@ impl EventWriter<'_> {
     }
 
     pub fn to_certificate_event(&self, cert: &MultiEraCert) -> Option<EventData> {
+        if let Some(cert) = cert.as_conway() {
+            return Some(self.to_conway_certificate_event(cert));
+        }
+
         let evt = match cert.as_alonzo()? {
             Certificate::StakeRegistration(credential) => EventData::StakeRegistration {
                 credential: credential.into(),
@@ -529,6 +586,148 @@ impl EventWriter<'_> {
         Some(evt)
     }
 
+    pub fn to_conway_certificate_event(&self, cert: &conway::Certificate) -> EventData {
+        match cert {
+            // certificates shared with previous eras map to the same events
+            conway::Certificate::StakeRegistration(credential)
+            | conway::Certificate::Reg(credential, _) => EventData::StakeRegistration {
+                credential: credential.into(),
+            },
+            conway::Certificate::StakeDeregistration(credential)
+            | conway::Certificate::UnReg(credential, _) => EventData::StakeDeregistration {
+                credential: credential.into(),
+            },
+            conway::Certificate::StakeDelegation(credential, pool) => EventData::StakeDelegation {
+                credential: credential.into(),
+                pool_hash: pool.to_hex(),
+            },
+            conway::Certificate::PoolRegistration {
+                operator,
+                vrf_keyhash,
+                pledge,
+                cost,
+                margin,
+                reward_account,
+                pool_owners,
+                relays,
+                pool_metadata,
+            } => EventData::PoolRegistration {
+                operator: operator.to_hex(),
+                vrf_keyhash: vrf_keyhash.to_hex(),
+                pledge: *pledge,
+                cost: *cost,
+                margin: (margin.numerator as f64 / margin.denominator as f64),
+                reward_account: reward_account.to_hex(),
+                pool_owners: pool_owners.iter().map(|p| p.to_hex()).collect(),
+                relays: relays.iter().map(relay_to_string).collect(),
+                pool_metadata: match pool_metadata {
+                    Nullable::Some(x) => Some(x.url.clone()),
+                    _ => None,
+                },
+                pool_metadata_hash: match pool_metadata {
+                    Nullable::Some(x) => Some(x.hash.clone().to_hex()),
+                    _ => None,
+                },
+            },
+            conway::Certificate::PoolRetirement(pool, epoch) => EventData::PoolRetirement {
+                pool: pool.to_hex(),
+                epoch: *epoch,
+            },
+            conway::Certificate::VoteDeleg(credential, drep) => EventData::VoteDelegation {
+                credential: credential.into(),
+                drep: drep.into(),
+            },
+            conway::Certificate::StakeVoteDeleg(credential, pool, drep) => {
+                EventData::StakeVoteDelegation {
+                    credential: credential.into(),
+                    pool_hash: pool.to_hex(),
+                    drep: drep.into(),
+                }
+            }
+            conway::Certificate::StakeRegDeleg(credential, pool, deposit) => {
+                EventData::StakeRegistrationDelegation {
+                    credential: credential.into(),
+                    pool_hash: Some(pool.to_hex()),
+                    drep: None,
+                    deposit: *deposit,
+                }
+            }
+            conway::Certificate::VoteRegDeleg(credential, drep, deposit) => {
+                EventData::StakeRegistrationDelegation {
+                    credential: credential.into(),
+                    pool_hash: None,
+                    drep: Some(drep.into()),
+                    deposit: *deposit,
+                }
+            }
+            conway::Certificate::StakeVoteRegDeleg(credential, pool, drep, deposit) => {
+                EventData::StakeRegistrationDelegation {
+                    credential: credential.into(),
+                    pool_hash: Some(pool.to_hex()),
+                    drep: Some(drep.into()),
+                    deposit: *deposit,
+                }
+            }
+            conway::Certificate::AuthCommitteeHot(cold, hot) => EventData::CommitteeHotKeyAuth {
+                cold_credential: cold.into(),
+                hot_credential: hot.into(),
+            },
+            conway::Certificate::ResignCommitteeCold(cold, anchor) => EventData::CommitteeResign {
+                cold_credential: cold.into(),
+                anchor: anchor_to_record(anchor),
+            },
+            conway::Certificate::RegDRepCert(credential, deposit, anchor) => {
+                EventData::DRepRegistration {
+                    credential: credential.into(),
+                    deposit: *deposit,
+                    anchor: anchor_to_record(anchor),
+                }
+            }
+            conway::Certificate::UnRegDRepCert(credential, refund) => EventData::DRepRetirement {
+                credential: credential.into(),
+                refund: *refund,
+            },
+            conway::Certificate::UpdateDRepCert(credential, anchor) => EventData::DRepUpdate {
+                credential: credential.into(),
+                anchor: anchor_to_record(anchor),
+            },
+        }
+    }
+
+    pub fn to_gov_proposal_record(
+        &self,
+        tx: &MultiEraTx,
+        idx: usize,
+        proposal: &conway::ProposalProcedure,
+    ) -> GovProposalRecord {
+        GovProposalRecord {
+            action_id: format!("{}#{}", tx.hash(), idx),
+            deposit: proposal.deposit,
+            reward_account: proposal.reward_account.to_hex(),
+            action_kind: gov_action_kind(&proposal.gov_action).to_string(),
+            action: serde_json::to_value(&proposal.gov_action).unwrap_or_default(),
+            anchor: (&proposal.anchor).into(),
+        }
+    }
+
+    pub fn to_vote_record(
+        &self,
+        voter: &conway::Voter,
+        action: &conway::GovActionId,
+        procedure: &conway::VotingProcedure,
+    ) -> VoteRecord {
+        VoteRecord {
+            voter: voter.into(),
+            action_id: format!("{}#{}", action.transaction_id, action.action_index),
+            vote: match procedure.vote {
+                conway::Vote::No => "no".to_string(),
+                conway::Vote::Yes => "yes".to_string(),
+                conway::Vote::Abstain => "abstain".to_string(),
+            },
+            anchor: anchor_to_record(&procedure.anchor),
+        }
+    }
+
     pub fn to_collateral_event(&self, collateral: &MultiEraInput) -> EventData {
         EventData::Collateral {
             tx_id: collateral.hash().to_string(),
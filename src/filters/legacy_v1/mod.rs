@@ -100,3 +100,144 @@ impl Config {
         Ok(stage)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pallas::codec::utils::{NonEmptyKeyValuePairs, NonEmptySet, Nullable};
+    use pallas::crypto::hash::Hash;
+    use pallas::ledger::primitives::{conway, StakeCredential};
+
+    use super::*;
+    use crate::filters::testing::*;
+    use crate::framework::legacy_v1::*;
+
+    fn proposal() -> conway::ProposalProcedure {
+        conway::ProposalProcedure {
+            deposit: 1000,
+            reward_account: [vec![0xe1], vec![1; 28]].concat().into(),
+            gov_action: conway::GovAction::Information,
+            anchor: conway::Anchor {
+                url: "https://example.com".into(),
+                content_hash: Hash::new([2; 32]),
+            },
+        }
+    }
+
+    #[test]
+    fn conway_governance_events() {
+        let config = Config::default();
+        let stage = config
+            .clone()
+            .bootstrapper(&Context {
+                chain: Default::default(),
+                intersect: IntersectConfig::Origin,
+                finalize: None,
+                current_dir: ".".into(),
+                breadcrumbs: Breadcrumbs::new(10),
+                control: Default::default(),
+            })
+            .unwrap();
+
+        let credential = StakeCredential::AddrKeyhash(Hash::new([3; 28]));
+
+        let mut body = tx(vec![], vec![output(100)]);
+        body.certificates = NonEmptySet::from_vec(vec![
+            conway::Certificate::VoteDeleg(credential.clone(), conway::DRep::Abstain),
+            conway::Certificate::RegDRepCert(credential.clone(), 500, Nullable::Null),
+        ]);
+        body.proposal_procedures = NonEmptySet::from_vec(vec![proposal(), proposal()]);
+        body.voting_procedures = NonEmptyKeyValuePairs::from_vec(vec![(
+            conway::Voter::DRepKey(Hash::new([3; 28])),
+            NonEmptyKeyValuePairs::from_vec(vec![(
+                conway::GovActionId {
+                    transaction_id: Hash::new([4; 32]),
+                    action_index: 1,
+                },
+                conway::VotingProcedure {
+                    vote: conway::Vote::Yes,
+                    anchor: Nullable::Null,
+                },
+            )])
+            .unwrap(),
+        )]);
+
+        let hash = tx_hash(&body);
+        let (point, record) = block(1, vec![body]);
+
+        let Record::CborBlock(cbor) = record else {
+            unreachable!()
+        };
+
+        let mut buffer = Vec::new();
+        let mut writer = EventWriter::new(
+            point,
+            &stage.output,
+            &stage.config,
+            &stage.genesis,
+            &mut buffer,
+        );
+        writer.crawl_cbor(&cbor).unwrap();
+
+        let events: Vec<_> = buffer
+            .into_iter()
+            .filter_map(|x| match x {
+                ChainEvent::Apply(_, Record::OuraV1Event(x)) => Some(x),
+                _ => None,
+            })
+            .collect();
+
+        let certificates: Vec<_> = events
+            .iter()
+            .filter(|x| x.context.certificate_idx.is_some())
+            .collect();
+
+        assert!(matches!(
+            certificates[..],
+            [
+                Event {
+                    data: EventData::VoteDelegation { .. },
+                    ..
+                },
+                Event {
+                    data: EventData::DRepRegistration { deposit: 500, .. },
+                    ..
+                },
+            ]
+        ));
+
+        let proposals: Vec<_> = events
+            .iter()
+            .filter_map(|x| match &x.data {
+                EventData::GovProposal(p) => Some((x.context.proposal_idx, p.action_id.clone())),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            proposals,
+            vec![
+                (Some(0), format!("{hash}#0")),
+                (Some(1), format!("{hash}#1")),
+            ]
+        );
+
+        let votes: Vec<_> = events
+            .iter()
+            .filter_map(|x| match &x.data {
+                EventData::Vote(v) => {
+                    Some((x.context.proposal_idx, v.action_id.clone(), v.vote.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            votes,
+            vec![(
+                None,
+                format!("{}#1", Hash::<32>::new([4; 32])),
+                "yes".to_string()
+            )]
+        );
+    }
+}
@@ -7,10 +7,14 @@ pub mod into_json;
 pub mod legacy_v1;
 pub mod noop;
 pub mod parse_cbor;
+pub mod reset_to_undo;
 pub mod rollback_buffer;
 pub mod select;
 pub mod split_block;
 
+#[cfg(test)]
+pub(crate) mod testing;
+
 #[cfg(feature = "wasm")]
 pub mod wasm_plugin;
 
@@ -23,6 +27,7 @@ pub enum Bootstrapper {
     ParseCbor(parse_cbor::Stage),
     Select(select::Stage),
     RollbackBuffer(rollback_buffer::Stage),
+    ResetToUndo(reset_to_undo::Stage),
 
     #[cfg(feature = "wasm")]
     WasmPlugin(wasm_plugin::Stage),
@@ -38,6 +43,7 @@ impl Bootstrapper {
             Bootstrapper::ParseCbor(p) => &mut p.input,
             Bootstrapper::Select(p) => &mut p.input,
             Bootstrapper::RollbackBuffer(p) => &mut p.input,
+            Bootstrapper::ResetToUndo(p) => &mut p.input,
 
             #[cfg(feature = "wasm")]
             Bootstrapper::WasmPlugin(p) => &mut p.input,
@@ -53,6 +59,7 @@ impl Bootstrapper {
             Bootstrapper::ParseCbor(p) => &mut p.output,
             Bootstrapper::Select(p) => &mut p.output,
             Bootstrapper::RollbackBuffer(p) => &mut p.output,
+            Bootstrapper::ResetToUndo(p) => &mut p.output,
 
             #[cfg(feature = "wasm")]
             Bootstrapper::WasmPlugin(p) => &mut p.output,
@@ -68,6 +75,7 @@ impl Bootstrapper {
             Bootstrapper::ParseCbor(x) => gasket::runtime::spawn_stage(x, policy),
             Bootstrapper::Select(x) => gasket::runtime::spawn_stage(x, policy),
             Bootstrapper::RollbackBuffer(x) => gasket::runtime::spawn_stage(x, policy),
+            Bootstrapper::ResetToUndo(x) => gasket::runtime::spawn_stage(x, policy),
 
             #[cfg(feature = "wasm")]
             Bootstrapper::WasmPlugin(x) => gasket::runtime::spawn_stage(x, policy),
@@ -85,6 +93,7 @@ pub enum Config {
     ParseCbor(parse_cbor::Config),
     Select(select::Config),
     RollbackBuffer(rollback_buffer::Config),
+    ResetToUndo(reset_to_undo::Config),
 
     #[cfg(feature = "wasm")]
     WasmPlugin(wasm_plugin::Config),
@@ -100,6 +109,7 @@ impl Config {
             Config::ParseCbor(c) => Ok(Bootstrapper::ParseCbor(c.bootstrapper(ctx)?)),
             Config::Select(c) => Ok(Bootstrapper::Select(c.bootstrapper(ctx)?)),
             Config::RollbackBuffer(c) => Ok(Bootstrapper::RollbackBuffer(c.bootstrapper(ctx)?)),
+            Config::ResetToUndo(c) => Ok(Bootstrapper::ResetToUndo(c.bootstrapper(ctx)?)),
 
             #[cfg(feature = "wasm")]
             Config::WasmPlugin(c) => Ok(Bootstrapper::WasmPlugin(c.bootstrapper(ctx)?)),
@@ -1,34 +1,50 @@
 //! A filter that turns raw cbor Tx into the corresponding parsed representation
 
+use std::sync::{Arc, RwLock};
+
 use gasket::framework::*;
 use serde::Deserialize;
 
 use pallas::interop::utxorpc::{self as interop};
 use pallas::ledger::traverse as trv;
+use pallas::network::miniprotocols::Point;
 
 use crate::framework::*;
 
-#[derive(Clone, Default)]
-struct NoOpContext;
+mod store;
 
-impl interop::LedgerContext for NoOpContext {
-    fn get_utxos(&self, _refs: &[interop::TxoRef]) -> Option<interop::UtxoMap> {
-        None
-    }
-}
+use store::{StoreContext, UtxoStore};
 
-#[derive(Default, Stage)]
+#[derive(Stage)]
 #[stage(name = "filter-parse-cbor", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
     pub input: FilterInputPort,
     pub output: FilterOutputPort,
 
-    mapper: interop::Mapper<NoOpContext>,
+    mapper: interop::Mapper<StoreContext>,
+    store: Option<Arc<RwLock<UtxoStore>>>,
 
     #[metric]
     ops_count: gasket::metrics::Counter,
 }
 
+impl Stage {
+    fn update_store(
+        &self,
+        f: impl FnOnce(&mut UtxoStore) -> Result<(), Error>,
+    ) -> Result<(), WorkerError> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+
+        let mut store = store.write().or_panic()?;
+        f(&mut store).or_panic()?;
+        store.commit().or_panic()?;
+
+        Ok(())
+    }
+}
+
 #[derive(Default)]
 pub struct Worker;
 
@@ -38,7 +54,36 @@ impl From<&Stage> for Worker {
     }
 }
 
+/// Updates the utxo store with the txs contained in the record, if any
+fn apply_to_store(stage: &Stage, point: &Point, record: &Record) -> Result<(), WorkerError> {
+    match record {
+        Record::CborBlock(cbor) => {
+            let block = trv::MultiEraBlock::decode(cbor).or_panic()?;
+            stage.update_store(|x| x.apply(point, &block.txs()))
+        }
+        Record::CborTx(cbor) => {
+            let tx = trv::MultiEraTx::decode(cbor).or_panic()?;
+            stage.update_store(|x| x.apply(point, &[tx]))
+        }
+        _ => Ok(()),
+    }
+}
+
 gasket::impl_mapper!(|_worker: Worker, stage: Stage, unit: ChainEvent| => {
+    // the store is updated before mapping applied records and after mapping
+    // undone ones, so that the inputs of the tx can be resolved in both cases
+    match &unit {
+        ChainEvent::Apply(point, record) => {
+            apply_to_store(stage, point, record)?;
+        }
+        ChainEvent::Reset(point) => {
+            stage.update_store(|x| x.reset(point))?;
+        }
+        // undone records are handled after mapping, mempool txs aren't
+        // confirmed so they don't change the store
+        ChainEvent::Undo(..) | ChainEvent::Pending(..) | ChainEvent::Removed(..) => (),
+    }
+
     let output = unit.clone().try_map_record(|r| match r {
         Record::CborBlock(cbor) => {
             let block = trv::MultiEraBlock::decode(&cbor).or_panic()?;
@@ -53,16 +98,178 @@ gasket::impl_mapper!(|_worker: Worker, stage: Stage, unit: ChainEvent| => {
         x => Ok(x),
     })?;
 
+    if let ChainEvent::Undo(point, Record::CborBlock(_) | Record::CborTx(_)) = &unit {
+        stage.update_store(|x| x.undo(point))?;
+    }
+
     stage.ops_count.inc(1);
 
     output
 });
 
 #[derive(Default, Deserialize)]
-pub struct Config {}
+pub struct Config {
+    pub utxo_store: Option<store::Config>,
+}
 
 impl Config {
     pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
-        Ok(Stage::default())
+        let store = match self.utxo_store {
+            Some(config) => Some(Arc::new(RwLock::new(config.open()?))),
+            None => None,
+        };
+
+        let stage = Stage {
+            mapper: interop::Mapper::new(StoreContext(store.clone())),
+            store,
+            ops_count: Default::default(),
+            input: Default::default(),
+            output: Default::default(),
+        };
+
+        Ok(stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gasket::framework::Worker as _;
+    use gasket::messaging::{InputPort, OutputPort};
+    use pallas::crypto::hash::Hash;
+
+    use super::*;
+    use crate::filters::testing::*;
+
+    fn stage() -> (Stage, InputPort<ChainEvent>) {
+        let config = Config {
+            utxo_store: Some(Default::default()),
+        };
+
+        let mut stage = config
+            .bootstrapper(&Context {
+                chain: Default::default(),
+                intersect: IntersectConfig::Origin,
+                finalize: None,
+                current_dir: ".".into(),
+                breadcrumbs: Breadcrumbs::new(10),
+                control: Default::default(),
+            })
+            .unwrap();
+
+        let mut events = InputPort::default();
+        gasket::messaging::tokio::connect_ports(&mut stage.output, &mut events, 100);
+
+        // the input isn't used, units are executed directly
+        let mut input = OutputPort::default();
+        gasket::messaging::tokio::connect_ports(&mut input, &mut stage.input, 100);
+
+        (stage, events)
+    }
+
+    async fn execute(
+        stage: &mut Stage,
+        events: &mut InputPort<ChainEvent>,
+        unit: ChainEvent,
+    ) -> ParsedBlock {
+        let mut worker = Worker::bootstrap(stage).await.unwrap();
+        worker.execute(&unit, stage).await.unwrap();
+
+        match events.recv().await.unwrap().payload {
+            ChainEvent::Apply(_, Record::ParsedBlock(x)) => x,
+            ChainEvent::Undo(_, Record::ParsedBlock(x)) => x,
+            _ => panic!("expected a parsed block"),
+        }
+    }
+
+    /// The coins of the resolved outputs of each input of each tx
+    fn resolved(block: &ParsedBlock) -> Vec<Vec<Option<u64>>> {
+        block
+            .body
+            .iter()
+            .flat_map(|x| &x.tx)
+            .map(|tx| {
+                tx.inputs
+                    .iter()
+                    .map(|x| x.as_output.as_ref().map(|o| o.coin))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn inputs_are_resolved_from_store() {
+        let (mut stage, mut events) = stage();
+
+        let genesis = tx(vec![], vec![output(100)]);
+        let (point, record) = block(1, vec![genesis.clone()]);
+        execute(&mut stage, &mut events, ChainEvent::Apply(point, record)).await;
+
+        // the second tx spends an output of the first one, in the same block
+        let first = tx(
+            vec![input(tx_hash(&genesis), 0)],
+            vec![output(60), output(40)],
+        );
+        let second = tx(vec![input(tx_hash(&first), 1)], vec![output(40)]);
+        let unknown = tx(vec![input(Hash::new([9; 32]), 0)], vec![]);
+
+        let (point, record) = block(2, vec![first, second, unknown]);
+        let parsed = execute(&mut stage, &mut events, ChainEvent::Apply(point, record)).await;
+
+        assert_eq!(
+            resolved(&parsed),
+            vec![vec![Some(100)], vec![Some(40)], vec![None]]
+        );
+    }
+
+    #[tokio::test]
+    async fn undo_restores_consumed_outputs() {
+        let (mut stage, mut events) = stage();
+
+        let genesis = tx(vec![], vec![output(100)]);
+        let (point, record) = block(1, vec![genesis.clone()]);
+        execute(&mut stage, &mut events, ChainEvent::Apply(point, record)).await;
+
+        let spend = tx(vec![input(tx_hash(&genesis), 0)], vec![output(100)]);
+        let (point, record) = block(2, vec![spend.clone()]);
+        execute(
+            &mut stage,
+            &mut events,
+            ChainEvent::Apply(point.clone(), record.clone()),
+        )
+        .await;
+
+        // undone records are still resolved, then the store is reverted
+        let undone = execute(&mut stage, &mut events, ChainEvent::Undo(point, record)).await;
+        assert_eq!(resolved(&undone), vec![vec![Some(100)]]);
+
+        let (point, record) = block(3, vec![spend]);
+        let parsed = execute(&mut stage, &mut events, ChainEvent::Apply(point, record)).await;
+        assert_eq!(resolved(&parsed), vec![vec![Some(100)]]);
+    }
+
+    #[tokio::test]
+    async fn repeated_records_are_applied_once() {
+        let (mut stage, mut events) = stage();
+
+        let genesis = tx(vec![], vec![output(100)]);
+        let (point, record) = block(1, vec![genesis.clone()]);
+        execute(&mut stage, &mut events, ChainEvent::Apply(point, record)).await;
+
+        // the same block goes through the filter twice, eg: after a restart
+        let spend = tx(vec![input(tx_hash(&genesis), 0)], vec![output(100)]);
+        let (point, record) = block(2, vec![spend.clone()]);
+
+        for _ in 0..2 {
+            let unit = ChainEvent::Apply(point.clone(), record.clone());
+            let parsed = execute(&mut stage, &mut events, unit).await;
+            assert_eq!(resolved(&parsed), vec![vec![Some(100)]]);
+        }
+
+        // a single undo reverts the block
+        execute(&mut stage, &mut events, ChainEvent::Undo(point, record)).await;
+
+        let (point, record) = block(3, vec![spend]);
+        let parsed = execute(&mut stage, &mut events, ChainEvent::Apply(point, record)).await;
+        assert_eq!(resolved(&parsed), vec![vec![Some(100)]]);
     }
 }
@@ -0,0 +1,543 @@
+//! A local UTxO store used to resolve the inputs of parsed txs
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use pallas::crypto::hash::Hash;
+use pallas::interop::utxorpc::{self as interop, EraCbor, TxoRef, UtxoMap};
+use pallas::ledger::traverse as trv;
+use pallas::network::miniprotocols::Point;
+use serde::{Deserialize, Serialize};
+
+use crate::framework::journal::{point_from_data, point_to_data, Journal, PointData};
+use crate::framework::*;
+
+const DEFAULT_MAX_DEPTH: usize = 2160;
+const DEFAULT_MAX_UTXOS: usize = 1_000_000;
+
+type TxoRefData = (String, u32);
+type UtxoData = (TxoRefData, u16, String);
+
+fn txo_ref_to_data(txo: &TxoRef) -> TxoRefData {
+    (txo.0.to_string(), txo.1)
+}
+
+fn txo_ref_from_data(data: TxoRefData) -> Result<TxoRef, Error> {
+    let hash: Hash<32> = data.0.parse().map_err(Error::parse)?;
+    Ok((hash, data.1))
+}
+
+fn utxo_to_data(txo: &TxoRef, utxo: &EraCbor) -> UtxoData {
+    (txo_ref_to_data(txo), utxo.0.into(), hex::encode(&utxo.1))
+}
+
+fn utxo_from_data(data: UtxoData) -> Result<(TxoRef, EraCbor), Error> {
+    let txo = txo_ref_from_data(data.0)?;
+    let era = trv::Era::try_from(data.1).map_err(Error::parse)?;
+    let cbor = hex::decode(data.2).map_err(Error::parse)?;
+
+    Ok((txo, (era, cbor)))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Entry {
+    /// Txs applied at a point, with the utxos they produce and consume
+    Apply(PointData, Vec<String>, Vec<UtxoData>, Vec<TxoRefData>),
+    Undo(PointData),
+    Reset(PointData),
+
+    /// A utxo of the set, written when compacting the journal
+    Utxo(UtxoData),
+
+    /// A change that can still be reverted, written when compacting the journal
+    Track(PointData, Vec<String>, Vec<TxoRefData>, Vec<UtxoData>),
+}
+
+/// The changes to the utxo set caused by the txs of a single record
+#[derive(Default)]
+struct Delta {
+    txs: Vec<Hash<32>>,
+    produced: Vec<TxoRef>,
+    consumed: HashMap<TxoRef, EraCbor>,
+}
+
+/// Utxo cache built from the txs that go through the pipeline
+///
+/// The set is bounded, once it holds more than `max_utxos` entries the oldest
+/// ones are dropped and the inputs that consume them can't be resolved
+/// anymore. The most recent changes are kept around so that they can be
+/// reverted when the chain rolls back.
+pub struct UtxoStore {
+    utxos: HashMap<TxoRef, EraCbor>,
+    order: VecDeque<TxoRef>,
+    deltas: VecDeque<(Point, Vec<Delta>)>,
+    max_depth: usize,
+    max_utxos: usize,
+    journal: Option<Journal>,
+}
+
+impl UtxoStore {
+    fn new(max_depth: usize, max_utxos: usize) -> Self {
+        Self {
+            utxos: Default::default(),
+            order: Default::default(),
+            deltas: Default::default(),
+            max_depth,
+            max_utxos,
+            journal: None,
+        }
+    }
+
+    fn open(path: PathBuf, max_depth: usize, max_utxos: usize) -> Result<Self, Error> {
+        let mut store = Self::new(max_depth, max_utxos);
+
+        let journal = Journal::open(path, |entry| store.replay(entry))?;
+
+        store.journal = Some(journal);
+        store.compact()?;
+
+        Ok(store)
+    }
+
+    fn replay(&mut self, entry: Entry) -> Result<(), Error> {
+        match entry {
+            Entry::Apply(point, txs, produced, consumed) => {
+                let txs = txs
+                    .iter()
+                    .map(|x| x.parse().map_err(Error::parse))
+                    .collect::<Result<_, _>>()?;
+
+                let produced = produced
+                    .into_iter()
+                    .map(utxo_from_data)
+                    .collect::<Result<_, _>>()?;
+
+                let consumed = consumed
+                    .into_iter()
+                    .map(txo_ref_from_data)
+                    .collect::<Result<_, _>>()?;
+
+                self.apply_delta(point_from_data(point)?, txs, produced, consumed);
+            }
+            Entry::Undo(point) => self.undo_delta(&point_from_data(point)?),
+            Entry::Reset(point) => self.reset_deltas(&point_from_data(point)?),
+            Entry::Utxo(utxo) => {
+                let (txo, utxo) = utxo_from_data(utxo)?;
+                self.insert(txo, utxo);
+            }
+            Entry::Track(point, txs, produced, consumed) => {
+                let delta = Delta {
+                    txs: txs
+                        .iter()
+                        .map(|x| x.parse().map_err(Error::parse))
+                        .collect::<Result<_, _>>()?,
+                    produced: produced
+                        .into_iter()
+                        .map(txo_ref_from_data)
+                        .collect::<Result<_, _>>()?,
+                    consumed: consumed
+                        .into_iter()
+                        .map(utxo_from_data)
+                        .collect::<Result<_, _>>()?,
+                };
+
+                self.track_delta(point_from_data(point)?, delta);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, txo: TxoRef, utxo: EraCbor) {
+        self.utxos.insert(txo, utxo);
+        self.order.push_back(txo);
+
+        while self.utxos.len() > self.max_utxos {
+            match self.order.pop_front() {
+                Some(oldest) => self.utxos.remove(&oldest),
+                None => break,
+            };
+        }
+
+        // refs of consumed utxos are left behind, drop them once they pile up
+        if self.order.len() > self.max_utxos * 2 {
+            let utxos = &self.utxos;
+            self.order.retain(|x| utxos.contains_key(x));
+        }
+    }
+
+    fn track_delta(&mut self, point: Point, delta: Delta) {
+        match self.deltas.back_mut() {
+            Some((last, deltas)) if *last == point => deltas.push(delta),
+            _ => self.deltas.push_back((point, vec![delta])),
+        }
+
+        while self.deltas.len() > self.max_depth {
+            self.deltas.pop_front();
+        }
+    }
+
+    fn apply_delta(
+        &mut self,
+        point: Point,
+        txs: Vec<Hash<32>>,
+        produced: Vec<(TxoRef, EraCbor)>,
+        consumed: Vec<TxoRef>,
+    ) {
+        let mut delta = Delta {
+            txs,
+            ..Default::default()
+        };
+
+        for (txo, utxo) in produced {
+            delta.produced.push(txo);
+            self.insert(txo, utxo);
+        }
+
+        for txo in consumed {
+            if let Some(utxo) = self.utxos.remove(&txo) {
+                delta.consumed.insert(txo, utxo);
+            }
+        }
+
+        self.track_delta(point, delta);
+    }
+
+    fn revert(&mut self, delta: Delta) {
+        // consumed utxos are restored first, some of them might have been produced
+        // by the same txs
+        for (txo, utxo) in delta.consumed {
+            self.insert(txo, utxo);
+        }
+
+        for txo in delta.produced {
+            self.utxos.remove(&txo);
+        }
+    }
+
+    fn undo_delta(&mut self, point: &Point) {
+        let delta = match self.deltas.back_mut() {
+            Some((last, deltas)) if last == point => deltas.pop(),
+            _ => None,
+        };
+
+        if let Some(delta) = delta {
+            self.revert(delta);
+        }
+
+        if self.deltas.back().is_some_and(|(_, x)| x.is_empty()) {
+            self.deltas.pop_back();
+        }
+    }
+
+    fn reset_deltas(&mut self, point: &Point) {
+        while let Some((last, _)) = self.deltas.back() {
+            if last.slot_or_default() <= point.slot_or_default() {
+                break;
+            }
+
+            let (_, deltas) = self.deltas.pop_back().unwrap();
+
+            for delta in deltas.into_iter().rev() {
+                self.revert(delta);
+            }
+        }
+    }
+
+    fn record(&mut self, entry: Entry) -> Result<(), Error> {
+        match self.journal.as_mut() {
+            Some(journal) => journal.append(&entry),
+            None => Ok(()),
+        }
+    }
+
+    /// Rewrites the journal if it grew too big compared to the current state
+    fn compact(&mut self) -> Result<(), Error> {
+        let threshold = (self.utxos.len() * 2).max(10_000);
+
+        match &self.journal {
+            Some(x) if x.lines() > threshold => self.rewrite(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Replaces the journal with the entries needed to rebuild the current state
+    fn rewrite(&mut self) -> Result<(), Error> {
+        let Some(journal) = self.journal.as_mut() else {
+            return Ok(());
+        };
+
+        // utxos are written from the oldest, so that they are dropped in the same
+        // order once the journal is replayed
+        let utxos = &self.utxos;
+        self.order.retain(|x| utxos.contains_key(x));
+
+        let utxos = self
+            .order
+            .iter()
+            .filter_map(|txo| Some(Entry::Utxo(utxo_to_data(txo, utxos.get(txo)?))));
+
+        let deltas = self.deltas.iter().flat_map(|(point, deltas)| {
+            deltas.iter().map(|delta| {
+                Entry::Track(
+                    point_to_data(point),
+                    delta.txs.iter().map(|x| x.to_string()).collect(),
+                    delta.produced.iter().map(txo_ref_to_data).collect(),
+                    delta
+                        .consumed
+                        .iter()
+                        .map(|(txo, utxo)| utxo_to_data(txo, utxo))
+                        .collect(),
+                )
+            })
+        });
+
+        journal.rewrite(utxos.chain(deltas))
+    }
+
+    /// Tells if the tx was already applied to the set, which happens when a
+    /// record is processed again (eg: after a restart from an older cursor)
+    ///
+    /// Records older than the latest change are assumed to be applied already,
+    /// rollbacks are expected to go through `undo` or `reset` first.
+    fn is_applied(&self, point: &Point, tx: &Hash<32>) -> bool {
+        match self.deltas.back() {
+            Some((last, _)) if last.slot_or_default() > point.slot_or_default() => true,
+            Some((last, deltas)) if last == point => deltas.iter().any(|x| x.txs.contains(tx)),
+            _ => false,
+        }
+    }
+
+    /// Updates the set with the utxos produced and consumed by the txs
+    ///
+    /// Txs that were already applied at the same point are skipped, so the same
+    /// record can be applied more than once.
+    pub fn apply(&mut self, point: &Point, txs: &[trv::MultiEraTx]) -> Result<(), Error> {
+        let txs: Vec<_> = txs
+            .iter()
+            .filter(|tx| !self.is_applied(point, &tx.hash()))
+            .collect();
+
+        if txs.is_empty() {
+            return Ok(());
+        }
+
+        let mut produced = vec![];
+        let mut consumed = vec![];
+
+        for tx in txs.iter() {
+            for (idx, output) in tx.produces() {
+                let txo = (tx.hash(), idx as u32);
+                produced.push((txo, (output.era(), output.encode())));
+            }
+
+            for input in tx.consumes() {
+                consumed.push((*input.hash(), input.index() as u32));
+            }
+        }
+
+        let txs: Vec<_> = txs.iter().map(|x| x.hash()).collect();
+
+        self.record(Entry::Apply(
+            point_to_data(point),
+            txs.iter().map(|x| x.to_string()).collect(),
+            produced.iter().map(|(t, u)| utxo_to_data(t, u)).collect(),
+            consumed.iter().map(txo_ref_to_data).collect(),
+        ))?;
+
+        self.apply_delta(point.clone(), txs, produced, consumed);
+
+        Ok(())
+    }
+
+    /// Reverts the latest change, if it was applied at the given point
+    pub fn undo(&mut self, point: &Point) -> Result<(), Error> {
+        self.record(Entry::Undo(point_to_data(point)))?;
+        self.undo_delta(point);
+
+        Ok(())
+    }
+
+    /// Reverts every change applied after the given point
+    pub fn reset(&mut self, point: &Point) -> Result<(), Error> {
+        self.record(Entry::Reset(point_to_data(point)))?;
+        self.reset_deltas(point);
+
+        Ok(())
+    }
+
+    /// Makes sure every change so far is persisted to disk
+    pub fn commit(&mut self) -> Result<(), Error> {
+        self.compact()?;
+
+        match self.journal.as_mut() {
+            Some(journal) => journal.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn resolve(&self, txo: &TxoRef) -> Option<&EraCbor> {
+        // utxos consumed by the latest change are still visible, so that the txs
+        // that consumed them can be resolved after the set was updated
+        let latest = self.deltas.back().and_then(|(_, x)| x.last());
+
+        self.utxos
+            .get(txo)
+            .or_else(|| latest.and_then(|x| x.consumed.get(txo)))
+    }
+}
+
+/// Ledger context backed by a shared utxo store, if one is configured
+#[derive(Clone, Default)]
+pub struct StoreContext(pub Option<Arc<RwLock<UtxoStore>>>);
+
+impl interop::LedgerContext for StoreContext {
+    fn get_utxos(&self, refs: &[TxoRef]) -> Option<UtxoMap> {
+        let store = self.0.as_ref()?.read().ok()?;
+
+        let utxos = refs
+            .iter()
+            .filter_map(|txo| store.resolve(txo).map(|utxo| (*txo, utxo.clone())))
+            .collect();
+
+        Some(utxos)
+    }
+}
+
+#[derive(Default, Deserialize)]
+pub struct Config {
+    pub path: Option<PathBuf>,
+    pub max_depth: Option<usize>,
+    pub max_utxos: Option<usize>,
+}
+
+impl Config {
+    pub fn open(self) -> Result<UtxoStore, Error> {
+        let max_depth = self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+        let max_utxos = self.max_utxos.unwrap_or(DEFAULT_MAX_UTXOS);
+
+        match self.path {
+            Some(path) => UtxoStore::open(path, max_depth, max_utxos),
+            None => Ok(UtxoStore::new(max_depth, max_utxos)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txo(seed: u8, idx: u32) -> TxoRef {
+        (Hash::new([seed; 32]), idx)
+    }
+
+    fn utxo(seed: u8) -> EraCbor {
+        (trv::Era::Conway, vec![seed])
+    }
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, slot.to_be_bytes().to_vec())
+    }
+
+    #[test]
+    fn consumed_utxos_remain_resolvable_for_latest_change() {
+        let mut store = UtxoStore::new(10, 10);
+
+        store.apply_delta(point(1), vec![], vec![(txo(1, 0), utxo(1))], vec![]);
+        store.apply_delta(
+            point(2),
+            vec![],
+            vec![(txo(2, 0), utxo(2))],
+            vec![txo(1, 0)],
+        );
+
+        assert!(!store.utxos.contains_key(&txo(1, 0)));
+        assert_eq!(store.resolve(&txo(1, 0)), Some(&utxo(1)));
+
+        store.apply_delta(point(3), vec![], vec![], vec![txo(2, 0)]);
+        assert_eq!(store.resolve(&txo(1, 0)), None);
+    }
+
+    #[test]
+    fn reset_reverts_changes_after_point() {
+        let mut store = UtxoStore::new(10, 10);
+
+        store.apply_delta(point(1), vec![], vec![(txo(1, 0), utxo(1))], vec![]);
+        store.apply_delta(
+            point(2),
+            vec![],
+            vec![(txo(2, 0), utxo(2))],
+            vec![txo(1, 0)],
+        );
+        store.apply_delta(
+            point(2),
+            vec![],
+            vec![(txo(3, 0), utxo(3))],
+            vec![txo(2, 0)],
+        );
+
+        store.reset_deltas(&point(1));
+
+        assert_eq!(store.utxos.len(), 1);
+        assert_eq!(store.utxos.get(&txo(1, 0)), Some(&utxo(1)));
+    }
+
+    #[test]
+    fn journal_rebuilds_set() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("utxos");
+
+        let mut store = UtxoStore::open(path.clone(), 10, 10).unwrap();
+        store
+            .record(Entry::Apply(
+                point_to_data(&point(1)),
+                vec![],
+                vec![utxo_to_data(&txo(1, 0), &utxo(1))],
+                vec![],
+            ))
+            .unwrap();
+        store.record(Entry::Undo(point_to_data(&point(1)))).unwrap();
+        store
+            .record(Entry::Apply(
+                point_to_data(&point(2)),
+                vec![],
+                vec![utxo_to_data(&txo(2, 0), &utxo(2))],
+                vec![],
+            ))
+            .unwrap();
+        store.commit().unwrap();
+
+        let store = UtxoStore::open(path, 10, 10).unwrap();
+
+        assert_eq!(store.utxos.len(), 1);
+        assert_eq!(store.resolve(&txo(2, 0)), Some(&utxo(2)));
+    }
+
+    #[test]
+    fn oldest_utxos_are_dropped_over_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("utxos");
+
+        let mut store = UtxoStore::open(path.clone(), 10, 2).unwrap();
+
+        store.apply_delta(point(1), vec![], vec![(txo(1, 0), utxo(1))], vec![]);
+        store.apply_delta(point(2), vec![], vec![(txo(2, 0), utxo(2))], vec![]);
+        store.apply_delta(point(3), vec![], vec![(txo(3, 0), utxo(3))], vec![]);
+
+        assert_eq!(store.resolve(&txo(1, 0)), None);
+        assert_eq!(store.resolve(&txo(2, 0)), Some(&utxo(2)));
+        assert_eq!(store.resolve(&txo(3, 0)), Some(&utxo(3)));
+
+        // the journal keeps the order in which utxos were added
+        store.rewrite().unwrap();
+        drop(store);
+
+        let mut store = UtxoStore::open(path, 10, 2).unwrap();
+        store.apply_delta(point(4), vec![], vec![(txo(4, 0), utxo(4))], vec![]);
+
+        assert_eq!(store.resolve(&txo(2, 0)), None);
+        assert_eq!(store.resolve(&txo(3, 0)), Some(&utxo(3)));
+    }
+}
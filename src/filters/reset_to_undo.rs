@@ -0,0 +1,278 @@
+//! Turns reset events into undo events for each of the records rolled back
+
+use std::path::PathBuf;
+
+use gasket::framework::*;
+use pallas::network::miniprotocols::Point;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::framework::history::{Block, History};
+use crate::framework::*;
+
+const DEFAULT_MAX_DEPTH: usize = 100;
+
+#[derive(Stage)]
+#[stage(name = "reset-to-undo", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    pub input: FilterInputPort,
+    pub output: FilterOutputPort,
+
+    max_depth: usize,
+    path: Option<PathBuf>,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    undo_count: gasket::metrics::Counter,
+}
+
+pub struct Worker {
+    history: History,
+}
+
+impl Worker {
+    /// Removes the points of the history after the given one, from oldest to
+    /// newest
+    fn roll_back(&mut self, point: &Point) -> Result<Vec<Block>, WorkerError> {
+        if let Some(discarded) = self.history.roll_back(point).or_panic()? {
+            return Ok(discarded);
+        }
+
+        let slot = point.slot_or_default();
+
+        // the point is not part of the history, but it might still be older than
+        // some of the points we hold (eg: a rollback deeper than the history)
+        if self
+            .history
+            .oldest()
+            .is_some_and(|x| x.slot_or_default() > slot)
+        {
+            warn!(
+                ?point,
+                "rollback is deeper than history, some records can't be undone"
+            );
+        }
+
+        self.history.roll_back_to_slot(slot).or_panic()
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        let history = match &stage.path {
+            Some(path) => History::open(path.clone()).or_panic()?,
+            None => History::default(),
+        };
+
+        Ok(Self { history })
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        let msg = stage.input.recv().await.or_panic()?;
+        Ok(WorkSchedule::Unit(msg.payload))
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        match unit {
+            ChainEvent::Apply(point, record) => {
+                self.history
+                    .push(point.clone(), record.clone())
+                    .or_panic()?;
+                self.history.pop_with_depth(stage.max_depth).or_panic()?;
+                self.history.commit().or_panic()?;
+
+                stage.output.send(unit.clone().into()).await.or_panic()?;
+            }
+            ChainEvent::Undo(point, _) => {
+                self.history.pop_latest(point).or_panic()?;
+                self.history.commit().or_panic()?;
+
+                stage.output.send(unit.clone().into()).await.or_panic()?;
+            }
+            ChainEvent::Reset(point) => {
+                let discarded = self.roll_back(point)?;
+                self.history.commit().or_panic()?;
+
+                info!(
+                    ?point,
+                    blocks = discarded.len(),
+                    "undoing rolled back records"
+                );
+
+                for (point, records) in discarded.into_iter().rev() {
+                    for record in records.into_iter().rev() {
+                        stage
+                            .output
+                            .send(ChainEvent::undo(point.clone(), record))
+                            .await
+                            .or_panic()?;
+
+                        stage.undo_count.inc(1);
+                    }
+                }
+
+                // the reset is still sent so that downstream stages (eg: the
+                // cursor) know where the chain continues from
+                stage
+                    .output
+                    .send(ChainEvent::reset(point.clone()))
+                    .await
+                    .or_panic()?;
+            }
+            // mempool txs aren't part of the chain history
+            ChainEvent::Pending(..) | ChainEvent::Removed(..) => {
+                stage.output.send(unit.clone().into()).await.or_panic()?;
+            }
+        };
+
+        stage.ops_count.inc(1);
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub max_depth: Option<usize>,
+    pub path: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+        let stage = Stage {
+            max_depth: self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH),
+            path: self.path,
+            ops_count: Default::default(),
+            undo_count: Default::default(),
+            input: Default::default(),
+            output: Default::default(),
+        };
+
+        Ok(stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gasket::framework::Worker as _;
+    use gasket::messaging::{InputPort, OutputPort};
+
+    use super::*;
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, slot.to_be_bytes().to_vec())
+    }
+
+    fn record(slot: u64) -> Record {
+        Record::CborTx(slot.to_be_bytes().to_vec())
+    }
+
+    fn stage(max_depth: usize) -> (Stage, InputPort<ChainEvent>) {
+        let mut stage = Config {
+            max_depth: Some(max_depth),
+            path: None,
+        }
+        .bootstrapper(&Context {
+            chain: Default::default(),
+            intersect: IntersectConfig::Origin,
+            finalize: None,
+            current_dir: ".".into(),
+            breadcrumbs: Breadcrumbs::new(10),
+            control: Default::default(),
+        })
+        .unwrap();
+
+        let mut output = InputPort::default();
+        gasket::messaging::tokio::connect_ports(&mut stage.output, &mut output, 100);
+
+        // the input isn't used, units are executed directly
+        let mut input = OutputPort::default();
+        gasket::messaging::tokio::connect_ports(&mut input, &mut stage.input, 100);
+
+        (stage, output)
+    }
+
+    async fn next(output: &mut InputPort<ChainEvent>) -> (&'static str, Point) {
+        match output.recv().await.unwrap().payload {
+            ChainEvent::Apply(x, _) => ("apply", x),
+            ChainEvent::Undo(x, _) => ("undo", x),
+            ChainEvent::Reset(x) => ("reset", x),
+            ChainEvent::Pending(x, _) => ("pending", x),
+            ChainEvent::Removed(x, _) => ("removed", x),
+        }
+    }
+
+    #[tokio::test]
+    async fn reset_undoes_rolled_back_records() {
+        let (mut stage, mut output) = stage(10);
+        let mut worker = Worker::bootstrap(&stage).await.unwrap();
+
+        for slot in 0..4 {
+            let apply = ChainEvent::Apply(point(slot), record(slot));
+            worker.execute(&apply, &mut stage).await.unwrap();
+            assert_eq!(next(&mut output).await, ("apply", point(slot)));
+        }
+
+        let reset = ChainEvent::Reset(point(1));
+        worker.execute(&reset, &mut stage).await.unwrap();
+
+        assert_eq!(next(&mut output).await, ("undo", point(3)));
+        assert_eq!(next(&mut output).await, ("undo", point(2)));
+        assert_eq!(next(&mut output).await, ("reset", point(1)));
+    }
+
+    #[tokio::test]
+    async fn reset_deeper_than_history_undoes_everything_held() {
+        let (mut stage, mut output) = stage(2);
+        let mut worker = Worker::bootstrap(&stage).await.unwrap();
+
+        for slot in 10..15 {
+            let apply = ChainEvent::Apply(point(slot), record(slot));
+            worker.execute(&apply, &mut stage).await.unwrap();
+            assert_eq!(next(&mut output).await, ("apply", point(slot)));
+        }
+
+        // only the last points are still held, the older ones can't be undone
+        let reset = ChainEvent::Reset(point(5));
+        worker.execute(&reset, &mut stage).await.unwrap();
+
+        assert_eq!(next(&mut output).await, ("undo", point(14)));
+        assert_eq!(next(&mut output).await, ("undo", point(13)));
+        assert_eq!(next(&mut output).await, ("reset", point(5)));
+        assert!(worker.history.latest().is_none());
+    }
+
+    #[tokio::test]
+    async fn reset_between_points_keeps_older_records() {
+        let (mut stage, mut output) = stage(10);
+        let mut worker = Worker::bootstrap(&stage).await.unwrap();
+
+        for slot in [10, 12, 14, 16] {
+            let apply = ChainEvent::Apply(point(slot), record(slot));
+            worker.execute(&apply, &mut stage).await.unwrap();
+            assert_eq!(next(&mut output).await, ("apply", point(slot)));
+        }
+
+        // slot 13 is not part of the history, only newer points are undone
+        let reset = ChainEvent::Reset(point(13));
+        worker.execute(&reset, &mut stage).await.unwrap();
+
+        assert_eq!(next(&mut output).await, ("undo", point(16)));
+        assert_eq!(next(&mut output).await, ("undo", point(14)));
+        assert_eq!(next(&mut output).await, ("reset", point(13)));
+
+        // the older records are still around for a later rollback
+        let reset = ChainEvent::Reset(point(11));
+        worker.execute(&reset, &mut stage).await.unwrap();
+
+        assert_eq!(next(&mut output).await, ("undo", point(12)));
+        assert_eq!(next(&mut output).await, ("reset", point(11)));
+        assert_eq!(worker.history.latest(), Some(&point(10)));
+    }
+}
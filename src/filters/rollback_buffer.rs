@@ -1,10 +1,11 @@
-use std::collections::HashMap;
+use std::path::PathBuf;
 
 use gasket::framework::*;
-use pallas::network::miniprotocols::{chainsync, Point};
+use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
-use tracing::info;
+use tracing::{info, warn};
 
+use crate::framework::history::History;
 use crate::framework::*;
 
 #[derive(Stage)]
@@ -14,21 +15,83 @@ pub struct Stage {
     pub output: FilterOutputPort,
 
     min_depth: usize,
+    path: Option<PathBuf>,
 
     #[metric]
     ops_count: gasket::metrics::Counter,
 }
 
-#[derive(Default)]
 pub struct Worker {
-    buffer: chainsync::RollbackBuffer,
-    events: HashMap<Point, Record>,
+    history: History,
+
+    /// Set when the buffer was loaded from disk, until the source either
+    /// catches up with it or sends a reset that can't be part of the replay
+    restarted: bool,
+
+    /// Set while the source is re-sending points that we already have
+    replaying: bool,
+}
+
+impl Worker {
+    /// Checks if the point is not newer than the last one released downstream
+    ///
+    /// After a restart, the source re-sends the blocks after the last point
+    /// persisted by the cursor, which might lag behind the points released by
+    /// the buffer. Only a persistent buffer knows which ones were released.
+    fn was_released(&self, point: &Point) -> bool {
+        self.history
+            .released()
+            .is_some_and(|x| point.slot_or_default() <= x.slot_or_default())
+    }
+
+    /// Makes room for a new point that isn't newer than the latest one in the
+    /// buffer (eg: the chain forked while we were down)
+    fn discard_fork(&mut self, point: &Point) -> Result<(), WorkerError> {
+        let slot = point.slot_or_default();
+
+        let last_valid = self
+            .history
+            .points()
+            .rev()
+            .find(|x| x.slot_or_default() < slot)
+            .cloned();
+
+        match last_valid {
+            Some(x) => {
+                self.history.roll_back(&x).or_panic()?;
+            }
+            None => {
+                self.history.drain().or_panic()?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait(?Send)]
 impl gasket::framework::Worker<Stage> for Worker {
-    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
-        Ok(Default::default())
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        let history = match &stage.path {
+            Some(path) => History::open(path.clone()).or_panic()?,
+            None => History::default(),
+        };
+
+        if stage.path.is_some() {
+            info!(
+                size = history.size(),
+                released = ?history.released(),
+                "loaded persistent rollback buffer"
+            );
+        }
+
+        let restarted = history.size() > 0 || history.released().is_some();
+
+        Ok(Self {
+            restarted,
+            replaying: restarted,
+            history,
+        })
     }
 
     async fn schedule(
@@ -42,62 +105,101 @@ impl gasket::framework::Worker<Stage> for Worker {
     async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
         match unit {
             ChainEvent::Apply(point, record) => {
-                self.events.insert(point.clone(), record.clone());
-                self.buffer.roll_forward(point.clone());
+                if self.replaying && self.was_released(point) {
+                    info!(?point, "skipping point already released before restart");
+                    stage.ops_count.inc(1);
+                    return Ok(());
+                }
+
+                if self.replaying && self.history.contains(point) {
+                    info!(?point, "skipping point already in buffer");
+                    stage.ops_count.inc(1);
+                    return Ok(());
+                }
 
-                let ready = self.buffer.pop_with_depth(stage.min_depth);
-                for point in ready {
-                    if let Some(record) = self.events.remove(&point) {
+                self.replaying = false;
+                self.restarted = false;
+
+                let is_behind = self
+                    .history
+                    .latest()
+                    .is_some_and(|x| x.slot_or_default() > point.slot_or_default());
+
+                if is_behind {
+                    self.discard_fork(point)?;
+                }
+
+                self.history
+                    .push(point.clone(), record.clone())
+                    .or_panic()?;
+
+                let ready = self.history.pop_with_depth(stage.min_depth).or_panic()?;
+
+                for (point, records) in ready {
+                    for record in records {
                         stage
                             .output
-                            .send(ChainEvent::apply(point, record))
+                            .send(ChainEvent::apply(point.clone(), record))
                             .await
                             .or_panic()?;
                     }
                 }
             }
-            ChainEvent::Undo(point, record) => match self.buffer.roll_back(point) {
-                chainsync::RollbackEffect::Handled => {
+            ChainEvent::Undo(point, record) => {
+                if self.history.pop_latest(point).or_panic()?.is_some() {
                     info!(?point, "handled rollback within buffer");
-                    self.events
-                        .retain(|x, _| x.slot_or_default() <= point.slot_or_default());
-                }
-                chainsync::RollbackEffect::OutOfScope => {
+                } else {
                     info!("rollback out of buffer scope, sending event down the pipeline");
-                    self.events.clear();
+                    self.history.drain().or_panic()?;
                     stage
                         .output
                         .send(ChainEvent::undo(point.clone(), record.clone()))
                         .await
                         .or_panic()?;
                 }
-            },
+            }
             ChainEvent::Reset(point) => {
-                match self.buffer.roll_back(point) {
-                    chainsync::RollbackEffect::Handled => {
-                        info!(?point, "handled reset rollback within buffer");
-                        self.events
-                            .retain(|x, _| x.slot_or_default() < point.slot_or_default());
-                    }
-                    chainsync::RollbackEffect::OutOfScope => {
-                        info!("reset rollback out of buffer scope");
-                        self.events.clear();
-                    }
-                };
+                // only the first reset after a restart can be the intersection
+                // of the replay, any later one is a real rollback
+                let restarted = std::mem::take(&mut self.restarted);
 
-                stage
-                    .output
-                    .send(ChainEvent::reset(point.clone()))
-                    .await
-                    .or_panic()?;
+                if self.history.roll_back(point).or_panic()?.is_some() {
+                    // the rollback is transparent to downstream stages, sending the
+                    // reset would make the cursor move to a point that wasn't
+                    // released yet
+                    info!(?point, "handled reset rollback within buffer");
+                } else if restarted && self.was_released(point) {
+                    self.replaying = true;
+                    warn!(
+                        ?point,
+                        "reset to a point already released, assuming a restart replay"
+                    );
+                } else {
+                    info!("reset rollback out of buffer scope");
+                    self.replaying = false;
+                    self.history.drain().or_panic()?;
+
+                    stage
+                        .output
+                        .send(ChainEvent::reset(point.clone()))
+                        .await
+                        .or_panic()?;
+                }
+            }
+            // mempool txs have no depth to wait for, they go through as they
+            // arrive
+            ChainEvent::Pending(..) | ChainEvent::Removed(..) => {
+                stage.output.send(unit.clone().into()).await.or_panic()?;
             }
         };
 
+        self.history.commit().or_panic()?;
+
         info!(
             "rollback buffer state, size: {}, oldest: {:?}, latest: {:?}",
-            self.buffer.size(),
-            self.buffer.oldest(),
-            self.buffer.latest(),
+            self.history.size(),
+            self.history.oldest(),
+            self.history.latest(),
         );
 
         stage.ops_count.inc(1);
@@ -109,6 +211,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Deserialize)]
 pub struct Config {
     pub min_depth: usize,
+    pub path: Option<PathBuf>,
 }
 
 impl Config {
@@ -117,6 +220,7 @@ impl Config {
 
         let stage = Stage {
             min_depth: self.min_depth,
+            path: self.path,
             ops_count: Default::default(),
             input: Default::default(),
             output: Default::default(),
@@ -125,3 +229,117 @@ impl Config {
         Ok(stage)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use gasket::framework::Worker as _;
+    use gasket::messaging::{InputPort, OutputPort};
+
+    use super::*;
+
+    fn point(slot: u64, fork: u8) -> Point {
+        Point::Specific(slot, vec![fork])
+    }
+
+    fn buffer(path: PathBuf) -> (Stage, InputPort<ChainEvent>) {
+        let mut stage = Stage {
+            min_depth: 2,
+            path: Some(path),
+            ops_count: Default::default(),
+            input: Default::default(),
+            output: Default::default(),
+        };
+
+        let mut output = InputPort::default();
+        gasket::messaging::tokio::connect_ports(&mut stage.output, &mut output, 100);
+
+        // the input isn't used, units are executed directly
+        let mut input = OutputPort::default();
+        gasket::messaging::tokio::connect_ports(&mut input, &mut stage.input, 100);
+
+        (stage, output)
+    }
+
+    async fn apply(worker: &mut Worker, stage: &mut Stage, point: Point) {
+        let unit = ChainEvent::Apply(point, Record::CborBlock(vec![]));
+        worker.execute(&unit, stage).await.unwrap();
+    }
+
+    async fn reset(worker: &mut Worker, stage: &mut Stage, point: Point) {
+        let unit = ChainEvent::Reset(point);
+        worker.execute(&unit, stage).await.unwrap();
+    }
+
+    async fn next(output: &mut InputPort<ChainEvent>) -> (&'static str, Point) {
+        match output.recv().await.unwrap().payload {
+            ChainEvent::Apply(x, _) => ("apply", x),
+            ChainEvent::Undo(x, _) => ("undo", x),
+            ChainEvent::Reset(x) => ("reset", x),
+            ChainEvent::Pending(x, _) => ("pending", x),
+            ChainEvent::Removed(x, _) => ("removed", x),
+        }
+    }
+
+    #[tokio::test]
+    async fn restart_replay_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("buffer");
+
+        let (mut stage, mut output) = buffer(path.clone());
+        let mut worker = Worker::bootstrap(&stage).await.unwrap();
+
+        for slot in 1..=5 {
+            apply(&mut worker, &mut stage, point(slot, 0)).await;
+        }
+
+        for slot in 1..=3 {
+            assert_eq!(next(&mut output).await, ("apply", point(slot, 0)));
+        }
+
+        drop(worker);
+
+        // the cursor lags behind, so the source replays from an older point
+        let (mut stage, mut output) = buffer(path);
+        let mut worker = Worker::bootstrap(&stage).await.unwrap();
+
+        reset(&mut worker, &mut stage, point(1, 0)).await;
+
+        for slot in 2..=6 {
+            apply(&mut worker, &mut stage, point(slot, 0)).await;
+        }
+
+        assert_eq!(next(&mut output).await, ("apply", point(4, 0)));
+        let rest = tokio::time::timeout(Duration::from_millis(100), output.recv()).await;
+        assert!(rest.is_err());
+    }
+
+    #[tokio::test]
+    async fn deep_rollback_after_release_is_forwarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("buffer");
+
+        let (mut stage, mut output) = buffer(path);
+        let mut worker = Worker::bootstrap(&stage).await.unwrap();
+
+        for slot in 1..=5 {
+            apply(&mut worker, &mut stage, point(slot, 0)).await;
+        }
+
+        for slot in 1..=3 {
+            assert_eq!(next(&mut output).await, ("apply", point(slot, 0)));
+        }
+
+        // the rollback goes past the points still held by the buffer
+        reset(&mut worker, &mut stage, point(2, 0)).await;
+        assert_eq!(next(&mut output).await, ("reset", point(2, 0)));
+
+        for slot in 3..=6 {
+            apply(&mut worker, &mut stage, point(slot, 1)).await;
+        }
+
+        assert_eq!(next(&mut output).await, ("apply", point(3, 1)));
+        assert_eq!(next(&mut output).await, ("apply", point(4, 1)));
+    }
+}
@@ -1,20 +1,97 @@
+use bech32::FromBase32;
+use pallas::ledger::addresses::byron::AddrAttrProperty;
 use pallas::ledger::addresses::{
-    Address, ByronAddress, ShelleyAddress, ShelleyDelegationPart, StakeAddress,
+    Address, ByronAddress, Network, ShelleyAddress, ShelleyDelegationPart, StakeAddress,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::str::FromStr;
 
 use super::*;
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkKind {
+    Mainnet,
+    Testnet,
+}
+
+impl NetworkKind {
+    fn from_network(network: Network) -> Option<Self> {
+        match network {
+            Network::Mainnet => Some(Self::Mainnet),
+            Network::Testnet => Some(Self::Testnet),
+            Network::Other(_) => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressType {
+    Base,
+    Enterprise,
+    Pointer,
+    Reward,
+    Byron,
+}
+
+/// Parses the hash of a payment or delegation credential, either hex-encoded
+/// or as any of the bech32 strings that carry one
+fn parse_credential(s: &str) -> anyhow::Result<FlexBytes> {
+    let content = match FlexBytes::from_hex(s) {
+        Ok(x) => x.0,
+        Err(_) => {
+            let (hrp, content, _) = bech32::decode(s)?;
+            let content = Vec::<u8>::from_base32(&content)?;
+
+            match hrp.as_str() {
+                "addr_vkh" | "addr_shared_vkh" | "stake_vkh" | "stake_shared_vkh" | "script"
+                | "stake" | "stake_test" => content,
+                x => anyhow::bail!("bech32 hrp '{x}' is not a credential"),
+            }
+        }
+    };
+
+    // stake addresses carry the credential after the header
+    match Address::from_bytes(&content) {
+        Ok(Address::Stake(x)) if content.len() == 29 => {
+            Ok(FlexBytes(x.payload().as_hash().to_vec()))
+        }
+        _ => Ok(FlexBytes(content)),
+    }
+}
+
+fn deserialize_credential<'de, D>(deserializer: D) -> Result<Option<FlexBytes>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+
+    value
+        .map(|x| parse_credential(&x))
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct AddressPattern {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub byron_address: Option<FlexBytes>,
 
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Hex-encoded or as an `addr_vkh` or `script` bech32 string
+    #[serde(
+        default,
+        deserialize_with = "deserialize_credential",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub payment_part: Option<FlexBytes>,
 
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Hex-encoded or as a `stake_vkh`, `script` or `stake` bech32 string
+    #[serde(
+        default,
+        deserialize_with = "deserialize_credential",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub delegation_part: Option<FlexBytes>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -22,6 +99,43 @@ pub struct AddressPattern {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub delegation_is_script: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<NetworkKind>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_type: Option<AddressType>,
+}
+
+impl AddressPattern {
+    fn match_kind(&self, network: Option<NetworkKind>, address_type: AddressType) -> MatchOutcome {
+        let a = match &self.network {
+            Some(x) => MatchOutcome::if_equal(&Some(*x), &network),
+            None => MatchOutcome::Positive,
+        };
+
+        let b = match &self.address_type {
+            Some(x) => MatchOutcome::if_equal(x, &address_type),
+            None => MatchOutcome::Positive,
+        };
+
+        MatchOutcome::fold_all_of([a, b].into_iter())
+    }
+}
+
+/// Byron addresses only carry a network tag outside of mainnet
+fn byron_network(subject: &ByronAddress) -> Option<NetworkKind> {
+    let payload = subject.decode().ok()?;
+
+    let tagged = payload
+        .attributes
+        .iter()
+        .any(|x| matches!(x, AddrAttrProperty::NetworkTag(_)));
+
+    match tagged {
+        true => Some(NetworkKind::Testnet),
+        false => Some(NetworkKind::Mainnet),
+    }
 }
 
 impl PatternOf<&ByronAddress> for AddressPattern {
@@ -36,7 +150,9 @@ impl PatternOf<&ByronAddress> for AddressPattern {
 
         let e = self.delegation_is_script.is_match(false);
 
-        MatchOutcome::fold_all_of([a, b, c, d, e].into_iter())
+        let f = self.match_kind(byron_network(subject), AddressType::Byron);
+
+        MatchOutcome::fold_all_of([a, b, c, d, e, f].into_iter())
     }
 }
 
@@ -46,9 +162,13 @@ impl PatternOf<&ShelleyAddress> for AddressPattern {
 
         let b = self.payment_part.is_match(&subject.payment().to_vec());
 
-        let c = self
-            .delegation_part
-            .is_match(&subject.delegation().to_vec());
+        // a pointer references the certificate that registered the stake
+        // credential, which can't be resolved without the ledger state
+        let c = match (&self.delegation_part, subject.delegation()) {
+            (None, _) => MatchOutcome::Positive,
+            (Some(_), ShelleyDelegationPart::Pointer(_)) => MatchOutcome::Uncertain,
+            (Some(x), delegation) => x.is_match(&delegation.to_vec()),
+        };
 
         let d = self
             .payment_is_script
@@ -58,7 +178,16 @@ impl PatternOf<&ShelleyAddress> for AddressPattern {
             .delegation_is_script
             .is_match(subject.delegation().is_script());
 
-        MatchOutcome::fold_all_of([a, b, c, d, e].into_iter())
+        let address_type = match subject.delegation() {
+            ShelleyDelegationPart::Key(_) | ShelleyDelegationPart::Script(_) => AddressType::Base,
+            ShelleyDelegationPart::Pointer(_) => AddressType::Pointer,
+            ShelleyDelegationPart::Null => AddressType::Enterprise,
+        };
+
+        let network = NetworkKind::from_network(subject.network());
+        let f = self.match_kind(network, address_type);
+
+        MatchOutcome::fold_all_of([a, b, c, d, e, f].into_iter())
     }
 }
 
@@ -68,13 +197,18 @@ impl PatternOf<&StakeAddress> for AddressPattern {
 
         let b = MatchOutcome::if_false(self.payment_part.is_some());
 
-        let c = self.delegation_part.is_match(&subject.to_vec());
+        let c = self
+            .delegation_part
+            .is_match(subject.payload().as_hash().as_ref());
 
         let d = MatchOutcome::if_false(self.payment_is_script.is_some());
 
         let e = self.delegation_is_script.is_match(subject.is_script());
 
-        MatchOutcome::fold_all_of([a, b, c, d, e].into_iter())
+        let network = NetworkKind::from_network(subject.network());
+        let f = self.match_kind(network, AddressType::Reward);
+
+        MatchOutcome::fold_all_of([a, b, c, d, e, f].into_iter())
     }
 }
 
@@ -104,7 +238,7 @@ impl From<Address> for AddressPattern {
                 ..Default::default()
             },
             Address::Stake(x) => Self {
-                delegation_part: Some(x.to_vec().into()),
+                delegation_part: Some(x.payload().as_hash().to_vec().into()),
                 ..Default::default()
             },
             Address::Shelley(x) => Self {
@@ -123,8 +257,17 @@ impl From<Address> for AddressPattern {
 impl FromBech32 for AddressPattern {
     fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
         match hrp {
-            "addr" | "addr_test" | "stake" => Address::from_bytes(&content).ok().map(From::from),
-            // TODO: add vk prefixes
+            "addr" | "addr_test" | "stake" | "stake_test" => {
+                Address::from_bytes(&content).ok().map(From::from)
+            }
+            "addr_vkh" | "addr_shared_vkh" | "script" => Some(Self {
+                payment_part: Some(FlexBytes(content)),
+                ..Default::default()
+            }),
+            "stake_vkh" | "stake_shared_vkh" => Some(Self {
+                delegation_part: Some(FlexBytes(content)),
+                ..Default::default()
+            }),
             _ => None,
         }
     }
@@ -140,6 +283,8 @@ impl FromStr for AddressPattern {
 
 #[cfg(test)]
 mod tests {
+    use pallas::ledger::addresses::{Pointer, ShelleyPaymentPart};
+
     use super::*;
 
     #[test]
@@ -202,4 +347,98 @@ mod tests {
         ));
         assert_eq!(possitives, vec![1, 2, 3]);
     }
+
+    #[test]
+    fn parse_credential_parts() {
+        let pattern: AddressPattern = serde_json::from_str(
+            r#"{
+                "payment_part": "addr_vkh1jjfnzhxe966a33psfenm0ct2udkkr569qf55v4uprgkgu8zsvmg",
+                "delegation_part": "stake1uyehkck0lajq8gr28t9uxnuvgcqrc6070x3k9r8048z8y5gh6ffgw"
+            }"#,
+        )
+        .unwrap();
+
+        let expected = AddressPattern::from_str("addr1qx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer3n0d3vllmyqwsx5wktcd8cc3sq835lu7drv2xwl2wywfgse35a3x").unwrap();
+
+        assert_eq!(pattern, expected);
+
+        let pattern: AddressPattern = serde_json::from_str(
+            r#"{ "delegation_part": "stake_vkh1xdak9nllvsp6q636e0p5lrzxqq7xnlne5d3gemafc3e9z3v4vud" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(pattern.delegation_part, expected.delegation_part);
+
+        let invalid = serde_json::from_str::<AddressPattern>(
+            r#"{ "payment_part": "asset17jd78wukhtrnmjh3fngzasxm8rck0l2r4hhyyt" }"#,
+        );
+
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn stake_address_match() {
+        let pattern = |addr: &str| Pattern::from(AddressPattern::from_str(addr).unwrap());
+
+        let possitives = testing::find_positive_test_vectors(pattern(
+            "stake1uyehkck0lajq8gr28t9uxnuvgcqrc6070x3k9r8048z8y5gh6ffgw",
+        ));
+        assert_eq!(possitives, vec![1, 3]);
+    }
+
+    #[test]
+    fn network_and_type_match() {
+        let base = Address::from_str("addr1qx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer3n0d3vllmyqwsx5wktcd8cc3sq835lu7drv2xwl2wywfgse35a3x").unwrap();
+        let enterprise =
+            Address::from_str("addr1vx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzers66hrl8")
+                .unwrap();
+        let reward =
+            Address::from_str("stake1uyehkck0lajq8gr28t9uxnuvgcqrc6070x3k9r8048z8y5gh6ffgw")
+                .unwrap();
+
+        let pattern = |json: &str| serde_json::from_str::<AddressPattern>(json).unwrap();
+
+        let enterprise_mainnet =
+            pattern(r#"{ "network": "mainnet", "address_type": "enterprise" }"#);
+        assert_eq!(
+            enterprise_mainnet.is_match(&enterprise),
+            MatchOutcome::Positive
+        );
+        assert_eq!(enterprise_mainnet.is_match(&base), MatchOutcome::Negative);
+
+        let testnet = pattern(r#"{ "network": "testnet" }"#);
+        assert_eq!(testnet.is_match(&base), MatchOutcome::Negative);
+
+        let delegating = pattern(
+            r#"{ "delegation_part": "stake1uyehkck0lajq8gr28t9uxnuvgcqrc6070x3k9r8048z8y5gh6ffgw" }"#,
+        );
+        assert_eq!(delegating.is_match(&base), MatchOutcome::Positive);
+        assert_eq!(delegating.is_match(&reward), MatchOutcome::Positive);
+        assert_eq!(delegating.is_match(&enterprise), MatchOutcome::Negative);
+
+        let reward_type = pattern(r#"{ "address_type": "reward" }"#);
+        assert_eq!(reward_type.is_match(&reward), MatchOutcome::Positive);
+        assert_eq!(reward_type.is_match(&base), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn pointer_delegation_is_uncertain() {
+        let pointer = ShelleyAddress::new(
+            Network::Mainnet,
+            ShelleyPaymentPart::key_hash([1; 28].into()),
+            ShelleyDelegationPart::Pointer(Pointer::new(2498243, 27, 3)),
+        );
+
+        let pattern = AddressPattern {
+            delegation_part: Some(FlexBytes(vec![2; 28])),
+            ..Default::default()
+        };
+        assert_eq!(pattern.is_match(&pointer), MatchOutcome::Uncertain);
+
+        let pattern = AddressPattern {
+            address_type: Some(AddressType::Pointer),
+            ..Default::default()
+        };
+        assert_eq!(pattern.is_match(&pointer), MatchOutcome::Positive);
+    }
 }
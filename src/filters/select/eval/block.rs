@@ -0,0 +1,336 @@
+use pallas::crypto::hash::Hasher;
+use pallas::interop::utxorpc::{LedgerContext, Mapper, TxoRef, UtxoMap};
+use pallas::ledger::traverse::{MultiEraBlock, MultiEraTx};
+
+use self::serde_ext::FromBech32;
+
+use super::*;
+
+pub type HeightPattern = NumericPattern<u64>;
+
+pub type SizePattern = NumericPattern<u64>;
+
+pub type TxCountPattern = NumericPattern<u64>;
+
+/// Header data that is only available when the block is evaluated from its
+/// cbor, u5c blocks don't carry it
+#[derive(Clone, Debug, PartialEq)]
+pub struct CborHeader {
+    pub era: u8,
+    pub size: u64,
+    pub issuer_vkey: Option<Vec<u8>>,
+    pub vrf_vkey: Option<Vec<u8>>,
+}
+
+/// The subject of a block pattern, built either from a parsed block or from
+/// the decoded cbor of a block
+#[derive(Clone)]
+pub struct BlockSubject<'a> {
+    pub hash: Vec<u8>,
+    pub slot: u64,
+    pub height: u64,
+    pub cbor: Option<CborHeader>,
+    pub txs: &'a [ParsedTx],
+
+    /// The decoded txs, in the same order as the parsed ones. Empty unless
+    /// the block is evaluated from its cbor.
+    pub cbor_txs: &'a [MultiEraTx<'a>],
+}
+
+impl<'a> From<&'a ParsedBlock> for BlockSubject<'a> {
+    fn from(value: &'a ParsedBlock) -> Self {
+        let header = value.header.clone().unwrap_or_default();
+
+        let txs = match &value.body {
+            Some(x) => x.tx.as_slice(),
+            None => &[],
+        };
+
+        Self {
+            hash: header.hash.to_vec(),
+            slot: header.slot,
+            height: header.height,
+            cbor: None,
+            txs,
+            cbor_txs: &[],
+        }
+    }
+}
+
+impl<'a> BlockSubject<'a> {
+    pub fn from_cbor(
+        block: &MultiEraBlock,
+        parsed: &'a ParsedBlock,
+        txs: &'a [MultiEraTx<'a>],
+    ) -> Self {
+        let header = block.header();
+
+        let cbor = CborHeader {
+            era: u16::from(block.era()) as u8,
+            size: block.size() as u64,
+            issuer_vkey: header.issuer_vkey().map(|x| x.to_vec()),
+            vrf_vkey: header.vrf_vkey().map(|x| x.to_vec()),
+        };
+
+        Self {
+            cbor: Some(cbor),
+            cbor_txs: txs,
+            ..Self::from(parsed)
+        }
+    }
+
+    pub fn tx_subjects(&self) -> impl Iterator<Item = TxSubject<'a>> + '_ {
+        self.txs.iter().enumerate().map(|(idx, parsed)| TxSubject {
+            parsed,
+            cbor: self.cbor_txs.get(idx),
+        })
+    }
+}
+
+#[derive(Clone, Default)]
+struct NoLedger;
+
+impl LedgerContext for NoLedger {
+    fn get_utxos(&self, _refs: &[TxoRef]) -> Option<UtxoMap> {
+        None
+    }
+}
+
+/// Maps a block cbor into its parsed representation. Inputs can't be resolved
+/// at this point, so input patterns will be uncertain.
+pub fn map_block_cbor(block: &MultiEraBlock) -> ParsedBlock {
+    Mapper::<NoLedger>::default().map_block(block)
+}
+
+/// Maps a tx cbor into its parsed representation, with the same caveats as
+/// blocks
+pub fn map_tx_cbor(tx: &MultiEraTx) -> ParsedTx {
+    Mapper::<NoLedger>::default().map_tx(tx)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct IssuerPattern {
+    /// The id of the pool (hash of the issuer vkey)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_id: Option<FlexBytes>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vrf_vkey: Option<FlexBytes>,
+}
+
+impl FromBech32 for IssuerPattern {
+    fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
+        match hrp {
+            "pool" => Some(Self {
+                pool_id: Some(FlexBytes(content)),
+                ..Default::default()
+            }),
+            "vrf_vk" => Some(Self {
+                vrf_vkey: Some(FlexBytes(content)),
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for IssuerPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(x) = Self::from_bech32(s) {
+            return Ok(x);
+        }
+
+        if let Ok(x) = FlexBytes::from_hex(s) {
+            return Ok(Self {
+                pool_id: Some(x),
+                ..Default::default()
+            });
+        }
+
+        anyhow::bail!("can't parse string as issuer pattern (expected pool id or vrf key)");
+    }
+}
+
+impl PatternOf<&CborHeader> for IssuerPattern {
+    fn is_match(&self, subject: &CborHeader) -> MatchOutcome {
+        // byron blocks aren't issued by pools
+        let pool_id = subject.issuer_vkey.as_ref().map(|x| Hasher::<224>::hash(x));
+        let a = match_optional(&self.pool_id, pool_id.as_ref().map(|x| x.as_ref()));
+
+        let b = self.vrf_vkey.is_some_match(subject.vrf_vkey.as_deref());
+
+        MatchOutcome::fold_all_of([a, b].into_iter())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BlockPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<FlexBytes>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    slot: Option<SlotPattern>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    height: Option<HeightPattern>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tx_count: Option<TxCountPattern>,
+
+    /// Requires the block cbor, uncertain when evaluating parsed blocks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    era: Option<EraPattern>,
+
+    /// Requires the block cbor, uncertain when evaluating parsed blocks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<SizePattern>,
+
+    /// Requires the block cbor, uncertain when evaluating parsed blocks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    issuer: Option<StringOrStruct<IssuerPattern>>,
+
+    /// Each of the patterns needs to match at least one of the txs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    txs: Vec<TxPattern>,
+}
+
+impl PatternOf<&CborHeader> for BlockPattern {
+    fn is_match(&self, subject: &CborHeader) -> MatchOutcome {
+        let a = self.era.is_match(subject.era);
+
+        let b = self.size.is_match(subject.size);
+
+        let c = self.issuer.is_match(subject);
+
+        MatchOutcome::fold_all_of([a, b, c].into_iter())
+    }
+}
+
+impl PatternOf<&BlockSubject<'_>> for BlockPattern {
+    fn is_match(&self, subject: &BlockSubject) -> MatchOutcome {
+        let a = self.hash.is_match(subject.hash.as_slice());
+
+        let b = self.slot.is_match(subject.slot);
+
+        let c = self.height.is_match(subject.height);
+
+        let d = self.tx_count.is_match(subject.txs.len() as u64);
+
+        let e = match &subject.cbor {
+            Some(x) => self.is_match(x),
+            None if self.era.is_some() || self.size.is_some() || self.issuer.is_some() => {
+                MatchOutcome::Uncertain
+            }
+            None => MatchOutcome::Positive,
+        };
+
+        let f = self.txs.iter().map(|x| {
+            let outcomes = subject.tx_subjects().map(|tx| x.is_match(&tx));
+            MatchOutcome::fold_any_of(outcomes)
+        });
+        let f = MatchOutcome::fold_all_of(f);
+
+        MatchOutcome::fold_all_of([a, b, c, d, e, f].into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bech32::ToBase32;
+    use pallas::interop::utxorpc::spec::cardano::{BlockBody, BlockHeader};
+
+    use super::*;
+
+    const POOL_ID: &str = "c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4";
+
+    fn parsed_block() -> ParsedBlock {
+        ParsedBlock {
+            header: Some(BlockHeader {
+                slot: 1000,
+                hash: hex::decode(
+                    "923918e403bf43c34b4ef6b48eb2ee04babed17320d8d1b9ff9ad086e86f44ec",
+                )
+                .unwrap()
+                .into(),
+                height: 50,
+            }),
+            body: Some(BlockBody {
+                tx: testing::test_vectors(),
+            }),
+        }
+    }
+
+    #[test]
+    fn issuer_parse() {
+        let parsed = IssuerPattern::from_str(POOL_ID).unwrap();
+        assert_eq!(parsed.pool_id, Some(FlexBytes::from_hex(POOL_ID).unwrap()));
+
+        let content = hex::decode(POOL_ID).unwrap().to_base32();
+        let bech32 = bech32::encode("pool", content, bech32::Variant::Bech32).unwrap();
+        let parsed = IssuerPattern::from_str(&bech32).unwrap();
+        assert_eq!(parsed.pool_id, Some(FlexBytes::from_hex(POOL_ID).unwrap()));
+
+        assert!(IssuerPattern::from_str("addr_xyz").is_err());
+    }
+
+    #[test]
+    fn header_match() {
+        let block = parsed_block();
+        let subject = BlockSubject::from(&block);
+
+        let pattern = |json: &str| serde_json::from_str::<BlockPattern>(json).unwrap();
+
+        let slot = pattern(r#"{ "slot": { "between": [900, 1100] } }"#);
+        assert_eq!(slot.is_match(&subject), MatchOutcome::Positive);
+
+        let slot = pattern(r#"{ "slot": { "gte": 1100 } }"#);
+        assert_eq!(slot.is_match(&subject), MatchOutcome::Negative);
+
+        let hash = pattern(
+            r#"{ "hash": "923918e403bf43c34b4ef6b48eb2ee04babed17320d8d1b9ff9ad086e86f44ec" }"#,
+        );
+        assert_eq!(hash.is_match(&subject), MatchOutcome::Positive);
+
+        let tx_count = pattern(r#"{ "height": { "exact": 50 }, "tx_count": { "gte": 4 } }"#);
+        assert_eq!(tx_count.is_match(&subject), MatchOutcome::Positive);
+
+        let era = pattern(r#"{ "era": { "exact": 7 } }"#);
+        assert_eq!(era.is_match(&subject), MatchOutcome::Uncertain);
+
+        let txs = pattern(r##"{ "txs": [{ "metadata": [{ "label": 9980 }] }] }"##);
+        assert_eq!(txs.is_match(&subject), MatchOutcome::Positive);
+    }
+
+    #[test]
+    fn cbor_header_match() {
+        let vkey = vec![7u8; 32];
+        let pool_id = Hasher::<224>::hash(&vkey);
+
+        let block = parsed_block();
+
+        let subject = BlockSubject {
+            cbor: Some(CborHeader {
+                era: 7,
+                size: 2048,
+                issuer_vkey: Some(vkey),
+                vrf_vkey: Some(vec![9u8; 32]),
+            }),
+            ..BlockSubject::from(&block)
+        };
+
+        let pattern = |issuer: &str| BlockPattern {
+            era: Some(EraPattern::Gte(6)),
+            size: Some(SizePattern::Lte(4096)),
+            issuer: Some(IssuerPattern::from_str(issuer).unwrap().into()),
+            ..Default::default()
+        };
+
+        let ours = pattern(&pool_id.to_string());
+        assert_eq!(ours.is_match(&subject), MatchOutcome::Positive);
+
+        let theirs = pattern(POOL_ID);
+        assert_eq!(theirs.is_match(&subject), MatchOutcome::Negative);
+    }
+}
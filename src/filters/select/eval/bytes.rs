@@ -1,3 +1,4 @@
+use bech32::FromBase32;
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::{self, Debug, Display};
@@ -68,7 +69,13 @@ impl Visitor<'_> for FlexBytesVisitor {
     where
         E: de::Error,
     {
-        let bytes = hex::decode(value).map_err(de::Error::custom)?;
+        if let Ok(bytes) = hex::decode(value) {
+            return Ok(FlexBytes(bytes));
+        }
+
+        let (_, content, _) = bech32::decode(value).map_err(de::Error::custom)?;
+        let bytes = Vec::<u8>::from_base32(&content).map_err(de::Error::custom)?;
+
         Ok(FlexBytes(bytes))
     }
 }
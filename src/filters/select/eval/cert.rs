@@ -0,0 +1,323 @@
+use pallas::interop::utxorpc::spec::cardano::{
+    certificate::Certificate as CertKind, d_rep::Drep, stake_credential::StakeCredential as Cred,
+    Certificate, DRep, StakeCredential, Withdrawal,
+};
+
+use self::serde_ext::FromBech32;
+
+use super::*;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CertificateKind {
+    StakeRegistration,
+    StakeDeregistration,
+    StakeDelegation,
+    VoteDelegation,
+    PoolRegistration,
+    PoolRetirement,
+    GenesisKeyDelegation,
+    Mir,
+    CommitteeHotAuth,
+    CommitteeColdResign,
+    DrepRegistration,
+    DrepDeregistration,
+    DrepUpdate,
+}
+
+/// The kinds of a certificate. Conway certificates that combine several
+/// operations (eg: registration and delegation) have more than one kind.
+fn certificate_kinds(cert: &CertKind) -> &'static [CertificateKind] {
+    use CertificateKind::*;
+
+    match cert {
+        CertKind::StakeRegistration(_) => &[StakeRegistration],
+        CertKind::RegCert(_) => &[StakeRegistration],
+        CertKind::StakeDeregistration(_) => &[StakeDeregistration],
+        CertKind::UnregCert(_) => &[StakeDeregistration],
+        CertKind::StakeDelegation(_) => &[StakeDelegation],
+        CertKind::VoteDelegCert(_) => &[VoteDelegation],
+        CertKind::StakeVoteDelegCert(_) => &[StakeDelegation, VoteDelegation],
+        CertKind::StakeRegDelegCert(_) => &[StakeRegistration, StakeDelegation],
+        CertKind::VoteRegDelegCert(_) => &[StakeRegistration, VoteDelegation],
+        CertKind::StakeVoteRegDelegCert(_) => &[StakeRegistration, StakeDelegation, VoteDelegation],
+        CertKind::PoolRegistration(_) => &[PoolRegistration],
+        CertKind::PoolRetirement(_) => &[PoolRetirement],
+        CertKind::GenesisKeyDelegation(_) => &[GenesisKeyDelegation],
+        CertKind::MirCert(_) => &[Mir],
+        CertKind::AuthCommitteeHotCert(_) => &[CommitteeHotAuth],
+        CertKind::ResignCommitteeColdCert(_) => &[CommitteeColdResign],
+        CertKind::RegDrepCert(_) => &[DrepRegistration],
+        CertKind::UnregDrepCert(_) => &[DrepDeregistration],
+        CertKind::UpdateDrepCert(_) => &[DrepUpdate],
+    }
+}
+
+fn credential_hash(cred: &StakeCredential) -> Option<&[u8]> {
+    match cred.stake_credential.as_ref()? {
+        Cred::AddrKeyHash(x) => Some(x.as_ref()),
+        Cred::ScriptHash(x) => Some(x.as_ref()),
+    }
+}
+
+fn drep_hash(drep: &DRep) -> Option<&[u8]> {
+    match drep.drep.as_ref()? {
+        Drep::AddrKeyHash(x) => Some(x.as_ref()),
+        Drep::ScriptHash(x) => Some(x.as_ref()),
+        Drep::Abstain(_) | Drep::NoConfidence(_) => None,
+    }
+}
+
+pub(super) fn stake_credential(cert: &CertKind) -> Option<&StakeCredential> {
+    match cert {
+        CertKind::StakeRegistration(x) => Some(x),
+        CertKind::StakeDeregistration(x) => Some(x),
+        CertKind::StakeDelegation(x) => x.stake_credential.as_ref(),
+        CertKind::RegCert(x) => x.stake_credential.as_ref(),
+        CertKind::UnregCert(x) => x.stake_credential.as_ref(),
+        CertKind::VoteDelegCert(x) => x.stake_credential.as_ref(),
+        CertKind::StakeVoteDelegCert(x) => x.stake_credential.as_ref(),
+        CertKind::StakeRegDelegCert(x) => x.stake_credential.as_ref(),
+        CertKind::VoteRegDelegCert(x) => x.stake_credential.as_ref(),
+        CertKind::StakeVoteRegDelegCert(x) => x.stake_credential.as_ref(),
+        _ => None,
+    }
+}
+
+fn pool_keyhash(cert: &CertKind) -> Option<&[u8]> {
+    match cert {
+        CertKind::StakeDelegation(x) => Some(x.pool_keyhash.as_ref()),
+        CertKind::StakeVoteDelegCert(x) => Some(x.pool_keyhash.as_ref()),
+        CertKind::StakeRegDelegCert(x) => Some(x.pool_keyhash.as_ref()),
+        CertKind::StakeVoteRegDelegCert(x) => Some(x.pool_keyhash.as_ref()),
+        CertKind::PoolRegistration(x) => Some(x.operator.as_ref()),
+        CertKind::PoolRetirement(x) => Some(x.pool_keyhash.as_ref()),
+        _ => None,
+    }
+}
+
+fn drep(cert: &CertKind) -> Option<&[u8]> {
+    match cert {
+        CertKind::VoteDelegCert(x) => x.drep.as_ref().and_then(drep_hash),
+        CertKind::StakeVoteDelegCert(x) => x.drep.as_ref().and_then(drep_hash),
+        CertKind::VoteRegDelegCert(x) => x.drep.as_ref().and_then(drep_hash),
+        CertKind::StakeVoteRegDelegCert(x) => x.drep.as_ref().and_then(drep_hash),
+        CertKind::RegDrepCert(x) => x.drep_credential.as_ref().and_then(credential_hash),
+        CertKind::UnregDrepCert(x) => x.drep_credential.as_ref().and_then(credential_hash),
+        CertKind::UpdateDrepCert(x) => x.drep_credential.as_ref().and_then(credential_hash),
+        _ => None,
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CertificatePattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<CertificateKind>,
+
+    /// The hash of the stake credential (key or script) of the certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stake_credential: Option<FlexBytes>,
+
+    /// The id of the pool that is delegated to, registered or retired
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<FlexBytes>,
+
+    /// The hash of the DRep that is delegated to, registered, unregistered or
+    /// updated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drep: Option<FlexBytes>,
+}
+
+impl FromBech32 for CertificatePattern {
+    fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
+        match hrp {
+            "pool" => Some(Self {
+                pool: Some(FlexBytes(content)),
+                ..Default::default()
+            }),
+            "drep" => Some(Self {
+                drep: Some(FlexBytes(content)),
+                ..Default::default()
+            }),
+            // the first byte of a reward account is the header
+            "stake" | "stake_test" => Some(Self {
+                stake_credential: Some(FlexBytes(content.get(1..)?.to_vec())),
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for CertificatePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bech32(s)
+    }
+}
+
+impl PatternOf<&Certificate> for CertificatePattern {
+    fn is_match(&self, subject: &Certificate) -> MatchOutcome {
+        let cert = match subject.certificate.as_ref() {
+            Some(x) => x,
+            None => return MatchOutcome::Uncertain,
+        };
+
+        let a = match &self.kind {
+            Some(x) => MatchOutcome::if_true(certificate_kinds(cert).contains(x)),
+            None => MatchOutcome::Positive,
+        };
+
+        let b = match_optional(
+            &self.stake_credential,
+            stake_credential(cert).and_then(credential_hash),
+        );
+
+        let c = match_optional(&self.pool, pool_keyhash(cert));
+
+        let d = match_optional(&self.drep, drep(cert));
+
+        MatchOutcome::fold_all_of([a, b, c, d].into_iter())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WithdrawalPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reward_account: Option<FlexBytes>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coin: Option<CoinPattern>,
+}
+
+impl FromBech32 for WithdrawalPattern {
+    fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
+        match hrp {
+            "stake" | "stake_test" => Some(Self {
+                reward_account: Some(FlexBytes(content)),
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for WithdrawalPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bech32(s)
+    }
+}
+
+impl PatternOf<&Withdrawal> for WithdrawalPattern {
+    fn is_match(&self, subject: &Withdrawal) -> MatchOutcome {
+        let a = self
+            .reward_account
+            .is_match(subject.reward_account.as_ref());
+
+        let b = self.coin.is_match(subject.coin);
+
+        MatchOutcome::fold_all_of([a, b].into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::interop::utxorpc::spec::cardano::{
+        StakeDelegationCert, StakeRegDelegCert, VoteDelegCert,
+    };
+
+    use super::*;
+
+    const POOL_A: &str = "c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4";
+    const POOL_B: &str = "d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5d5";
+    const CRED: &str = "337b62cfff6403a06a3acbc34f8c46003c69fe79a3628cefa9c47251";
+
+    fn credential() -> Option<StakeCredential> {
+        Some(StakeCredential {
+            stake_credential: Some(Cred::AddrKeyHash(hex::decode(CRED).unwrap().into())),
+        })
+    }
+
+    fn certificate(cert: CertKind) -> Certificate {
+        Certificate {
+            certificate: Some(cert),
+            redeemer: None,
+        }
+    }
+
+    #[test]
+    fn delegation_match() {
+        let shelley = certificate(CertKind::StakeDelegation(StakeDelegationCert {
+            stake_credential: credential(),
+            pool_keyhash: hex::decode(POOL_A).unwrap().into(),
+        }));
+
+        let conway = certificate(CertKind::StakeRegDelegCert(StakeRegDelegCert {
+            stake_credential: credential(),
+            pool_keyhash: hex::decode(POOL_B).unwrap().into(),
+            coin: 2000000,
+        }));
+
+        let vote = certificate(CertKind::VoteDelegCert(VoteDelegCert {
+            stake_credential: credential(),
+            drep: Some(DRep {
+                drep: Some(Drep::Abstain(true)),
+            }),
+        }));
+
+        let pattern = |pool: &str| CertificatePattern {
+            kind: Some(CertificateKind::StakeDelegation),
+            pool: Some(FlexBytes::from_hex(pool).unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(pattern(POOL_A).is_match(&shelley), MatchOutcome::Positive);
+        assert_eq!(pattern(POOL_A).is_match(&conway), MatchOutcome::Negative);
+        assert_eq!(pattern(POOL_B).is_match(&conway), MatchOutcome::Positive);
+        assert_eq!(pattern(POOL_B).is_match(&vote), MatchOutcome::Negative);
+
+        let registration: CertificatePattern =
+            serde_json::from_str(r#"{ "kind": "stake_registration" }"#).unwrap();
+        assert_eq!(registration.is_match(&shelley), MatchOutcome::Negative);
+        assert_eq!(registration.is_match(&conway), MatchOutcome::Positive);
+
+        let by_credential = CertificatePattern {
+            stake_credential: Some(FlexBytes::from_hex(CRED).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(by_credential.is_match(&vote), MatchOutcome::Positive);
+
+        let by_drep = CertificatePattern {
+            drep: Some(FlexBytes::from_hex(CRED).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(by_drep.is_match(&vote), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn withdrawal_match() {
+        let account =
+            hex::decode("e1337b62cfff6403a06a3acbc34f8c46003c69fe79a3628cefa9c47251").unwrap();
+
+        let withdrawal = Withdrawal {
+            reward_account: account.clone().into(),
+            coin: 5000000,
+            redeemer: None,
+        };
+
+        let pattern = WithdrawalPattern {
+            reward_account: Some(FlexBytes(account)),
+            coin: Some(CoinPattern::Gte(1000000)),
+        };
+        assert_eq!(pattern.is_match(&withdrawal), MatchOutcome::Positive);
+
+        let pattern = WithdrawalPattern {
+            coin: Some(CoinPattern::Lte(1000000)),
+            ..Default::default()
+        };
+        assert_eq!(pattern.is_match(&withdrawal), MatchOutcome::Negative);
+    }
+}
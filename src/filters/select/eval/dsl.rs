@@ -0,0 +1,379 @@
+//! A small textual language for predicates
+//!
+//! Expressions combine patterns with `and`, `or`, `not` and parenthesis, such
+//! as `addr1... and not asset1... or (metadata #674 and output.coin >= 1000000)`.
+//! `not` binds tighter than `and`, which binds tighter than `or`. Terms are
+//! either any of the strings accepted as a pattern (bech32 strings, metadata
+//! labels), `metadata <label>`, `mempool` or a numeric comparison of a field.
+
+use miette::{Diagnostic, GraphicalReportHandler, GraphicalTheme, SourceSpan};
+use serde_json::json;
+use thiserror::Error;
+
+use super::*;
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("{message}")]
+#[diagnostic(code(select::dsl))]
+pub struct ParseError {
+    message: String,
+
+    #[source_code]
+    input: String,
+
+    #[label("{label}")]
+    span: SourceSpan,
+
+    label: String,
+}
+
+impl ParseError {
+    fn new(input: &str, span: (usize, usize), message: impl ToString, label: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            input: input.to_string(),
+            span: span.into(),
+            label: label.to_string(),
+        }
+    }
+
+    /// Renders the error with the offending span of the expression, without
+    /// colors so it can be embedded in other error messages
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let handler = GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor());
+
+        if handler.render_report(&mut out, self).is_err() {
+            return self.to_string();
+        }
+
+        out
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Cmp {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token<'a> {
+    Open,
+    Close,
+    Cmp(Cmp),
+    Word(&'a str),
+}
+
+/// A token and its span, as (offset, length)
+type Spanned<'a> = (Token<'a>, (usize, usize));
+
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && !matches!(c, '(' | ')' | '<' | '>' | '=')
+}
+
+fn tokenize(input: &str) -> Vec<Spanned<'_>> {
+    let mut tokens = vec![];
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+
+        let token = match c {
+            x if x.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '<' | '>' | '=' => {
+                let eq = chars.next_if(|(_, x)| *x == '=').is_some();
+
+                if eq {
+                    end += 1;
+                }
+
+                let cmp = match (c, eq) {
+                    ('<', false) => Cmp::Lt,
+                    ('<', true) => Cmp::Lte,
+                    ('>', false) => Cmp::Gt,
+                    ('>', true) => Cmp::Gte,
+                    _ => Cmp::Eq,
+                };
+
+                Token::Cmp(cmp)
+            }
+            _ => {
+                while let Some((idx, x)) = chars.next_if(|(_, x)| is_word_char(*x)) {
+                    end = idx + x.len_utf8();
+                }
+
+                Token::Word(&input[start..end])
+            }
+        };
+
+        tokens.push((token, (start, end - start)));
+    }
+
+    tokens
+}
+
+/// Builds the pattern for a numeric comparison of a field, using the json
+/// representation of the patterns
+fn field_pattern(field: &str, cmp: Cmp, value: u64) -> Option<Result<Pattern, String>> {
+    let (pattern, key) = match field {
+        "output.coin" | "output.lovelace" => ("output", "lovelace"),
+        "input.coin" | "input.lovelace" => ("input", "lovelace"),
+        "block.slot" => ("block", "slot"),
+        "block.height" => ("block", "height"),
+        "block.tx_count" => ("block", "tx_count"),
+        "block.era" => ("block", "era"),
+        "block.size" => ("block", "size"),
+        "withdrawal.coin" => ("withdrawal", "coin"),
+        "proposal.deposit" => ("proposal", "deposit"),
+        _ => return None,
+    };
+
+    let numeric = match cmp {
+        Cmp::Eq => Some(json!({ "exact": value })),
+        Cmp::Gte => Some(json!({ "gte": value })),
+        Cmp::Lte => Some(json!({ "lte": value })),
+        Cmp::Gt => value.checked_add(1).map(|x| json!({ "gte": x })),
+        Cmp::Lt => value.checked_sub(1).map(|x| json!({ "lte": x })),
+    };
+
+    let Some(numeric) = numeric else {
+        return Some(Err(format!("{value} is out of range for this comparison")));
+    };
+
+    let pattern = json!({ pattern: { key: numeric } });
+
+    Some(serde_json::from_value(pattern).map_err(|err| err.to_string()))
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Spanned<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos).map(|(x, _)| x)
+    }
+
+    fn next(&mut self) -> Option<Spanned<'a>> {
+        let next = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        next
+    }
+
+    fn next_if_word(&mut self, word: &str) -> bool {
+        if self.peek() == Some(&Token::Word(word)) {
+            self.pos += 1;
+            return true;
+        }
+
+        false
+    }
+
+    /// The span of the current token, or the end of the input if there are no
+    /// more tokens
+    fn span(&self) -> (usize, usize) {
+        match self.tokens.get(self.pos) {
+            Some((_, x)) => *x,
+            None => (self.input.len(), 0),
+        }
+    }
+
+    fn error(&self, span: (usize, usize), message: impl ToString, label: &str) -> ParseError {
+        ParseError::new(self.input, span, message, label)
+    }
+
+    fn or(&mut self) -> Result<Predicate, ParseError> {
+        let mut items = vec![self.and()?];
+
+        while self.next_if_word("or") {
+            items.push(self.and()?);
+        }
+
+        match items.len() {
+            1 => Ok(items.remove(0)),
+            _ => Ok(Predicate::any_of(items)),
+        }
+    }
+
+    fn and(&mut self) -> Result<Predicate, ParseError> {
+        let mut items = vec![self.unary()?];
+
+        while self.next_if_word("and") {
+            items.push(self.unary()?);
+        }
+
+        match items.len() {
+            1 => Ok(items.remove(0)),
+            _ => Ok(Predicate::all_of(items)),
+        }
+    }
+
+    fn unary(&mut self) -> Result<Predicate, ParseError> {
+        if self.next_if_word("not") {
+            return Ok(Predicate::not(self.unary()?));
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Predicate, ParseError> {
+        let span = self.span();
+
+        match self.next() {
+            Some((Token::Open, _)) => {
+                let inner = self.or()?;
+
+                match self.next() {
+                    Some((Token::Close, _)) => Ok(inner),
+                    _ => Err(self.error(span, "unclosed parenthesis", "opened here")),
+                }
+            }
+            Some((Token::Word(x), span)) => self.term(x, span),
+            Some(_) => Err(self.error(span, "expected a pattern", "unexpected token")),
+            None => Err(self.error(span, "expected a pattern", "expression ends here")),
+        }
+    }
+
+    fn term(&mut self, word: &'a str, span: (usize, usize)) -> Result<Predicate, ParseError> {
+        if matches!(word, "and" | "or") {
+            return Err(self.error(span, "expected a pattern", "unexpected operator"));
+        }
+
+        if word == "mempool" {
+            return Ok(Pattern::Mempool(true).into());
+        }
+
+        if word == "metadata" {
+            let span = self.span();
+
+            return match self.next() {
+                Some((Token::Word(x), _)) => {
+                    let label = format!("#{}", x.trim_start_matches('#'));
+
+                    MetadataPattern::from_str(&label)
+                        .map(|x| Pattern::from(x).into())
+                        .map_err(|err| self.error(span, err, "invalid metadata label"))
+                }
+                _ => Err(self.error(span, "expected a metadata label", "after `metadata`")),
+            };
+        }
+
+        if let Some(Token::Cmp(cmp)) = self.peek().cloned() {
+            self.pos += 1;
+
+            let value_span = self.span();
+
+            let value = match self.next() {
+                Some((Token::Word(x), _)) => x
+                    .parse::<u64>()
+                    .map_err(|_| self.error(value_span, "expected a number", "not a number"))?,
+                _ => return Err(self.error(value_span, "expected a number", "after comparison")),
+            };
+
+            return match field_pattern(word, cmp, value) {
+                Some(Ok(x)) => Ok(x.into()),
+                Some(Err(err)) => Err(self.error(value_span, err, "invalid value")),
+                None => Err(self.error(span, "unknown field", "can't be compared")),
+            };
+        }
+
+        Pattern::from_str(word)
+            .map(Predicate::from)
+            .map_err(|err| self.error(span, err, "not a valid pattern"))
+    }
+}
+
+/// Parses a predicate expression
+pub fn parse(input: &str) -> Result<Predicate, ParseError> {
+    let mut parser = Parser {
+        input,
+        tokens: tokenize(input),
+        pos: 0,
+    };
+
+    let predicate = parser.or()?;
+
+    if parser.pos < parser.tokens.len() {
+        let span = parser.span();
+        return Err(parser.error(span, "unexpected input", "expected `and`, `or` or the end"));
+    }
+
+    Ok(predicate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "addr1w8phkx6acpnf78fuvxn0mkew3l0fd058hzquvz7w36x4gtcyjy7wx";
+    const ASSET: &str = "asset17jd78wukhtrnmjh3fngzasxm8rck0l2r4hhyyt";
+
+    fn predicate(s: &str) -> Predicate {
+        Predicate::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        let parsed = parse(&format!(
+            "{ADDRESS} and not {ASSET} or (metadata #674 and output.coin >= 1000000)"
+        ))
+        .unwrap();
+
+        let output: Pattern =
+            serde_json::from_str(r#"{ "output": { "lovelace": { "gte": 1000000 } } }"#).unwrap();
+
+        let expected = Predicate::any_of(vec![
+            Predicate::all_of(vec![predicate(ADDRESS), Predicate::not(predicate(ASSET))]),
+            Predicate::all_of(vec![predicate("#674"), output.into()]),
+        ]);
+
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn single_term() {
+        assert_eq!(parse(ADDRESS).unwrap(), predicate(ADDRESS));
+        assert_eq!(parse("#674.msg[*]").unwrap(), predicate("#674.msg[*]"));
+        assert_eq!(parse("metadata 674").unwrap(), predicate("#674"));
+        assert_eq!(parse("mempool").unwrap(), Pattern::Mempool(true).into());
+    }
+
+    #[test]
+    fn comparisons() {
+        let block: Pattern =
+            serde_json::from_str(r#"{ "block": { "slot": { "gte": 11 } } }"#).unwrap();
+        assert_eq!(parse("block.slot>10").unwrap(), block.into());
+
+        let block: Pattern =
+            serde_json::from_str(r#"{ "block": { "era": { "exact": 7 } } }"#).unwrap();
+        assert_eq!(parse("block.era = 7").unwrap(), block.into());
+
+        assert!(parse("block.slot < 0").is_err());
+        assert!(parse("block.era > 300").is_err());
+    }
+
+    #[test]
+    fn error_spans() {
+        let err = parse("#674 and (#1 or #2").unwrap_err();
+        assert_eq!(err.span, (9, 1).into());
+
+        let err = parse("#674 and addr_xyz").unwrap_err();
+        assert_eq!(err.span, (9, 8).into());
+
+        let err = parse("#674 and").unwrap_err();
+        assert_eq!(err.span, (8, 0).into());
+
+        let err = parse("output.fee >= 10").unwrap_err();
+        assert_eq!(err.span, (0, 10).into());
+        assert!(err.render().contains("unknown field"));
+    }
+}
@@ -0,0 +1,192 @@
+use pallas::ledger::addresses::Address;
+
+use crate::framework::legacy_v1::{Event, EventContext, EventData};
+
+use super::*;
+
+/// The name of the variant of the event data, as it's serialized (eg:
+/// `tx_output` or `stake_delegation`)
+fn event_kind(data: &EventData) -> &'static str {
+    match data {
+        EventData::Block { .. } => "block",
+        EventData::BlockEnd { .. } => "block_end",
+        EventData::Transaction { .. } => "transaction",
+        EventData::TransactionEnd { .. } => "transaction_end",
+        EventData::TxInput { .. } => "tx_input",
+        EventData::TxOutput { .. } => "tx_output",
+        EventData::OutputAsset { .. } => "output_asset",
+        EventData::Metadata { .. } => "metadata",
+        EventData::VKeyWitness { .. } => "v_key_witness",
+        EventData::NativeWitness { .. } => "native_witness",
+        EventData::PlutusWitness { .. } => "plutus_witness",
+        EventData::PlutusRedeemer { .. } => "plutus_redeemer",
+        EventData::PlutusDatum { .. } => "plutus_datum",
+        EventData::CIP25Asset { .. } => "cip25_asset",
+        EventData::CIP15Asset { .. } => "cip15_asset",
+        EventData::Mint { .. } => "mint",
+        EventData::Collateral { .. } => "collateral",
+        EventData::NativeScript { .. } => "native_script",
+        EventData::PlutusScript { .. } => "plutus_script",
+        EventData::StakeRegistration { .. } => "stake_registration",
+        EventData::StakeDeregistration { .. } => "stake_deregistration",
+        EventData::StakeDelegation { .. } => "stake_delegation",
+        EventData::PoolRegistration { .. } => "pool_registration",
+        EventData::PoolRetirement { .. } => "pool_retirement",
+        EventData::GenesisKeyDelegation { .. } => "genesis_key_delegation",
+        EventData::MoveInstantaneousRewardsCert { .. } => "move_instantaneous_rewards_cert",
+        EventData::VoteDelegation { .. } => "vote_delegation",
+        EventData::StakeVoteDelegation { .. } => "stake_vote_delegation",
+        EventData::StakeRegistrationDelegation { .. } => "stake_registration_delegation",
+        EventData::DRepRegistration { .. } => "d_rep_registration",
+        EventData::DRepUpdate { .. } => "d_rep_update",
+        EventData::DRepRetirement { .. } => "d_rep_retirement",
+        EventData::CommitteeHotKeyAuth { .. } => "committee_hot_key_auth",
+        EventData::CommitteeResign { .. } => "committee_resign",
+        EventData::GovProposal { .. } => "gov_proposal",
+        EventData::Vote { .. } => "vote",
+        EventData::RollBack { .. } => "roll_back",
+    }
+}
+
+fn hex_field(value: &Option<String>) -> Option<Vec<u8>> {
+    value.as_ref().and_then(|x| hex::decode(x).ok())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct EventPattern {
+    /// The variant of the event data, in snake case (eg: `transaction`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<FlexBytes>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<HeightPattern>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<SlotPattern>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<FlexBytes>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_idx: Option<NumericPattern<usize>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_address: Option<StringOrStruct<AddressPattern>>,
+}
+
+impl PatternOf<&EventContext> for EventPattern {
+    fn is_match(&self, subject: &EventContext) -> MatchOutcome {
+        let block_hash = hex_field(&subject.block_hash);
+        let a = match_optional(&self.block_hash, block_hash.as_deref());
+
+        let b = match_optional(&self.block_number, subject.block_number);
+
+        let c = match_optional(&self.slot, subject.slot);
+
+        let tx_hash = hex_field(&subject.tx_hash);
+        let d = match_optional(&self.tx_hash, tx_hash.as_deref());
+
+        let e = match_optional(&self.tx_idx, subject.tx_idx);
+
+        let address = subject
+            .output_address
+            .as_ref()
+            .and_then(|x| Address::from_bech32(x).ok());
+        let f = match_optional(&self.output_address, address.as_ref());
+
+        MatchOutcome::fold_all_of([a, b, c, d, e, f].into_iter())
+    }
+}
+
+impl PatternOf<&Event> for EventPattern {
+    fn is_match(&self, subject: &Event) -> MatchOutcome {
+        let a = match &self.kind {
+            Some(x) => MatchOutcome::if_equal(x.as_str(), event_kind(&subject.data)),
+            None => MatchOutcome::Positive,
+        };
+
+        let b = self.is_match(&subject.context);
+
+        MatchOutcome::fold_all_of([a, b].into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::framework::legacy_v1::TxInputRecord;
+
+    use super::*;
+
+    fn event() -> Event {
+        Event {
+            context: EventContext {
+                slot: Some(1000),
+                tx_idx: Some(2),
+                tx_hash: Some(
+                    "9c0dfbe2e8e5ba1e4aa4e8e9a8a1ae39e5a1b5d1ad6a0c0e01b1e0e1f1c3b2a1".into(),
+                ),
+                output_address: Some(
+                    "addr1w8phkx6acpnf78fuvxn0mkew3l0fd058hzquvz7w36x4gtcyjy7wx".into(),
+                ),
+                ..Default::default()
+            },
+            data: EventData::TxInput(TxInputRecord {
+                tx_id: "abcd".into(),
+                index: 0,
+            }),
+            fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn event_match() {
+        let event = event();
+
+        let pattern = |json: &str| serde_json::from_str::<EventPattern>(json).unwrap();
+
+        let kind = pattern(r#"{ "kind": "tx_input", "slot": { "gte": 900 } }"#);
+        assert_eq!(kind.is_match(&event), MatchOutcome::Positive);
+
+        let kind = pattern(r#"{ "kind": "tx_output" }"#);
+        assert_eq!(kind.is_match(&event), MatchOutcome::Negative);
+
+        let tx = pattern(
+            r#"{ "tx_hash": "9c0dfbe2e8e5ba1e4aa4e8e9a8a1ae39e5a1b5d1ad6a0c0e01b1e0e1f1c3b2a1", "tx_idx": { "exact": 2 } }"#,
+        );
+        assert_eq!(tx.is_match(&event), MatchOutcome::Positive);
+
+        let address = pattern(
+            r#"{ "output_address": "addr1w8phkx6acpnf78fuvxn0mkew3l0fd058hzquvz7w36x4gtcyjy7wx" }"#,
+        );
+        assert_eq!(address.is_match(&event), MatchOutcome::Positive);
+
+        let missing = pattern(r#"{ "block_number": { "gte": 1 } }"#);
+        assert_eq!(missing.is_match(&event), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn kind_matches_serialized_name() {
+        let data = [
+            event().data,
+            EventData::Collateral {
+                tx_id: "abcd".into(),
+                index: 0,
+            },
+            EventData::GenesisKeyDelegation {},
+            EventData::RollBack {
+                block_slot: 0,
+                block_hash: "abcd".into(),
+            },
+        ];
+
+        for data in data {
+            let json = serde_json::to_value(&data).unwrap();
+            let name = json.as_object().unwrap().keys().next().unwrap().clone();
+
+            assert_eq!(event_kind(&data), name);
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use pallas::ledger::primitives::conway;
+use pallas::ledger::traverse::MultiEraTx;
+
+use super::*;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VoterKind {
+    Committee,
+    Drep,
+    Pool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteKind {
+    Yes,
+    No,
+    Abstain,
+}
+
+impl From<&conway::Vote> for VoteKind {
+    fn from(value: &conway::Vote) -> Self {
+        match value {
+            conway::Vote::Yes => VoteKind::Yes,
+            conway::Vote::No => VoteKind::No,
+            conway::Vote::Abstain => VoteKind::Abstain,
+        }
+    }
+}
+
+fn voter_parts(voter: &conway::Voter) -> (VoterKind, &[u8]) {
+    match voter {
+        conway::Voter::ConstitutionalCommitteeScript(x) => (VoterKind::Committee, x.as_ref()),
+        conway::Voter::ConstitutionalCommitteeKey(x) => (VoterKind::Committee, x.as_ref()),
+        conway::Voter::DRepScript(x) => (VoterKind::Drep, x.as_ref()),
+        conway::Voter::DRepKey(x) => (VoterKind::Drep, x.as_ref()),
+        conway::Voter::StakePoolKey(x) => (VoterKind::Pool, x.as_ref()),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GovActionKind {
+    ParameterChange,
+    HardForkInitiation,
+    TreasuryWithdrawals,
+    NoConfidence,
+    UpdateCommittee,
+    NewConstitution,
+    Information,
+}
+
+impl From<&conway::GovAction> for GovActionKind {
+    fn from(value: &conway::GovAction) -> Self {
+        match value {
+            conway::GovAction::ParameterChange(..) => GovActionKind::ParameterChange,
+            conway::GovAction::HardForkInitiation(..) => GovActionKind::HardForkInitiation,
+            conway::GovAction::TreasuryWithdrawals(..) => GovActionKind::TreasuryWithdrawals,
+            conway::GovAction::NoConfidence(..) => GovActionKind::NoConfidence,
+            conway::GovAction::UpdateCommittee(..) => GovActionKind::UpdateCommittee,
+            conway::GovAction::NewConstitution(..) => GovActionKind::NewConstitution,
+            conway::GovAction::Information => GovActionKind::Information,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct VotePattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voter_kind: Option<VoterKind>,
+
+    /// The hash of the voter credential (committee or DRep) or the pool id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voter: Option<FlexBytes>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vote: Option<VoteKind>,
+
+    /// The hash of the tx that submitted the voted proposal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proposal_tx: Option<FlexBytes>,
+}
+
+impl
+    PatternOf<(
+        &conway::Voter,
+        &conway::GovActionId,
+        &conway::VotingProcedure,
+    )> for VotePattern
+{
+    fn is_match(
+        &self,
+        subject: (
+            &conway::Voter,
+            &conway::GovActionId,
+            &conway::VotingProcedure,
+        ),
+    ) -> MatchOutcome {
+        let (voter, action, procedure) = subject;
+        let (kind, credential) = voter_parts(voter);
+
+        let a = match &self.voter_kind {
+            Some(x) => MatchOutcome::if_equal(x, &kind),
+            None => MatchOutcome::Positive,
+        };
+
+        let b = self.voter.is_match(credential);
+
+        let c = match &self.vote {
+            Some(x) => MatchOutcome::if_equal(x, &VoteKind::from(&procedure.vote)),
+            None => MatchOutcome::Positive,
+        };
+
+        let d = self.proposal_tx.is_match(action.transaction_id.as_ref());
+
+        MatchOutcome::fold_all_of([a, b, c, d].into_iter())
+    }
+}
+
+impl PatternOf<&MultiEraTx<'_>> for VotePattern {
+    fn is_match(&self, subject: &MultiEraTx) -> MatchOutcome {
+        let Some(tx) = subject.as_conway() else {
+            return MatchOutcome::Negative;
+        };
+
+        let votes = tx
+            .transaction_body
+            .voting_procedures
+            .iter()
+            .flat_map(|x| x.iter())
+            .flat_map(|(voter, votes)| {
+                votes
+                    .iter()
+                    .map(move |(action, procedure)| (voter, action, procedure))
+            });
+
+        self.is_any_match(votes)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProposalPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<GovActionKind>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deposit: Option<CoinPattern>,
+
+    /// The reward account that receives the deposit back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reward_account: Option<FlexBytes>,
+}
+
+impl PatternOf<&conway::ProposalProcedure> for ProposalPattern {
+    fn is_match(&self, subject: &conway::ProposalProcedure) -> MatchOutcome {
+        let a = match &self.action {
+            Some(x) => MatchOutcome::if_equal(x, &GovActionKind::from(&subject.gov_action)),
+            None => MatchOutcome::Positive,
+        };
+
+        let b = self.deposit.is_match(subject.deposit);
+
+        let c = self
+            .reward_account
+            .is_match(subject.reward_account.as_ref());
+
+        MatchOutcome::fold_all_of([a, b, c].into_iter())
+    }
+}
+
+impl PatternOf<&MultiEraTx<'_>> for ProposalPattern {
+    fn is_match(&self, subject: &MultiEraTx) -> MatchOutcome {
+        let Some(tx) = subject.as_conway() else {
+            return MatchOutcome::Negative;
+        };
+
+        let proposals = tx
+            .transaction_body
+            .proposal_procedures
+            .iter()
+            .flat_map(|x| x.iter());
+
+        self.is_any_match(proposals)
+    }
+}
+
+// votes and proposals aren't part of the u5c tx, they can only be evaluated
+// when the record carries the tx cbor
+
+impl PatternOf<&TxSubject<'_>> for VotePattern {
+    fn is_match(&self, subject: &TxSubject) -> MatchOutcome {
+        match subject.cbor {
+            Some(x) => self.is_match(x),
+            None => MatchOutcome::Uncertain,
+        }
+    }
+}
+
+impl PatternOf<&TxSubject<'_>> for ProposalPattern {
+    fn is_match(&self, subject: &TxSubject) -> MatchOutcome {
+        match subject.cbor {
+            Some(x) => self.is_match(x),
+            None => MatchOutcome::Uncertain,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::crypto::hash::Hash;
+    use pallas::ledger::primitives::Nullable;
+
+    use super::*;
+
+    const POOL: &str = "c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4c4";
+
+    #[test]
+    fn vote_match() {
+        let voter = conway::Voter::StakePoolKey(POOL.parse().unwrap());
+
+        let action = conway::GovActionId {
+            transaction_id: Hash::new([1; 32]),
+            action_index: 0,
+        };
+
+        let procedure = conway::VotingProcedure {
+            vote: conway::Vote::Yes,
+            anchor: Nullable::Null,
+        };
+
+        let subject = (&voter, &action, &procedure);
+
+        let pattern: VotePattern = serde_json::from_str(&format!(
+            r#"{{ "voter_kind": "pool", "voter": "{POOL}", "vote": "yes" }}"#
+        ))
+        .unwrap();
+        assert_eq!(pattern.is_match(subject), MatchOutcome::Positive);
+
+        let pattern = VotePattern {
+            voter_kind: Some(VoterKind::Drep),
+            ..Default::default()
+        };
+        assert_eq!(pattern.is_match(subject), MatchOutcome::Negative);
+
+        let pattern = VotePattern {
+            proposal_tx: Some(FlexBytes(vec![2; 32])),
+            ..Default::default()
+        };
+        assert_eq!(pattern.is_match(subject), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn proposal_match() {
+        let proposal = conway::ProposalProcedure {
+            deposit: 100_000_000_000,
+            reward_account: hex::decode(
+                "e1337b62cfff6403a06a3acbc34f8c46003c69fe79a3628cefa9c47251",
+            )
+            .unwrap()
+            .into(),
+            gov_action: conway::GovAction::Information,
+            anchor: conway::Anchor {
+                url: "https://example.com".into(),
+                content_hash: Hash::new([0; 32]),
+            },
+        };
+
+        let pattern: ProposalPattern =
+            serde_json::from_str(r#"{ "action": "information", "deposit": { "gte": 1000000 } }"#)
+                .unwrap();
+        assert_eq!(pattern.is_match(&proposal), MatchOutcome::Positive);
+
+        let pattern = ProposalPattern {
+            action: Some(GovActionKind::TreasuryWithdrawals),
+            ..Default::default()
+        };
+        assert_eq!(pattern.is_match(&proposal), MatchOutcome::Negative);
+    }
+}
@@ -0,0 +1,155 @@
+use serde_json::Value as JsonValue;
+
+use super::*;
+
+/// A path to nested values of a json document, using the same syntax as
+/// metadatum paths (eg: `outputs[*].address` or `context.slot`)
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct JsonPath(MetadatumPath);
+
+impl JsonPath {
+    fn select<'a>(segment: &PathSegment, subject: &'a JsonValue) -> Vec<&'a JsonValue> {
+        match (segment, subject) {
+            (PathSegment::Key(key), JsonValue::Object(x)) => x.get(key).into_iter().collect(),
+            (PathSegment::AnyKey, JsonValue::Object(x)) => x.values().collect(),
+            (PathSegment::Index(idx), JsonValue::Array(x)) => x.get(*idx).into_iter().collect(),
+            (PathSegment::AnyIndex, JsonValue::Array(x)) => x.iter().collect(),
+            _ => vec![],
+        }
+    }
+
+    pub fn resolve<'a>(&self, subject: &'a JsonValue) -> Vec<&'a JsonValue> {
+        let mut current = vec![subject];
+
+        for segment in self.0.segments() {
+            current = current
+                .into_iter()
+                .flat_map(|x| Self::select(segment, x))
+                .collect();
+        }
+
+        current
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MetadatumPath::from_str(s).map(Self)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum JsonValuePattern {
+    /// The value is equal to the given json value
+    Equals(JsonValue),
+
+    /// The value is an integer number
+    Number(NumericPattern<i128>),
+
+    Text(TextPattern),
+}
+
+impl PatternOf<&JsonValue> for JsonValuePattern {
+    fn is_match(&self, subject: &JsonValue) -> MatchOutcome {
+        match (self, subject) {
+            (JsonValuePattern::Equals(x), subject) => MatchOutcome::if_equal(x, subject),
+            (JsonValuePattern::Number(x), JsonValue::Number(subject)) => {
+                let subject = subject
+                    .as_i64()
+                    .map(i128::from)
+                    .or(subject.as_u64().map(i128::from));
+
+                match subject {
+                    Some(subject) => x.is_match(subject),
+                    None => MatchOutcome::Negative,
+                }
+            }
+            (JsonValuePattern::Text(x), JsonValue::String(subject)) => x.is_match(subject.as_str()),
+            _ => MatchOutcome::Negative,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct JsonPattern {
+    /// The values to match, the root of the document if omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<JsonPath>,
+
+    /// If omitted, the pattern matches if the path exists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<JsonValuePattern>,
+}
+
+impl PatternOf<&JsonValue> for JsonPattern {
+    fn is_match(&self, subject: &JsonValue) -> MatchOutcome {
+        let values = match &self.path {
+            Some(x) => x.resolve(subject),
+            None => vec![subject],
+        };
+
+        match &self.value {
+            Some(x) => x.is_any_match(values.into_iter()),
+            None => MatchOutcome::if_false(values.is_empty()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn subject() -> JsonValue {
+        json!({
+            "context": { "slot": 1000, "tx_hash": "abcd" },
+            "outputs": [
+                { "address": "addr1xyz", "amount": 5000000 },
+                { "address": "addr1abc", "amount": 1000000 },
+            ],
+        })
+    }
+
+    #[test]
+    fn path_match() {
+        let subject = subject();
+
+        let pattern = |json: &str| serde_json::from_str::<JsonPattern>(json).unwrap();
+
+        let exists = pattern(r#"{ "path": "context.tx_hash" }"#);
+        assert_eq!(exists.is_match(&subject), MatchOutcome::Positive);
+
+        let missing = pattern(r#"{ "path": "context.block_hash" }"#);
+        assert_eq!(missing.is_match(&subject), MatchOutcome::Negative);
+
+        let equals = pattern(r#"{ "path": "context.slot", "value": { "Equals": 1000 } }"#);
+        assert_eq!(equals.is_match(&subject), MatchOutcome::Positive);
+
+        let number = pattern(
+            r#"{ "path": "outputs[*].amount", "value": { "Number": { "gte": 2000000 } } }"#,
+        );
+        assert_eq!(number.is_match(&subject), MatchOutcome::Positive);
+
+        let number = pattern(
+            r#"{ "path": "outputs[1].amount", "value": { "Number": { "gte": 2000000 } } }"#,
+        );
+        assert_eq!(number.is_match(&subject), MatchOutcome::Negative);
+
+        let text = pattern(
+            r#"{ "path": "outputs.*.address", "value": { "Text": { "StartsWith": "addr1" } } }"#,
+        );
+        assert_eq!(text.is_match(&subject), MatchOutcome::Negative);
+
+        let text = pattern(
+            r#"{ "path": "outputs[*].address", "value": { "Text": { "StartsWith": "addr1" } } }"#,
+        );
+        assert_eq!(text.is_match(&subject), MatchOutcome::Positive);
+
+        let mismatched =
+            pattern(r#"{ "path": "context.tx_hash", "value": { "Number": { "exact": 1 } } }"#);
+        assert_eq!(mismatched.is_match(&subject), MatchOutcome::Negative);
+    }
+}
@@ -1,50 +1,277 @@
+use std::fmt;
+
+use pallas::interop::utxorpc::spec::cardano::metadatum::Metadatum as MetadatumKind;
+use regex::Regex;
+use serde::de::{self, Visitor};
+use serde::{Deserializer, Serializer};
+
 use super::*;
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MetadatumPairPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<MetadatumPattern>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<MetadatumPattern>,
+}
+
+impl PatternOf<&MetadatumPair> for MetadatumPairPattern {
+    fn is_match(&self, subject: &MetadatumPair) -> MatchOutcome {
+        let a = match &self.key {
+            Some(x) => x.is_some_match(subject.key.as_ref()),
+            None => MatchOutcome::Positive,
+        };
+
+        let b = match &self.value {
+            Some(x) => x.is_some_match(subject.value.as_ref()),
+            None => MatchOutcome::Positive,
+        };
+
+        MatchOutcome::fold_all_of([a, b].into_iter())
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum MetadatumPattern {
     Text(TextPattern),
     Int(NumericPattern<i64>),
-    // TODO: bytes, array, map
+    Bytes(FlexBytes),
+
+    /// Each of the patterns needs to match at least one of the items
+    Array(Vec<MetadatumPattern>),
+
+    /// Each of the patterns needs to match at least one of the pairs
+    Map(Vec<MetadatumPairPattern>),
 }
 
 impl PatternOf<&Metadatum> for MetadatumPattern {
     fn is_match(&self, subject: &Metadatum) -> MatchOutcome {
+        match (self, subject.metadatum.as_ref()) {
+            (MetadatumPattern::Text(x), _) => x.is_match(subject),
+            (MetadatumPattern::Int(x), Some(MetadatumKind::Int(subject))) => x.is_match(*subject),
+            (MetadatumPattern::Bytes(x), Some(MetadatumKind::Bytes(subject))) => {
+                x.is_match(subject.as_ref())
+            }
+            (MetadatumPattern::Array(x), Some(MetadatumKind::Array(subject))) => {
+                let outcomes = x.iter().map(|x| x.is_any_match(subject.items.iter()));
+                MatchOutcome::fold_all_of(outcomes)
+            }
+            (MetadatumPattern::Map(x), Some(MetadatumKind::Map(subject))) => {
+                let outcomes = x.iter().map(|x| x.is_any_match(subject.pairs.iter()));
+                MatchOutcome::fold_all_of(outcomes)
+            }
+            _ => MatchOutcome::Negative,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathSegment {
+    /// The value of a map entry whose key is a text, an int or hex-encoded
+    /// bytes equal to the segment
+    Key(String),
+
+    /// The value of every entry of a map
+    AnyKey,
+
+    Index(usize),
+
+    /// Every item of an array
+    AnyIndex,
+}
+
+impl PathSegment {
+    fn matches_key(key: &str, subject: Option<&Metadatum>) -> bool {
+        match subject.and_then(|x| x.metadatum.as_ref()) {
+            Some(MetadatumKind::Text(x)) => x == key,
+            Some(MetadatumKind::Int(x)) => x.to_string() == key,
+            Some(MetadatumKind::Bytes(x)) => hex::encode(x) == key,
+            _ => false,
+        }
+    }
+
+    fn select<'a>(&self, subject: &'a Metadatum) -> Vec<&'a Metadatum> {
+        match (self, subject.metadatum.as_ref()) {
+            (PathSegment::Key(key), Some(MetadatumKind::Map(map))) => map
+                .pairs
+                .iter()
+                .filter(|x| Self::matches_key(key, x.key.as_ref()))
+                .filter_map(|x| x.value.as_ref())
+                .collect(),
+            (PathSegment::AnyKey, Some(MetadatumKind::Map(map))) => {
+                map.pairs.iter().filter_map(|x| x.value.as_ref()).collect()
+            }
+            (PathSegment::Index(idx), Some(MetadatumKind::Array(array))) => {
+                array.items.get(*idx).into_iter().collect()
+            }
+            (PathSegment::AnyIndex, Some(MetadatumKind::Array(array))) => {
+                array.items.iter().collect()
+            }
+            _ => vec![],
+        }
+    }
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            MetadatumPattern::Text(x) => x.is_match(subject),
-            MetadatumPattern::Int(_) => todo!(),
+            PathSegment::Key(x) => write!(f, ".{x}"),
+            PathSegment::AnyKey => f.write_str(".*"),
+            PathSegment::Index(x) => write!(f, "[{x}]"),
+            PathSegment::AnyIndex => f.write_str("[*]"),
         }
     }
 }
 
+/// A path to nested values of a metadatum, such as `msg[*]` or
+/// `<policy>.<asset>.name`
+///
+/// Segments are separated by dots and select the value of the map entries with
+/// the given key (or any key, using `*`). Array items are selected using
+/// `[<index>]` or `[*]` for any of them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetadatumPath(Vec<PathSegment>);
+
+impl MetadatumPath {
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.0
+    }
+
+    pub fn resolve<'a>(&self, subject: &'a Metadatum) -> Vec<&'a Metadatum> {
+        let mut current = vec![subject];
+
+        for segment in self.0.iter() {
+            current = current
+                .into_iter()
+                .flat_map(|x| segment.select(x))
+                .collect();
+        }
+
+        current
+    }
+}
+
+impl FromStr for MetadatumPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = vec![];
+
+        for part in s.split('.') {
+            let (key, mut indexes) = match part.find('[') {
+                Some(x) => part.split_at(x),
+                None => (part, ""),
+            };
+
+            match key {
+                "" if segments.is_empty() && !indexes.is_empty() => (),
+                "" => anyhow::bail!("empty key in metadatum path '{s}'"),
+                "*" => segments.push(PathSegment::AnyKey),
+                x => segments.push(PathSegment::Key(x.to_string())),
+            }
+
+            while !indexes.is_empty() {
+                let end = indexes
+                    .find(']')
+                    .ok_or_else(|| anyhow::anyhow!("unclosed index in metadatum path '{s}'"))?;
+
+                let segment = match &indexes[1..end] {
+                    "*" => PathSegment::AnyIndex,
+                    x => PathSegment::Index(x.parse()?),
+                };
+
+                segments.push(segment);
+                indexes = &indexes[end + 1..];
+
+                if !indexes.is_empty() && !indexes.starts_with('[') {
+                    anyhow::bail!("unexpected characters after index in metadatum path '{s}'");
+                }
+            }
+        }
+
+        Ok(Self(segments))
+    }
+}
+
+impl fmt::Display for MetadatumPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let out: String = self.0.iter().map(|x| x.to_string()).collect();
+        f.write_str(out.strip_prefix('.').unwrap_or(&out))
+    }
+}
+
+impl Serialize for MetadatumPath {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+struct MetadatumPathVisitor;
+
+impl Visitor<'_> for MetadatumPathVisitor {
+    type Value = MetadatumPath;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a metadatum path")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        MetadatumPath::from_str(value).map_err(de::Error::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for MetadatumPath {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(MetadatumPathVisitor)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct MetadataPattern {
     #[serde(skip_serializing_if = "Option::is_none")]
     label: Option<u64>,
 
+    /// Where to look for the value inside of the metadatum of the label. If
+    /// the path doesn't exist, the pattern doesn't match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<MetadatumPath>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     value: Option<MetadatumPattern>,
 }
 
-use regex::Regex;
-
 impl FromStr for MetadataPattern {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let re = Regex::new(r"#(\d+)").unwrap();
+        let re = Regex::new(r"^#(\d+)(?:\.(.+))?$").unwrap();
 
         if let Some(caps) = re.captures(s) {
-            if caps.len() == 2 {
-                let label = caps[1].parse()?;
+            let label = caps[1].parse()?;
 
-                return Ok(Self {
-                    label: Some(label),
-                    ..Default::default()
-                });
-            }
+            let path = match caps.get(2) {
+                Some(x) => Some(MetadatumPath::from_str(x.as_str())?),
+                None => None,
+            };
+
+            return Ok(Self {
+                label: Some(label),
+                path,
+                ..Default::default()
+            });
         }
 
-        anyhow::bail!("can't parse string as metadata pattern (expected #<u64>)");
+        anyhow::bail!("can't parse string as metadata pattern (expected #<u64>[.<path>])");
     }
 }
 
@@ -52,7 +279,14 @@ impl PatternOf<&Metadata> for MetadataPattern {
     fn is_match(&self, subject: &Metadata) -> MatchOutcome {
         let a = self.label.is_match(subject.label);
 
-        let b = self.value.is_any_match(subject.value.iter());
+        let b = match &self.path {
+            Some(path) => {
+                let values = subject.value.iter().flat_map(|x| path.resolve(x));
+                let outcomes = values.map(|x| self.value.is_match(x));
+                MatchOutcome::fold_any_of(outcomes)
+            }
+            None => self.value.is_any_match(subject.value.iter()),
+        };
 
         MatchOutcome::fold_all_of([a, b].into_iter())
     }
@@ -66,6 +300,8 @@ impl PatternOf<&AuxData> for MetadataPattern {
 
 #[cfg(test)]
 mod tests {
+    use pallas::interop::utxorpc::spec::cardano::{MetadatumArray, MetadatumMap};
+
     use super::*;
 
     #[test]
@@ -79,6 +315,108 @@ mod tests {
         assert_eq!(parsed, expected);
     }
 
+    #[test]
+    fn path_parse() {
+        let parsed = MetadataPattern::from_str("#674.msg[*]").unwrap();
+        assert_eq!(parsed.label, Some(674));
+        assert_eq!(
+            parsed.path,
+            Some(MetadatumPath(vec![
+                PathSegment::Key("msg".into()),
+                PathSegment::AnyIndex
+            ]))
+        );
+
+        let path = MetadatumPath::from_str("abc.*.name[0][*]").unwrap();
+        assert_eq!(path.to_string(), "abc.*.name[0][*]");
+
+        assert!(MetadatumPath::from_str("abc..name").is_err());
+        assert!(MetadatumPath::from_str("abc[0").is_err());
+        assert!(MetadatumPath::from_str("abc[x]").is_err());
+    }
+
+    fn text(x: &str) -> Metadatum {
+        Metadatum {
+            metadatum: Some(MetadatumKind::Text(x.into())),
+        }
+    }
+
+    fn map(pairs: Vec<(&str, Metadatum)>) -> Metadatum {
+        let pairs = pairs
+            .into_iter()
+            .map(|(k, v)| MetadatumPair {
+                key: Some(text(k)),
+                value: Some(v),
+            })
+            .collect();
+
+        Metadatum {
+            metadatum: Some(MetadatumKind::Map(MetadatumMap { pairs })),
+        }
+    }
+
+    fn array(items: Vec<Metadatum>) -> Metadatum {
+        Metadatum {
+            metadatum: Some(MetadatumKind::Array(MetadatumArray { items })),
+        }
+    }
+
+    fn int(x: i64) -> Metadatum {
+        Metadatum {
+            metadatum: Some(MetadatumKind::Int(x)),
+        }
+    }
+
+    #[test]
+    fn nested_match() {
+        let cip20 = Metadata {
+            label: 674,
+            value: Some(map(vec![(
+                "msg",
+                array(vec![text("hello"), text("foo bar")]),
+            )])),
+        };
+
+        let cip25 = Metadata {
+            label: 721,
+            value: Some(map(vec![(
+                "f0ff48bbb7bbe9d59a40f1ce90e9e9d0ff5002ec48f232b49ca0fb9a",
+                map(vec![(
+                    "bud42",
+                    map(vec![("name", text("SpaceBud #42")), ("rank", int(12))]),
+                )]),
+            )])),
+        };
+
+        let pattern = |json: &str| serde_json::from_str::<MetadataPattern>(json).unwrap();
+
+        let contains_foo = pattern(
+            r#"{ "label": 674, "path": "msg[*]", "value": { "Text": { "Contains": "foo" } } }"#,
+        );
+        assert_eq!(contains_foo.is_match(&cip20), MatchOutcome::Positive);
+        assert_eq!(contains_foo.is_match(&cip25), MatchOutcome::Negative);
+
+        let name = pattern(
+            r#"{ "label": 721, "path": "f0ff48bbb7bbe9d59a40f1ce90e9e9d0ff5002ec48f232b49ca0fb9a.*.name", "value": { "Text": { "StartsWith": "SpaceBud" } } }"#,
+        );
+        assert_eq!(name.is_match(&cip25), MatchOutcome::Positive);
+
+        let rank = pattern(r#"{ "path": "*.bud42.rank", "value": { "Int": { "lte": 20 } } }"#);
+        assert_eq!(rank.is_match(&cip25), MatchOutcome::Positive);
+
+        let missing = pattern(r#"{ "path": "*.bud43" }"#);
+        assert_eq!(missing.is_match(&cip25), MatchOutcome::Negative);
+
+        let array_pattern = pattern(
+            r#"{ "path": "msg", "value": { "Array": [{ "Text": { "Exact": "hello" } }] } }"#,
+        );
+        assert_eq!(array_pattern.is_match(&cip20), MatchOutcome::Positive);
+
+        let map_pattern =
+            pattern(r#"{ "label": 721, "value": { "Map": [{ "key": { "Bytes": "" } }] } }"#);
+        assert_eq!(map_pattern.is_match(&cip25), MatchOutcome::Negative);
+    }
+
     #[test]
     fn label_match() {
         let pattern = |label: u64| {
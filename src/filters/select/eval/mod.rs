@@ -1,8 +1,9 @@
 use std::{ops::Deref, str::FromStr};
 
 use pallas::interop::utxorpc::spec::cardano::{
-    Asset, AuxData, Metadata, Metadatum, Multiasset, TxInput, TxOutput,
+    Asset, AuxData, Metadata, Metadatum, MetadatumPair, Multiasset, TxInput, TxOutput,
 };
+use pallas::ledger::traverse::{MultiEraBlock, MultiEraTx};
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
@@ -10,20 +11,37 @@ use crate::framework::*;
 
 mod address;
 mod assets;
+mod block;
 mod bytes;
+mod cert;
 mod cip14;
+pub mod dsl;
+mod event;
+mod governance;
+mod json;
 mod metadata;
+mod quantity;
+mod script;
 mod serde_ext;
+mod text;
 
 #[cfg(test)]
 mod testing;
 
 pub use address::*;
 pub use assets::*;
+pub use block::*;
 pub use bytes::*;
+pub use cert::*;
 pub use cip14::*;
+pub use event::*;
+pub use governance::*;
+pub use json::*;
 pub use metadata::*;
+pub use quantity::*;
+pub use script::*;
 pub use serde_ext::*;
+pub use text::*;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum MatchOutcome {
@@ -148,6 +166,20 @@ where
     }
 }
 
+/// Matches an optional pattern against an optional subject. The absence of a
+/// pattern matches everything while a missing subject only matches the
+/// absence of a pattern.
+pub fn match_optional<P, S>(pattern: &Option<P>, subject: Option<S>) -> MatchOutcome
+where
+    P: PatternOf<S>,
+{
+    match (pattern, subject) {
+        (None, _) => MatchOutcome::Positive,
+        (Some(x), Some(subject)) => x.is_match(subject),
+        (Some(_), None) => MatchOutcome::Negative,
+    }
+}
+
 impl PatternOf<&[u8]> for Vec<u8> {
     fn is_match(&self, subject: &[u8]) -> MatchOutcome {
         MatchOutcome::if_equal(self.as_ref(), subject)
@@ -183,49 +215,16 @@ pub enum NumericPattern<I: Ord + Eq> {
 
 pub type CoinPattern = NumericPattern<u64>;
 
-impl PatternOf<u64> for CoinPattern {
-    fn is_match(&self, subject: u64) -> MatchOutcome {
+impl<I> PatternOf<I> for NumericPattern<I>
+where
+    I: Ord + Eq + Copy,
+{
+    fn is_match(&self, subject: I) -> MatchOutcome {
         match self {
-            CoinPattern::Exact(x) => MatchOutcome::if_true(subject == *x),
-            CoinPattern::Gte(x) => MatchOutcome::if_true(subject >= *x),
-            CoinPattern::Lte(x) => MatchOutcome::if_true(subject <= *x),
-            CoinPattern::Between(a, b) => MatchOutcome::if_true(subject >= *a && subject <= *b),
-        }
-    }
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub enum TextPattern {
-    Exact(String),
-    // TODO: Regex
-}
-
-impl PatternOf<&str> for TextPattern {
-    fn is_match(&self, subject: &str) -> MatchOutcome {
-        match self {
-            TextPattern::Exact(x) => MatchOutcome::if_equal(x.as_str(), subject),
-        }
-    }
-}
-
-impl PatternOf<&[u8]> for TextPattern {
-    fn is_match(&self, subject: &[u8]) -> MatchOutcome {
-        let subject = match String::from_utf8(subject.to_vec()) {
-            Ok(subject) => subject,
-            Err(_) => return MatchOutcome::Uncertain,
-        };
-
-        self.is_match(subject.as_str())
-    }
-}
-
-impl PatternOf<&Metadatum> for TextPattern {
-    fn is_match(&self, subject: &Metadatum) -> MatchOutcome {
-        match subject.metadatum.as_ref() {
-            Some(pallas::interop::utxorpc::spec::cardano::metadatum::Metadatum::Text(subject)) => {
-                self.is_match(subject.as_str())
-            }
-            _ => MatchOutcome::Negative,
+            NumericPattern::Exact(x) => MatchOutcome::if_true(subject == *x),
+            NumericPattern::Gte(x) => MatchOutcome::if_true(subject >= *x),
+            NumericPattern::Lte(x) => MatchOutcome::if_true(subject <= *x),
+            NumericPattern::Between(a, b) => MatchOutcome::if_true(subject >= *a && subject <= *b),
         }
     }
 }
@@ -260,11 +259,6 @@ impl PatternOf<&[u8]> for DatumPattern {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct ScriptPattern {
-    hash: Option<Vec<u8>>,
-}
-
 #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct OutputPattern {
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -278,6 +272,10 @@ pub struct OutputPattern {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     datum: Option<StringOrStruct<DatumPattern>>,
+
+    /// The number of distinct assets held by the output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    asset_count: Option<NumericPattern<u64>>,
 }
 
 impl PatternOf<&TxOutput> for OutputPattern {
@@ -297,7 +295,10 @@ impl PatternOf<&TxOutput> for OutputPattern {
             .datum
             .is_some_match(subject.datum.as_ref().map(|d| d.hash.as_ref()));
 
-        MatchOutcome::fold_all_of([a, b, c, d].into_iter())
+        let asset_count = subject.assets.iter().map(|x| x.assets.len() as u64).sum();
+        let e = self.asset_count.is_match(asset_count);
+
+        MatchOutcome::fold_all_of([a, b, c, d, e].into_iter())
     }
 }
 
@@ -362,25 +363,74 @@ impl PatternOf<&Multiasset> for MintPattern {
     }
 }
 
+/// A tx along with its cbor, when the record carries it
+#[derive(Clone, Copy)]
+pub struct TxSubject<'a> {
+    pub parsed: &'a ParsedTx,
+    pub cbor: Option<&'a MultiEraTx<'a>>,
+}
+
+impl<'a> From<&'a ParsedTx> for TxSubject<'a> {
+    fn from(value: &'a ParsedTx) -> Self {
+        Self {
+            parsed: value,
+            cbor: None,
+        }
+    }
+}
+
+impl Deref for TxSubject<'_> {
+    type Target = ParsedTx;
+
+    fn deref(&self) -> &Self::Target {
+        self.parsed
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct TxPattern {
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     inputs: Vec<InputPattern>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     outputs: Vec<OutputPattern>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     mint: Vec<MintPattern>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     metadata: Vec<MetadataPattern>,
-    // the u5c struct is not suitable, it lacks hash for the scripts
-    // scripts: Vec<ScriptPattern>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    certificates: Vec<StringOrStruct<CertificatePattern>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    withdrawals: Vec<StringOrStruct<WithdrawalPattern>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    votes: Vec<VotePattern>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    proposals: Vec<ProposalPattern>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    reference_inputs: Vec<InputPattern>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    collateral: Vec<InputPattern>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    scripts: Vec<StringOrStruct<ScriptPattern>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    redeemers: Vec<RedeemerPattern>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    quantities: Vec<QuantityPattern>,
 }
 
-impl PatternOf<&ParsedTx> for TxPattern {
-    fn is_match(&self, tx: &ParsedTx) -> MatchOutcome {
+impl PatternOf<&TxSubject<'_>> for TxPattern {
+    fn is_match(&self, tx: &TxSubject) -> MatchOutcome {
         let a = self.inputs.iter().map(|x| x.is_any_match(tx.inputs.iter()));
 
         let a = MatchOutcome::fold_all_of(a);
@@ -403,7 +453,68 @@ impl PatternOf<&ParsedTx> for TxPattern {
 
         let d = MatchOutcome::fold_all_of(d);
 
-        MatchOutcome::fold_all_of([a, b, c, d].into_iter())
+        let e = self
+            .certificates
+            .iter()
+            .map(|x| x.is_any_match(tx.certificates.iter()));
+
+        let e = MatchOutcome::fold_all_of(e);
+
+        let f = self
+            .withdrawals
+            .iter()
+            .map(|x| x.is_any_match(tx.withdrawals.iter()));
+
+        let f = MatchOutcome::fold_all_of(f);
+
+        let g = self.votes.iter().map(|x| x.is_match(tx));
+
+        let g = MatchOutcome::fold_all_of(g);
+
+        let h = self.proposals.iter().map(|x| x.is_match(tx));
+
+        let h = MatchOutcome::fold_all_of(h);
+
+        let i = self
+            .reference_inputs
+            .iter()
+            .map(|x| x.is_any_match(tx.reference_inputs.iter()));
+
+        let i = MatchOutcome::fold_all_of(i);
+
+        let j = self
+            .collateral
+            .iter()
+            .map(|x| x.is_any_match(iter_tx_collateral(tx)));
+
+        let j = MatchOutcome::fold_all_of(j);
+
+        let scripts = tx_scripts(tx);
+
+        let k = self.scripts.iter().map(|x| x.is_any_match(scripts.iter()));
+
+        let k = MatchOutcome::fold_all_of(k);
+
+        let redeemers = tx_redeemers(tx);
+
+        let l = self
+            .redeemers
+            .iter()
+            .map(|x| x.is_any_match(redeemers.iter()));
+
+        let l = MatchOutcome::fold_all_of(l);
+
+        let m = self.quantities.iter().map(|x| x.is_match(tx.parsed));
+
+        let m = MatchOutcome::fold_all_of(m);
+
+        MatchOutcome::fold_all_of([a, b, c, d, e, f, g, h, i, j, k, l, m].into_iter())
+    }
+}
+
+impl PatternOf<&ParsedTx> for TxPattern {
+    fn is_match(&self, tx: &ParsedTx) -> MatchOutcome {
+        self.is_match(&TxSubject::from(tx))
     }
 }
 
@@ -411,19 +522,11 @@ pub type SlotPattern = NumericPattern<u64>;
 
 pub type EraPattern = NumericPattern<u8>;
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct BlockPattern {
-    hash: Option<Vec<u8>>,
-    slot: Option<SlotPattern>,
-    era: Option<EraPattern>,
-    txs: Vec<TxPattern>,
-}
-
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Pattern {
-    Block(BlockPattern),
-    Tx(TxPattern),
+    Block(Box<BlockPattern>),
+    Tx(Box<TxPattern>),
     Address(StringOrStruct<AddressPattern>),
     Asset(StringOrStruct<AssetPattern>),
     Input(InputPattern),
@@ -431,6 +534,21 @@ pub enum Pattern {
     Mint(MintPattern),
     Metadata(StringOrStruct<MetadataPattern>),
     Datum(StringOrStruct<DatumPattern>),
+    Certificate(StringOrStruct<CertificatePattern>),
+    Withdrawal(StringOrStruct<WithdrawalPattern>),
+    Vote(VotePattern),
+    Proposal(ProposalPattern),
+    #[serde(rename = "reference_input")]
+    ReferenceInput(InputPattern),
+    Collateral(InputPattern),
+    Script(StringOrStruct<ScriptPattern>),
+    Redeemer(RedeemerPattern),
+    Event(EventPattern),
+    Json(JsonPattern),
+    Quantity(Box<QuantityPattern>),
+
+    /// Matches the events of the mempool if true, the confirmed ones if false
+    Mempool(bool),
 }
 
 impl From<AssetPattern> for Pattern {
@@ -457,6 +575,18 @@ impl From<MetadataPattern> for Pattern {
     }
 }
 
+impl From<CertificatePattern> for Pattern {
+    fn from(value: CertificatePattern) -> Self {
+        Pattern::Certificate(StringOrStruct(value))
+    }
+}
+
+impl From<ScriptPattern> for Pattern {
+    fn from(value: ScriptPattern) -> Self {
+        Pattern::Script(StringOrStruct(value))
+    }
+}
+
 impl FromBech32 for Pattern {
     fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
         match hrp {
@@ -464,7 +594,13 @@ impl FromBech32 for Pattern {
             "addr" => AddressPattern::from_bech32_parts(hrp, content).map(From::from),
             "addr_test" => AddressPattern::from_bech32_parts(hrp, content).map(From::from),
             "stake" => AddressPattern::from_bech32_parts(hrp, content).map(From::from),
+            "stake_test" => AddressPattern::from_bech32_parts(hrp, content).map(From::from),
+            "addr_vkh" => AddressPattern::from_bech32_parts(hrp, content).map(From::from),
+            "stake_vkh" => AddressPattern::from_bech32_parts(hrp, content).map(From::from),
             "datum" => DatumPattern::from_bech32_parts(hrp, content).map(From::from),
+            "pool" => CertificatePattern::from_bech32_parts(hrp, content).map(From::from),
+            "drep" => CertificatePattern::from_bech32_parts(hrp, content).map(From::from),
+            "script" => ScriptPattern::from_bech32_parts(hrp, content).map(From::from),
             _ => None,
         }
     }
@@ -506,6 +642,10 @@ fn iter_tx_assets(tx: &ParsedTx) -> impl Iterator<Item = &Multiasset> {
     a.chain(b)
 }
 
+fn iter_tx_collateral(tx: &ParsedTx) -> impl Iterator<Item = &TxInput> {
+    tx.collateral.iter().flat_map(|x| x.collateral.iter())
+}
+
 fn iter_tx_datums(tx: &ParsedTx) -> impl Iterator<Item = &[u8]> {
     let a = tx
         .outputs
@@ -516,10 +656,13 @@ fn iter_tx_datums(tx: &ParsedTx) -> impl Iterator<Item = &[u8]> {
     a
 }
 
-impl PatternOf<&ParsedTx> for Pattern {
-    fn is_match(&self, subject: &ParsedTx) -> MatchOutcome {
+impl PatternOf<&TxSubject<'_>> for Pattern {
+    fn is_match(&self, subject: &TxSubject) -> MatchOutcome {
         match self {
             Pattern::Block(_) => MatchOutcome::Negative,
+            Pattern::Event(_) => MatchOutcome::Negative,
+            Pattern::Mempool(x) => MatchOutcome::if_true(!x),
+            Pattern::Json(_) => MatchOutcome::Negative,
             Pattern::Tx(x) => x.is_match(subject),
             Pattern::Address(x) => x.is_any_match(iter_tx_addresses(subject)),
             Pattern::Asset(x) => x.is_any_match(iter_tx_assets(subject)),
@@ -528,10 +671,25 @@ impl PatternOf<&ParsedTx> for Pattern {
             Pattern::Mint(x) => x.is_any_match(subject.mint.iter()),
             Pattern::Metadata(x) => x.is_any_match(subject.auxiliary.iter()),
             Pattern::Datum(x) => x.is_any_match(iter_tx_datums(subject)),
+            Pattern::Certificate(x) => x.is_any_match(subject.certificates.iter()),
+            Pattern::Withdrawal(x) => x.is_any_match(subject.withdrawals.iter()),
+            Pattern::Vote(x) => x.is_match(subject),
+            Pattern::Proposal(x) => x.is_match(subject),
+            Pattern::ReferenceInput(x) => x.is_any_match(subject.reference_inputs.iter()),
+            Pattern::Collateral(x) => x.is_any_match(iter_tx_collateral(subject)),
+            Pattern::Script(x) => x.is_any_match(tx_scripts(subject).iter()),
+            Pattern::Redeemer(x) => x.is_any_match(tx_redeemers(subject).iter()),
+            Pattern::Quantity(x) => x.is_match(subject.parsed),
         }
     }
 }
 
+impl PatternOf<&ParsedTx> for Pattern {
+    fn is_match(&self, subject: &ParsedTx) -> MatchOutcome {
+        self.is_match(&TxSubject::from(subject))
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Predicate {
@@ -561,6 +719,50 @@ impl Predicate {
     pub fn not(p: Self) -> Self {
         Predicate::Not(Box::new(StringOrStruct(p)))
     }
+
+    /// Replaces the mempool patterns with their outcome for the events of the
+    /// mempool, an empty `all` always matches and an empty `any` never does
+    fn resolve_mempool(&self) -> Self {
+        let resolve = |x: &[StringOrStruct<Self>]| x.iter().map(|x| x.resolve_mempool()).collect();
+
+        match self {
+            Predicate::Match(StringOrStruct(Pattern::Mempool(true))) => Predicate::all_of(vec![]),
+            Predicate::Match(StringOrStruct(Pattern::Mempool(false))) => Predicate::any_of(vec![]),
+            Predicate::Match(_) => self.clone(),
+            Predicate::Not(x) => Predicate::not(x.resolve_mempool()),
+            Predicate::AnyOf(x) => Predicate::any_of(resolve(x)),
+            Predicate::AllOf(x) => Predicate::all_of(resolve(x)),
+        }
+    }
+}
+
+/// A predicate ready to be evaluated against chain events. The mempool
+/// patterns are resolved ahead of time for the events of the mempool, the rest
+/// of the patterns are matched against the record of the event.
+#[derive(Clone, Debug)]
+pub struct EventPredicate {
+    chain: Predicate,
+    mempool: Predicate,
+}
+
+impl From<Predicate> for EventPredicate {
+    fn from(value: Predicate) -> Self {
+        Self {
+            mempool: value.resolve_mempool(),
+            chain: value,
+        }
+    }
+}
+
+impl EventPredicate {
+    /// Evaluates the record of the event, none if the event has no record
+    pub fn eval(&self, event: &ChainEvent) -> Option<MatchOutcome> {
+        match event {
+            ChainEvent::Apply(_, r) | ChainEvent::Undo(_, r) => Some(eval(r, &self.chain)),
+            ChainEvent::Pending(_, r) | ChainEvent::Removed(_, r) => Some(eval(r, &self.mempool)),
+            ChainEvent::Reset(_) => None,
+        }
+    }
 }
 
 impl From<Pattern> for Predicate {
@@ -573,44 +775,136 @@ impl FromStr for Predicate {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let inner = Pattern::from_str(s)?;
-        Ok(inner.into())
+        dsl::parse(s).map_err(|err| anyhow::anyhow!(err.render()))
     }
 }
 
-fn eval_tx(tx: &ParsedTx, predicate: &Predicate) -> MatchOutcome {
+fn eval_pattern(
+    pattern: &Pattern,
+    block: Option<&BlockSubject>,
+    tx: Option<&TxSubject>,
+) -> MatchOutcome {
+    match (pattern, block, tx) {
+        (Pattern::Mempool(x), ..) => MatchOutcome::if_true(!x),
+        (Pattern::Block(x), Some(block), _) => x.is_match(block),
+        (Pattern::Block(_), None, _) => MatchOutcome::Negative,
+        (x, _, Some(tx)) => x.is_match(tx),
+        (_, _, None) => MatchOutcome::Negative,
+    }
+}
+
+fn eval_predicate(predicate: &Predicate, eval: &impl Fn(&Pattern) -> MatchOutcome) -> MatchOutcome {
     match predicate {
-        Predicate::Not(x) => !eval_tx(tx, x),
+        Predicate::Not(x) => !eval_predicate(x, eval),
         Predicate::AnyOf(x) => {
-            let o = x.iter().map(|x| eval_tx(tx, x));
+            let o = x.iter().map(|x| eval_predicate(x, eval));
             MatchOutcome::fold_any_of(o)
         }
         Predicate::AllOf(x) => {
-            let o = x.iter().map(|x| eval_tx(tx, x));
+            let o = x.iter().map(|x| eval_predicate(x, eval));
             MatchOutcome::fold_all_of(o)
         }
-        Predicate::Match(x) => x.is_match(tx),
+        Predicate::Match(x) => eval(x),
     }
 }
 
-fn eval_block(block: &ParsedBlock, predicate: &Predicate) -> MatchOutcome {
+/// Evaluates the predicate for a tx and, if known, the block that contains
+/// it. Block patterns are matched against the block while the rest of the
+/// patterns are matched against the tx.
+fn eval_chain_predicate(
+    predicate: &Predicate,
+    block: Option<&BlockSubject>,
+    tx: Option<&TxSubject>,
+) -> MatchOutcome {
+    eval_predicate(predicate, &|x| eval_pattern(x, block, tx))
+}
+
+fn eval_tx(tx: &TxSubject, predicate: &Predicate) -> MatchOutcome {
+    eval_chain_predicate(predicate, None, Some(tx))
+}
+
+fn eval_block(block: &BlockSubject, predicate: &Predicate) -> MatchOutcome {
+    // a block without txs can still match block patterns
+    if block.txs.is_empty() {
+        return eval_chain_predicate(predicate, Some(block), None);
+    }
+
     let outcomes = block
-        .body
-        .iter()
-        .flat_map(|b| b.tx.iter())
-        .map(|tx| eval_tx(tx, predicate));
+        .tx_subjects()
+        .map(|tx| eval_chain_predicate(predicate, Some(block), Some(&tx)));
 
     MatchOutcome::fold_any_of(outcomes)
 }
 
+fn eval_tx_cbor(cbor: &[u8], predicate: &Predicate) -> MatchOutcome {
+    let tx = match MultiEraTx::decode(cbor) {
+        Ok(x) => x,
+        Err(err) => {
+            warn!(%err, "can't decode tx cbor for the select filter");
+            return MatchOutcome::Uncertain;
+        }
+    };
+
+    let parsed = map_tx_cbor(&tx);
+
+    let subject = TxSubject {
+        parsed: &parsed,
+        cbor: Some(&tx),
+    };
+
+    eval_tx(&subject, predicate)
+}
+
+fn eval_block_cbor(cbor: &[u8], predicate: &Predicate) -> MatchOutcome {
+    let block = match MultiEraBlock::decode(cbor) {
+        Ok(x) => x,
+        Err(err) => {
+            warn!(%err, "can't decode block cbor for the select filter");
+            return MatchOutcome::Uncertain;
+        }
+    };
+
+    let parsed = map_block_cbor(&block);
+    let txs = block.txs();
+    let subject = BlockSubject::from_cbor(&block, &parsed, &txs);
+
+    eval_block(&subject, predicate)
+}
+
+/// Evaluates the predicate for a v1 event. Event and json patterns are matched
+/// against the event, the rest of the patterns can't be evaluated since v1
+/// events don't carry the whole tx.
+fn eval_event(event: &legacy_v1::Event, predicate: &Predicate) -> MatchOutcome {
+    // the event is only serialized if a json pattern needs it
+    let json = std::cell::OnceCell::new();
+
+    eval_predicate(predicate, &|x| match x {
+        Pattern::Event(x) => x.is_match(event),
+        Pattern::Json(x) => {
+            x.is_match(json.get_or_init(|| serde_json::to_value(event).unwrap_or_default()))
+        }
+        Pattern::Mempool(x) => MatchOutcome::if_true(!x),
+        _ => MatchOutcome::Uncertain,
+    })
+}
+
+fn eval_json(json: &serde_json::Value, predicate: &Predicate) -> MatchOutcome {
+    eval_predicate(predicate, &|x| match x {
+        Pattern::Json(x) => x.is_match(json),
+        Pattern::Event(_) => MatchOutcome::Negative,
+        Pattern::Mempool(x) => MatchOutcome::if_true(!x),
+        _ => MatchOutcome::Uncertain,
+    })
+}
+
 pub fn eval(record: &Record, predicate: &Predicate) -> MatchOutcome {
     match record {
-        Record::ParsedTx(x) => eval_tx(x, predicate),
-        Record::ParsedBlock(x) => eval_block(x, predicate),
-        _ => {
-            warn!("The select filter is valid only with ParsedTx & ParsedBlock records");
-            MatchOutcome::Uncertain
-        }
+        Record::ParsedTx(x) => eval_tx(&x.into(), predicate),
+        Record::ParsedBlock(x) => eval_block(&x.into(), predicate),
+        Record::CborBlock(x) => eval_block_cbor(x, predicate),
+        Record::CborTx(x) => eval_tx_cbor(x, predicate),
+        Record::OuraV1Event(x) => eval_event(x, predicate),
+        Record::GenericJson(x) => eval_json(x, predicate),
     }
 }
 
@@ -621,12 +915,159 @@ mod tests {
 
     #[test]
     fn empty_tx_pattern() {
-        let pattern = Pattern::Tx(TxPattern::default());
+        let pattern = Pattern::Tx(Default::default());
 
         let positives = testing::find_positive_test_vectors(pattern);
         assert_eq!(positives, vec![0, 1, 2, 3]);
     }
 
+    #[test]
+    fn output_asset_count() {
+        let pattern = |json: &str| serde_json::from_str::<Pattern>(json).unwrap();
+
+        let positives = testing::find_positive_test_vectors(pattern(
+            r#"{ "output": { "asset_count": { "gte": 4 } } }"#,
+        ));
+        assert_eq!(positives, vec![1]);
+
+        let positives = testing::find_positive_test_vectors(pattern(
+            r#"{ "output": { "asset_count": { "exact": 2 } } }"#,
+        ));
+        assert_eq!(positives, vec![2, 3]);
+    }
+
+    #[test]
+    fn block_and_tx_predicate() {
+        let block = ParsedBlock {
+            header: Some(pallas::interop::utxorpc::spec::cardano::BlockHeader {
+                slot: 1000,
+                ..Default::default()
+            }),
+            body: Some(pallas::interop::utxorpc::spec::cardano::BlockBody {
+                tx: testing::test_vectors(),
+            }),
+        };
+
+        let record = Record::ParsedBlock(block);
+
+        let predicate = |label: &str| {
+            let block: BlockPattern =
+                serde_json::from_str(r#"{ "slot": { "gte": 900 } }"#).unwrap();
+
+            Predicate::all_of(vec![
+                Pattern::Block(Box::new(block)).into(),
+                Predicate::from_str(label).unwrap(),
+            ])
+        };
+
+        assert_eq!(eval(&record, &predicate("#9980")), MatchOutcome::Positive);
+        assert_eq!(eval(&record, &predicate("#1")), MatchOutcome::Negative);
+
+        let block: BlockPattern = serde_json::from_str(r#"{ "slot": { "lte": 900 } }"#).unwrap();
+        let not_block = Predicate::not(Pattern::Block(Box::new(block)).into());
+        assert_eq!(eval(&record, &not_block), MatchOutcome::Positive);
+
+        let not_block = Predicate::not(predicate("#1"));
+        assert_eq!(eval(&record, &not_block), MatchOutcome::Positive);
+
+        let block: BlockPattern = serde_json::from_str(r#"{ "slot": { "gte": 900 } }"#).unwrap();
+        let not_block = Predicate::not(Pattern::Block(Box::new(block)).into());
+        assert_eq!(eval(&record, &not_block), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn cbor_tx_record() {
+        let cbor = hex::decode(concat!(
+            "84a300d9010281825820f0a39560ea80ccc68e8dffb6a4a077c8927811f06c5d9058d0fa2d1a8d04",
+            "7d2000018282581d605e4e214a6addd337126b3a61faad5dfe1e4f14f637a8969e3a05eefd1a001e",
+            "848082581d600d45f2b310a98e766cee2ab2f6756c91719bd7b35929cef058365b651a015ef3c002",
+            "00a100d90102818258200f193a88190f6dace0a3db1e0e50797a6e28cd4b6e289260dc96b5a8d793",
+            "4bf858401b13ee550f3167a1b94796f2a2f5e22d782d628336a7797c5b798f358fa564dbe92ea75a",
+            "4e2449eb2cef59c097d8497545ef1e4ea441b88a481194323ae7c608f5f6",
+        ))
+        .unwrap();
+
+        let record = Record::CborTx(cbor);
+
+        let predicate = |json: &str| serde_json::from_str::<Predicate>(json).unwrap();
+
+        let lovelace =
+            predicate(r#"{ "match": { "output": { "lovelace": { "gte": 20000000 } } } }"#);
+        assert_eq!(eval(&record, &lovelace), MatchOutcome::Positive);
+
+        let lovelace =
+            predicate(r#"{ "match": { "output": { "lovelace": { "gte": 30000000 } } } }"#);
+        assert_eq!(eval(&record, &lovelace), MatchOutcome::Negative);
+
+        let invalid = Record::CborTx(vec![0xff]);
+        assert_eq!(eval(&invalid, &lovelace), MatchOutcome::Uncertain);
+    }
+
+    #[test]
+    fn mempool_events() {
+        let tx = Record::ParsedTx(testing::test_vectors().remove(0));
+        let point = pallas::network::miniprotocols::Point::Specific(1000, vec![]);
+
+        let confirmed = ChainEvent::Apply(point.clone(), tx.clone());
+        let pending = ChainEvent::Pending(point.clone(), tx.clone());
+        let removed = ChainEvent::Removed(point.clone(), tx);
+        let reset = ChainEvent::Reset(point);
+
+        let mempool = EventPredicate::from(Predicate::from_str("mempool").unwrap());
+        assert_eq!(mempool.eval(&confirmed), Some(MatchOutcome::Negative));
+        assert_eq!(mempool.eval(&pending), Some(MatchOutcome::Positive));
+        assert_eq!(mempool.eval(&removed), Some(MatchOutcome::Positive));
+        assert_eq!(mempool.eval(&reset), None);
+
+        let confirmed_tx: Predicate = serde_json::from_str(
+            r#"{ "all": [{ "match": { "mempool": false } }, { "match": { "tx": {} } }] }"#,
+        )
+        .unwrap();
+        let confirmed_tx = EventPredicate::from(confirmed_tx);
+        assert_eq!(confirmed_tx.eval(&confirmed), Some(MatchOutcome::Positive));
+        assert_eq!(confirmed_tx.eval(&pending), Some(MatchOutcome::Negative));
+    }
+
+    #[test]
+    fn json_and_event_records() {
+        let record = Record::GenericJson(serde_json::json!({ "tx": { "fee": 170000 } }));
+
+        let predicate = |json: &str| serde_json::from_str::<Predicate>(json).unwrap();
+
+        let fee = predicate(
+            r#"{ "match": { "json": { "path": "tx.fee", "value": { "Number": { "lte": 200000 } } } } }"#,
+        );
+        assert_eq!(eval(&record, &fee), MatchOutcome::Positive);
+
+        let event = predicate(r#"{ "match": { "event": { "kind": "transaction" } } }"#);
+        assert_eq!(eval(&record, &event), MatchOutcome::Negative);
+
+        let address =
+            Predicate::from_str("addr1w8phkx6acpnf78fuvxn0mkew3l0fd058hzquvz7w36x4gtcyjy7wx")
+                .unwrap();
+        assert_eq!(eval(&record, &address), MatchOutcome::Uncertain);
+
+        let record = Record::OuraV1Event(legacy_v1::Event {
+            context: legacy_v1::EventContext {
+                slot: Some(1000),
+                ..Default::default()
+            },
+            data: legacy_v1::EventData::RollBack {
+                block_slot: 900,
+                block_hash: "abcd".into(),
+            },
+            fingerprint: None,
+        });
+
+        let rollback = predicate(r#"{ "match": { "event": { "kind": "roll_back" } } }"#);
+        assert_eq!(eval(&record, &rollback), MatchOutcome::Positive);
+
+        let slot = predicate(
+            r#"{ "match": { "json": { "path": "roll_back.block_slot", "value": { "Equals": 900 } } } }"#,
+        );
+        assert_eq!(eval(&record, &slot), MatchOutcome::Positive);
+    }
+
     #[test]
     fn parse_pattern() {
         let pattern = Pattern::from_str("addr1qx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer3n0d3vllmyqwsx5wktcd8cc3sq835lu7drv2xwl2wywfgse35a3x").unwrap();
@@ -640,6 +1081,10 @@ mod tests {
 
         let pattern = Pattern::from_str("#8888").unwrap();
         assert!(matches!(pattern, Pattern::Metadata(..)));
+
+        let pattern =
+            Pattern::from_str("pool1cnzvf3xycnzvf3xycnzvf3xycnzvf3xycnzvf3xycnzvgvc00k0").unwrap();
+        assert!(matches!(pattern, Pattern::Certificate(..)));
     }
 
     #[test]
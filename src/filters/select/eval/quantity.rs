@@ -0,0 +1,202 @@
+use super::*;
+
+pub type QuantityValuePattern = NumericPattern<i128>;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuantityScope {
+    /// The total sent to the outputs of the tx
+    #[default]
+    Outputs,
+
+    /// The total minted by the tx, burns are negative
+    Mint,
+
+    /// The net change of the balance of each address (outputs minus inputs)
+    Balance,
+}
+
+/// Aggregates the quantity of lovelace or of the matching assets across a tx
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QuantityPattern {
+    /// The assets to aggregate, lovelace if omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<StringOrStruct<AssetPattern>>,
+
+    #[serde(default)]
+    pub scope: QuantityScope,
+
+    /// Restricts the outputs and inputs to the ones of matching addresses,
+    /// ignored for the mint scope
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<StringOrStruct<AddressPattern>>,
+
+    pub quantity: QuantityValuePattern,
+}
+
+impl QuantityPattern {
+    fn asset_quantity(&self, subject: &Multiasset, mint: bool) -> i128 {
+        let Some(pattern) = &self.asset else {
+            return 0;
+        };
+
+        let policy = subject.policy_id.as_ref();
+
+        subject
+            .assets
+            .iter()
+            .filter(|x| pattern.is_match((policy, *x)) == MatchOutcome::Positive)
+            .map(|x| match mint {
+                true => x.mint_coin as i128,
+                false => x.output_coin as i128,
+            })
+            .sum()
+    }
+
+    fn output_quantity(&self, subject: &TxOutput) -> i128 {
+        match &self.asset {
+            Some(_) => subject
+                .assets
+                .iter()
+                .map(|x| self.asset_quantity(x, false))
+                .sum(),
+            None => subject.coin as i128,
+        }
+    }
+
+    /// Whether the output belongs to the addresses of the pattern, none if it
+    /// can't be determined
+    fn owns(&self, subject: &TxOutput) -> Option<bool> {
+        match self.address.is_match(subject.address.as_ref()) {
+            MatchOutcome::Positive => Some(true),
+            MatchOutcome::Negative => Some(false),
+            MatchOutcome::Uncertain => None,
+        }
+    }
+
+    fn match_outputs(&self, tx: &ParsedTx) -> MatchOutcome {
+        let mut total = 0;
+
+        for output in tx.outputs.iter() {
+            match self.owns(output) {
+                Some(true) => total += self.output_quantity(output),
+                Some(false) => (),
+                None => return MatchOutcome::Uncertain,
+            }
+        }
+
+        self.quantity.is_match(total)
+    }
+
+    fn match_mint(&self, tx: &ParsedTx) -> MatchOutcome {
+        let total = tx.mint.iter().map(|x| self.asset_quantity(x, true)).sum();
+
+        self.quantity.is_match(total)
+    }
+
+    fn match_balance(&self, tx: &ParsedTx) -> MatchOutcome {
+        let outputs = tx.outputs.iter().map(|x| (Some(x), 1));
+
+        // the balance can't be computed without the resolved inputs
+        let inputs = tx.inputs.iter().map(|x| (x.as_output.as_ref(), -1));
+
+        let mut balances: Vec<(&[u8], i128)> = vec![];
+
+        for (output, sign) in outputs.chain(inputs) {
+            let Some(output) = output else {
+                return MatchOutcome::Uncertain;
+            };
+
+            match self.owns(output) {
+                Some(true) => (),
+                Some(false) => continue,
+                None => return MatchOutcome::Uncertain,
+            }
+
+            let address = output.address.as_ref();
+            let quantity = sign * self.output_quantity(output);
+
+            match balances.iter_mut().find(|(x, _)| *x == address) {
+                Some((_, x)) => *x += quantity,
+                None => balances.push((address, quantity)),
+            }
+        }
+
+        let outcomes = balances.iter().map(|(_, x)| self.quantity.is_match(*x));
+
+        MatchOutcome::fold_any_of(outcomes)
+    }
+}
+
+impl PatternOf<&ParsedTx> for QuantityPattern {
+    fn is_match(&self, subject: &ParsedTx) -> MatchOutcome {
+        match self.scope {
+            QuantityScope::Outputs => self.match_outputs(subject),
+            QuantityScope::Mint => self.match_mint(subject),
+            QuantityScope::Balance => self.match_balance(subject),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::interop::utxorpc::spec::cardano::TxInput;
+
+    use super::*;
+
+    fn pattern(json: &str) -> QuantityPattern {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn outputs_total() {
+        let lovelace = pattern(r#"{ "quantity": { "gte": 100000000 } }"#);
+        let positives = testing::find_positive_test_vectors(Pattern::Quantity(Box::new(lovelace)));
+        assert_eq!(positives, vec![1, 2, 3]);
+
+        let assets = pattern(
+            r#"{ "asset": { "policy": "1e349c9bdea19fd6c147626a5260bc44b71635f398b67c59881df209" }, "quantity": { "exact": 690000000 } }"#,
+        );
+        let positives = testing::find_positive_test_vectors(Pattern::Quantity(Box::new(assets)));
+        assert_eq!(positives, vec![1, 3]);
+
+        let enterprise =
+            pattern(r#"{ "address": { "address_type": "enterprise" }, "quantity": { "gte": 1 } }"#);
+        let positives =
+            testing::find_positive_test_vectors(Pattern::Quantity(Box::new(enterprise)));
+        assert_eq!(positives, vec![2]);
+    }
+
+    #[test]
+    fn mint_total() {
+        let minted = pattern(
+            r#"{ "scope": "mint", "asset": { "policy": "533bb94a8850ee3ccbe483106489399112b74c905342cb1792a797a0" }, "quantity": { "gte": 0 } }"#,
+        );
+        let positives = testing::find_positive_test_vectors(Pattern::Quantity(Box::new(minted)));
+        assert_eq!(positives, vec![0, 1, 2, 3]);
+
+        let lovelace = pattern(r#"{ "scope": "mint", "quantity": { "gte": 1 } }"#);
+        let positives = testing::find_positive_test_vectors(Pattern::Quantity(Box::new(lovelace)));
+        assert_eq!(positives, Vec::<usize>::new());
+    }
+
+    #[test]
+    fn balance_change() {
+        let mut tx = testing::test_vectors().remove(2);
+
+        let balance = pattern(r#"{ "scope": "balance", "quantity": { "lte": -1000000 } }"#);
+        assert_eq!(balance.is_match(&tx), MatchOutcome::Negative);
+
+        tx.inputs.push(TxInput::default());
+        assert_eq!(balance.is_match(&tx), MatchOutcome::Uncertain);
+
+        // the address sent 1 ada more than it received
+        let mut spent = tx.outputs[0].clone();
+        spent.coin += 1000000;
+        tx.inputs[0].as_output = Some(spent);
+        assert_eq!(balance.is_match(&tx), MatchOutcome::Positive);
+
+        let received = pattern(r#"{ "scope": "balance", "quantity": { "gte": 1 } }"#);
+        assert_eq!(received.is_match(&tx), MatchOutcome::Negative);
+    }
+}
@@ -0,0 +1,394 @@
+use pallas::interop::utxorpc::spec::cardano::{
+    plutus_data, script, stake_credential::StakeCredential as Cred, PlutusData, Redeemer,
+    RedeemerPurpose,
+};
+use pallas::ledger::addresses::{Address, ShelleyPaymentPart};
+use pallas::ledger::traverse::{ComputeHash, MultiEraTx, OriginalHash};
+
+use self::serde_ext::FromBech32;
+
+use super::*;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptKind {
+    Native,
+    PlutusV1,
+    PlutusV2,
+    PlutusV3,
+}
+
+/// A script of the tx along with its hash. The hash is unknown for native
+/// scripts unless the tx cbor is available.
+pub struct ScriptSubject {
+    pub kind: ScriptKind,
+    pub hash: Option<Vec<u8>>,
+}
+
+fn plutus_hash(version: u8, bytes: &[u8]) -> Vec<u8> {
+    let mut hasher = pallas::crypto::hash::Hasher::<224>::new();
+    hasher.input(&[version]);
+    hasher.input(bytes);
+    hasher.finalize().to_vec()
+}
+
+fn cbor_scripts(tx: &MultiEraTx) -> Vec<ScriptSubject> {
+    let native = tx.native_scripts().iter().map(|x| ScriptSubject {
+        kind: ScriptKind::Native,
+        hash: Some(x.original_hash().to_vec()),
+    });
+
+    let v1 = tx.plutus_v1_scripts().iter().map(|x| ScriptSubject {
+        kind: ScriptKind::PlutusV1,
+        hash: Some(x.compute_hash().to_vec()),
+    });
+
+    let v2 = tx.plutus_v2_scripts().iter().map(|x| ScriptSubject {
+        kind: ScriptKind::PlutusV2,
+        hash: Some(x.compute_hash().to_vec()),
+    });
+
+    let v3 = tx.plutus_v3_scripts().iter().map(|x| ScriptSubject {
+        kind: ScriptKind::PlutusV3,
+        hash: Some(x.compute_hash().to_vec()),
+    });
+
+    native.chain(v1).chain(v2).chain(v3).collect()
+}
+
+fn parsed_scripts(tx: &ParsedTx) -> Vec<ScriptSubject> {
+    tx.witnesses
+        .iter()
+        .flat_map(|x| x.script.iter())
+        .filter_map(|x| x.script.as_ref())
+        .map(|x| match x {
+            // the u5c native script can't be encoded back into its original
+            // cbor, so its hash can't be computed
+            script::Script::Native(_) => ScriptSubject {
+                kind: ScriptKind::Native,
+                hash: None,
+            },
+            script::Script::PlutusV1(x) => ScriptSubject {
+                kind: ScriptKind::PlutusV1,
+                hash: Some(plutus_hash(1, x)),
+            },
+            script::Script::PlutusV2(x) => ScriptSubject {
+                kind: ScriptKind::PlutusV2,
+                hash: Some(plutus_hash(2, x)),
+            },
+            script::Script::PlutusV3(x) => ScriptSubject {
+                kind: ScriptKind::PlutusV3,
+                hash: Some(plutus_hash(3, x)),
+            },
+        })
+        .collect()
+}
+
+/// The scripts included in the witness set of the tx
+pub fn tx_scripts(tx: &TxSubject) -> Vec<ScriptSubject> {
+    match tx.cbor {
+        Some(x) => cbor_scripts(x),
+        None => parsed_scripts(tx),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ScriptPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<FlexBytes>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ScriptKind>,
+}
+
+impl FromBech32 for ScriptPattern {
+    fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
+        match hrp {
+            "script" => Some(Self {
+                hash: Some(FlexBytes(content)),
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ScriptPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bech32(s)
+    }
+}
+
+impl PatternOf<&ScriptSubject> for ScriptPattern {
+    fn is_match(&self, subject: &ScriptSubject) -> MatchOutcome {
+        let a = match (&self.hash, &subject.hash) {
+            (None, _) => MatchOutcome::Positive,
+            (Some(x), Some(hash)) => x.is_match(hash.as_slice()),
+            (Some(_), None) => MatchOutcome::Uncertain,
+        };
+
+        let b = match &self.kind {
+            Some(x) => MatchOutcome::if_equal(x, &subject.kind),
+            None => MatchOutcome::Positive,
+        };
+
+        MatchOutcome::fold_all_of([a, b].into_iter())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PurposeKind {
+    Spend,
+    Mint,
+    Cert,
+    Reward,
+    Vote,
+    Propose,
+}
+
+impl PurposeKind {
+    fn from_u5c(value: i32) -> Option<Self> {
+        match RedeemerPurpose::try_from(value).ok()? {
+            RedeemerPurpose::Spend => Some(PurposeKind::Spend),
+            RedeemerPurpose::Mint => Some(PurposeKind::Mint),
+            RedeemerPurpose::Cert => Some(PurposeKind::Cert),
+            RedeemerPurpose::Reward => Some(PurposeKind::Reward),
+            RedeemerPurpose::Vote => Some(PurposeKind::Vote),
+            RedeemerPurpose::Propose => Some(PurposeKind::Propose),
+            RedeemerPurpose::Unspecified => None,
+        }
+    }
+}
+
+/// The constructor index of a plutus data, decoded from its cbor tag
+fn constructor_index(data: &PlutusData) -> Option<u64> {
+    let plutus_data::PlutusData::Constr(x) = data.plutus_data.as_ref()? else {
+        return None;
+    };
+
+    match x.tag {
+        121..=127 => Some((x.tag - 121) as u64),
+        1280..=1400 => Some((x.tag - 1280 + 7) as u64),
+        102 => Some(x.any_constructor),
+        _ => None,
+    }
+}
+
+/// A redeemer of the tx along with the hash of the script that it executes,
+/// if known. Spend redeemers only know their script when the input is
+/// resolved.
+pub struct RedeemerSubject<'a> {
+    pub redeemer: &'a Redeemer,
+    pub script: Option<Vec<u8>>,
+}
+
+fn address_script(address: &[u8]) -> Option<Vec<u8>> {
+    match Address::from_bytes(address).ok()? {
+        Address::Shelley(x) => match x.payment() {
+            ShelleyPaymentPart::Script(x) => Some(x.to_vec()),
+            ShelleyPaymentPart::Key(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// The redeemers attached to the inputs, mints, withdrawals and certificates
+/// of the tx
+pub fn tx_redeemers(tx: &ParsedTx) -> Vec<RedeemerSubject<'_>> {
+    let inputs = tx.inputs.iter().filter_map(|x| {
+        let redeemer = x.redeemer.as_ref()?;
+
+        let script = x
+            .as_output
+            .as_ref()
+            .and_then(|x| address_script(&x.address));
+
+        Some(RedeemerSubject { redeemer, script })
+    });
+
+    let mints = tx.mint.iter().filter_map(|x| {
+        let redeemer = x.redeemer.as_ref()?;
+        let script = Some(x.policy_id.to_vec());
+
+        Some(RedeemerSubject { redeemer, script })
+    });
+
+    // the first byte of a reward account is the header
+    let withdrawals = tx.withdrawals.iter().filter_map(|x| {
+        let redeemer = x.redeemer.as_ref()?;
+        let script = x.reward_account.get(1..).map(|x| x.to_vec());
+
+        Some(RedeemerSubject { redeemer, script })
+    });
+
+    let certificates = tx.certificates.iter().filter_map(|x| {
+        let redeemer = x.redeemer.as_ref()?;
+
+        let script = x
+            .certificate
+            .as_ref()
+            .and_then(cert::stake_credential)
+            .and_then(|x| match x.stake_credential.as_ref()? {
+                Cred::ScriptHash(x) => Some(x.to_vec()),
+                Cred::AddrKeyHash(_) => None,
+            });
+
+        Some(RedeemerSubject { redeemer, script })
+    });
+
+    inputs
+        .chain(mints)
+        .chain(withdrawals)
+        .chain(certificates)
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RedeemerPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<PurposeKind>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u32>,
+
+    /// The hash of the script executed by the redeemer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<FlexBytes>,
+
+    /// The constructor index of the redeemer data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constructor: Option<u64>,
+}
+
+impl PatternOf<&RedeemerSubject<'_>> for RedeemerPattern {
+    fn is_match(&self, subject: &RedeemerSubject) -> MatchOutcome {
+        let a = match &self.purpose {
+            Some(x) => {
+                let purpose = PurposeKind::from_u5c(subject.redeemer.purpose);
+                MatchOutcome::if_true(purpose.as_ref() == Some(x))
+            }
+            None => MatchOutcome::Positive,
+        };
+
+        let b = match &self.index {
+            Some(x) => MatchOutcome::if_equal(x, &subject.redeemer.index),
+            None => MatchOutcome::Positive,
+        };
+
+        let c = match (&self.script, &subject.script) {
+            (None, _) => MatchOutcome::Positive,
+            (Some(x), Some(script)) => x.is_match(script.as_slice()),
+            (Some(_), None) => MatchOutcome::Uncertain,
+        };
+
+        let d = match &self.constructor {
+            Some(x) => {
+                let constructor = subject
+                    .redeemer
+                    .payload
+                    .as_ref()
+                    .and_then(constructor_index);
+                MatchOutcome::if_true(constructor.as_ref() == Some(x))
+            }
+            None => MatchOutcome::Positive,
+        };
+
+        MatchOutcome::fold_all_of([a, b, c, d].into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::interop::utxorpc::spec::cardano::{Constr, Script, TxInput, WitnessSet};
+
+    use super::*;
+
+    const POLICY: &str = "7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373";
+
+    fn redeemer(purpose: RedeemerPurpose, tag: u32) -> Redeemer {
+        Redeemer {
+            purpose: purpose.into(),
+            payload: Some(PlutusData {
+                plutus_data: Some(plutus_data::PlutusData::Constr(Constr {
+                    tag,
+                    ..Default::default()
+                })),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn tx() -> ParsedTx {
+        let mut mint = testing::multiasset_combo(POLICY, "abc");
+        mint.redeemer = Some(redeemer(RedeemerPurpose::Mint, 122));
+
+        ParsedTx {
+            inputs: vec![TxInput {
+                redeemer: Some(redeemer(RedeemerPurpose::Spend, 121)),
+                ..Default::default()
+            }],
+            mint: vec![mint],
+            witnesses: Some(WitnessSet {
+                script: vec![Script {
+                    script: Some(script::Script::PlutusV2(vec![1, 2, 3].into())),
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn redeemer_match() {
+        let tx = tx();
+        let redeemers = tx_redeemers(&tx);
+
+        let pattern = RedeemerPattern {
+            script: Some(FlexBytes::from_hex(POLICY).unwrap()),
+            constructor: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            pattern.is_any_match(redeemers.iter()),
+            MatchOutcome::Positive
+        );
+
+        let pattern = RedeemerPattern {
+            script: Some(FlexBytes::from_hex(POLICY).unwrap()),
+            constructor: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            pattern.is_any_match(redeemers.iter()),
+            MatchOutcome::Uncertain
+        );
+
+        let pattern: RedeemerPattern =
+            serde_json::from_str(r#"{ "purpose": "spend", "constructor": 0 }"#).unwrap();
+        assert_eq!(
+            pattern.is_any_match(redeemers.iter()),
+            MatchOutcome::Positive
+        );
+    }
+
+    #[test]
+    fn script_match() {
+        let tx = tx();
+        let scripts = tx_scripts(&TxSubject::from(&tx));
+
+        let pattern = ScriptPattern {
+            hash: Some(FlexBytes(plutus_hash(2, &[1, 2, 3]))),
+            kind: Some(ScriptKind::PlutusV2),
+        };
+        assert_eq!(pattern.is_any_match(scripts.iter()), MatchOutcome::Positive);
+
+        let pattern = ScriptPattern {
+            kind: Some(ScriptKind::Native),
+            ..Default::default()
+        };
+        assert_eq!(pattern.is_any_match(scripts.iter()), MatchOutcome::Negative);
+    }
+}
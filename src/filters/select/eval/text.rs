@@ -0,0 +1,196 @@
+use regex::Regex;
+use serde::de::{self, Visitor};
+use serde::{Deserializer, Serializer};
+use std::fmt;
+
+use super::*;
+
+/// A regex that is compiled once, as soon as the pattern is deserialized
+#[derive(Clone)]
+pub struct TextRegex(Regex);
+
+impl TextRegex {
+    pub fn new(re: &str) -> Result<Self, regex::Error> {
+        Regex::new(re).map(Self)
+    }
+}
+
+impl Deref for TextRegex {
+    type Target = Regex;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Debug for TextRegex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.as_str())
+    }
+}
+
+impl PartialEq for TextRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Serialize for TextRegex {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+struct TextRegexVisitor;
+
+impl Visitor<'_> for TextRegexVisitor {
+    type Value = TextRegex;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a regular expression")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        TextRegex::new(value).map_err(de::Error::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for TextRegex {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(TextRegexVisitor)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum TextPattern {
+    Exact(String),
+    ExactIgnoreCase(String),
+    StartsWith(String),
+    StartsWithIgnoreCase(String),
+    Contains(String),
+    ContainsIgnoreCase(String),
+
+    /// Use the `(?i)` flag for a case-insensitive regex
+    Regex(TextRegex),
+}
+
+impl PatternOf<&str> for TextPattern {
+    fn is_match(&self, subject: &str) -> MatchOutcome {
+        match self {
+            TextPattern::Exact(x) => MatchOutcome::if_equal(x.as_str(), subject),
+            TextPattern::ExactIgnoreCase(x) => {
+                MatchOutcome::if_true(subject.to_lowercase() == x.to_lowercase())
+            }
+            TextPattern::StartsWith(x) => MatchOutcome::if_true(subject.starts_with(x.as_str())),
+            TextPattern::StartsWithIgnoreCase(x) => {
+                MatchOutcome::if_true(subject.to_lowercase().starts_with(&x.to_lowercase()))
+            }
+            TextPattern::Contains(x) => MatchOutcome::if_true(subject.contains(x.as_str())),
+            TextPattern::ContainsIgnoreCase(x) => {
+                MatchOutcome::if_true(subject.to_lowercase().contains(&x.to_lowercase()))
+            }
+            TextPattern::Regex(x) => MatchOutcome::if_true(x.is_match(subject)),
+        }
+    }
+}
+
+impl PatternOf<&[u8]> for TextPattern {
+    fn is_match(&self, subject: &[u8]) -> MatchOutcome {
+        let subject = match String::from_utf8(subject.to_vec()) {
+            Ok(subject) => subject,
+            Err(_) => return MatchOutcome::Uncertain,
+        };
+
+        self.is_match(subject.as_str())
+    }
+}
+
+impl PatternOf<&Metadatum> for TextPattern {
+    fn is_match(&self, subject: &Metadatum) -> MatchOutcome {
+        match subject.metadatum.as_ref() {
+            Some(pallas::interop::utxorpc::spec::cardano::metadatum::Metadatum::Text(subject)) => {
+                self.is_match(subject.as_str())
+            }
+            _ => MatchOutcome::Negative,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_variants() {
+        let check = |json: &str, subject: &str| {
+            let pattern: TextPattern = serde_json::from_str(json).unwrap();
+            pattern.is_match(subject)
+        };
+
+        assert_eq!(check(r#"{"Exact":"abc"}"#, "abc"), MatchOutcome::Positive);
+        assert_eq!(check(r#"{"Exact":"abc"}"#, "ABC"), MatchOutcome::Negative);
+        assert_eq!(
+            check(r#"{"ExactIgnoreCase":"abc"}"#, "ABC"),
+            MatchOutcome::Positive
+        );
+        assert_eq!(
+            check(r#"{"StartsWith":"ab"}"#, "abc"),
+            MatchOutcome::Positive
+        );
+        assert_eq!(
+            check(r#"{"StartsWith":"bc"}"#, "abc"),
+            MatchOutcome::Negative
+        );
+        assert_eq!(
+            check(r#"{"StartsWithIgnoreCase":"AB"}"#, "abc"),
+            MatchOutcome::Positive
+        );
+        assert_eq!(check(r#"{"Contains":"b"}"#, "abc"), MatchOutcome::Positive);
+        assert_eq!(
+            check(r#"{"ContainsIgnoreCase":"B"}"#, "abc"),
+            MatchOutcome::Positive
+        );
+        assert_eq!(check(r#"{"Regex":"^a.c$"}"#, "abc"), MatchOutcome::Positive);
+        assert_eq!(
+            check(r#"{"Regex":"(?i)^A"}"#, "abc"),
+            MatchOutcome::Positive
+        );
+        assert_eq!(check(r#"{"Regex":"^b"}"#, "abc"), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn invalid_regex_fails_deserialization() {
+        let result = serde_json::from_str::<TextPattern>(r#"{"Regex":"("}"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn asset_name_text_match() {
+        let pattern = |text: TextPattern| {
+            Pattern::Asset(
+                AssetPattern {
+                    name_text: Some(text),
+                    ..Default::default()
+                }
+                .into(),
+            )
+        };
+
+        let positives =
+            testing::find_positive_test_vectors(pattern(TextPattern::StartsWith("xyz".into())));
+        assert_eq!(positives, vec![2]);
+
+        let regex = TextRegex::new("^(abc|xyz)2$").unwrap();
+        let positives = testing::find_positive_test_vectors(pattern(TextPattern::Regex(regex)));
+        assert_eq!(positives, vec![1, 2]);
+    }
+}
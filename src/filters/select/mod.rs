@@ -1,18 +1,20 @@
 use gasket::framework::*;
+use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
 use tracing::info;
 
 use crate::framework::*;
 
-use self::eval::{MatchOutcome, Predicate, StringOrStruct};
+use self::eval::{EventPredicate, MatchOutcome, Predicate, StringOrStruct};
 
 pub mod eval;
 
 #[derive(Stage)]
 #[stage(name = "select", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
-    predicate: Predicate,
-    skip_uncertain: bool,
+    predicate: EventPredicate,
+    on_uncertain: UncertainPolicy,
+    skip_irrelevant_resets: bool,
 
     pub input: FilterInputPort,
     pub output: FilterOutputPort,
@@ -21,13 +23,32 @@ pub struct Stage {
     ops_count: gasket::metrics::Counter,
 }
 
-#[derive(Default)]
-pub struct Worker;
+/// How many of the forwarded points are kept to decide if a reset is
+/// relevant. Rollbacks can't go deeper than the security parameter.
+const MAX_FORWARDED_POINTS: usize = 2160;
+
+pub struct Worker {
+    forwarded: Breadcrumbs,
+
+    /// Points selected before a restart are unknown, so resets are always
+    /// forwarded until the first event is applied
+    primed: bool,
+}
+
+impl Worker {
+    fn is_relevant_reset(&mut self, point: &Point) -> bool {
+        let discarded = self.forwarded.rollback(point);
+        discarded || !self.primed
+    }
+}
 
 #[async_trait::async_trait(?Send)]
 impl gasket::framework::Worker<Stage> for Worker {
     async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
-        Ok(Default::default())
+        Ok(Self {
+            forwarded: Breadcrumbs::new(MAX_FORWARDED_POINTS),
+            primed: false,
+        })
     }
 
     async fn schedule(
@@ -40,32 +61,82 @@ impl gasket::framework::Worker<Stage> for Worker {
     }
 
     async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
-        let is_match = match unit {
-            ChainEvent::Apply(_, r) => eval::eval(r, &stage.predicate),
-            ChainEvent::Undo(_, r) => eval::eval(r, &stage.predicate),
-            ChainEvent::Reset(_) => MatchOutcome::Positive,
+        let is_match = match (stage.predicate.eval(unit), unit) {
+            (Some(x), _) => x,
+            (None, ChainEvent::Reset(p)) if stage.skip_irrelevant_resets => {
+                MatchOutcome::if_true(self.is_relevant_reset(p))
+            }
+            (None, _) => MatchOutcome::Positive,
         };
 
-        match is_match {
-            MatchOutcome::Positive => stage.output.send(unit.clone().into()).await.or_panic()?,
-            MatchOutcome::Negative => (),
-            MatchOutcome::Uncertain => {
-                if !stage.skip_uncertain {
-                    return Err(WorkerError::Panic);
-                }
-            }
+        let forward = match is_match {
+            MatchOutcome::Positive => true,
+            MatchOutcome::Negative => false,
+            MatchOutcome::Uncertain => match stage.on_uncertain {
+                UncertainPolicy::Drop => false,
+                UncertainPolicy::Forward => true,
+                UncertainPolicy::Fail => return Err(WorkerError::Panic),
+            },
         };
 
+        if let ChainEvent::Apply(p, _) = unit {
+            self.primed = true;
+
+            if forward {
+                self.forwarded.track(p.clone());
+            }
+        }
+
+        if forward {
+            stage.output.send(unit.clone().into()).await.or_panic()?;
+        }
+
         stage.ops_count.inc(1);
 
         Ok(())
     }
 }
 
+/// What to do with events that can't be evaluated by the predicate
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UncertainPolicy {
+    /// Discard the event
+    Drop,
+
+    /// Send the event downstream as if it matched
+    Forward,
+
+    /// Stop the pipeline
+    #[default]
+    Fail,
+}
+
+impl UncertainPolicy {
+    /// Resolves the policy of a config, honoring the legacy `skip_uncertain`
+    /// flag when no explicit policy is set
+    pub fn from_config(on_uncertain: Option<Self>, skip_uncertain: bool) -> Self {
+        match on_uncertain {
+            Some(x) => x,
+            None if skip_uncertain => Self::Drop,
+            None => Self::Fail,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct Config {
     pub predicate: StringOrStruct<Predicate>,
+
+    /// Same as setting `on_uncertain` to `drop`, kept for compatibility
+    #[serde(default)]
     pub skip_uncertain: bool,
+
+    pub on_uncertain: Option<UncertainPolicy>,
+
+    /// Drops resets that don't roll back any of the forwarded events
+    #[serde(default)]
+    pub skip_irrelevant_resets: bool,
 }
 
 impl Config {
@@ -73,8 +144,9 @@ impl Config {
         info!(predicate = ?self.predicate, "selection filter predicate");
 
         let stage = Stage {
-            predicate: self.predicate.unwrap(),
-            skip_uncertain: self.skip_uncertain,
+            predicate: self.predicate.unwrap().into(),
+            on_uncertain: UncertainPolicy::from_config(self.on_uncertain, self.skip_uncertain),
+            skip_irrelevant_resets: self.skip_irrelevant_resets,
             ops_count: Default::default(),
             input: Default::default(),
             output: Default::default(),
@@ -83,3 +155,49 @@ impl Config {
         Ok(stage)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, slot.to_be_bytes().to_vec())
+    }
+
+    #[test]
+    fn uncertain_policy_from_config() {
+        let config: Config = serde_json::from_str(r##"{ "predicate": "#674" }"##).unwrap();
+        let policy = UncertainPolicy::from_config(config.on_uncertain, config.skip_uncertain);
+        assert_eq!(policy, UncertainPolicy::Fail);
+
+        let policy = UncertainPolicy::from_config(None, true);
+        assert_eq!(policy, UncertainPolicy::Drop);
+
+        let config: Config = serde_json::from_str(
+            r##"{ "predicate": "#674", "skip_uncertain": true, "on_uncertain": "forward" }"##,
+        )
+        .unwrap();
+        let policy = UncertainPolicy::from_config(config.on_uncertain, config.skip_uncertain);
+        assert_eq!(policy, UncertainPolicy::Forward);
+    }
+
+    #[test]
+    fn reset_relevance() {
+        let mut worker = Worker {
+            forwarded: Breadcrumbs::new(MAX_FORWARDED_POINTS),
+            primed: false,
+        };
+
+        // nothing is known about the points selected before a restart
+        assert!(worker.is_relevant_reset(&point(100)));
+
+        worker.primed = true;
+        worker.forwarded.track(point(110));
+        worker.forwarded.track(point(130));
+
+        assert!(!worker.is_relevant_reset(&point(130)));
+        assert!(worker.is_relevant_reset(&point(120)));
+        assert!(!worker.is_relevant_reset(&point(115)));
+        assert!(worker.is_relevant_reset(&point(100)));
+    }
+}
@@ -0,0 +1,110 @@
+//! Builders of synthetic conway blocks shared by the stage tests
+
+use pallas::codec::minicbor;
+use pallas::codec::utils::{KeyValuePairs, MaybeIndefArray};
+use pallas::crypto::hash::{Hash, Hasher};
+use pallas::ledger::primitives::{alonzo, conway};
+use pallas::ledger::traverse as trv;
+use pallas::network::miniprotocols::Point;
+
+use crate::framework::*;
+
+pub fn input(tx: Hash<32>, index: u64) -> conway::TransactionInput {
+    conway::TransactionInput {
+        transaction_id: tx,
+        index,
+    }
+}
+
+pub fn output(coin: u64) -> conway::TransactionOutput {
+    conway::TransactionOutput::Legacy(alonzo::TransactionOutput {
+        address: [vec![0x61], vec![0; 28]].concat().into(),
+        amount: alonzo::Value::Coin(coin),
+        datum_hash: None,
+    })
+}
+
+pub fn tx(
+    inputs: Vec<conway::TransactionInput>,
+    outputs: Vec<conway::TransactionOutput>,
+) -> conway::TransactionBody {
+    conway::TransactionBody {
+        inputs: inputs.into(),
+        outputs,
+        fee: 0,
+        ttl: None,
+        certificates: None,
+        withdrawals: None,
+        auxiliary_data_hash: None,
+        validity_interval_start: None,
+        mint: None,
+        script_data_hash: None,
+        collateral: None,
+        required_signers: None,
+        network_id: None,
+        collateral_return: None,
+        total_collateral: None,
+        reference_inputs: None,
+        voting_procedures: None,
+        proposal_procedures: None,
+        treasury_value: None,
+        donation: None,
+    }
+}
+
+pub fn tx_hash(tx: &conway::TransactionBody) -> Hash<32> {
+    Hasher::<256>::hash(&minicbor::to_vec(tx).unwrap())
+}
+
+pub fn witnesses() -> conway::WitnessSet {
+    conway::WitnessSet {
+        vkeywitness: None,
+        native_script: None,
+        bootstrap_witness: None,
+        plutus_v1_script: None,
+        plutus_data: None,
+        redeemer: None,
+        plutus_v2_script: None,
+        plutus_v3_script: None,
+    }
+}
+
+/// A conway block at `slot` with the given txs, as the sources emit it
+pub fn block(slot: u64, txs: Vec<conway::TransactionBody>) -> (Point, Record) {
+    let header = conway::Header {
+        header_body: conway::HeaderBody {
+            block_number: slot,
+            slot,
+            prev_hash: None,
+            issuer_vkey: vec![].into(),
+            vrf_vkey: vec![].into(),
+            vrf_result: conway::VrfCert(vec![].into(), vec![].into()),
+            block_body_size: 0,
+            block_body_hash: Hash::new([0; 32]),
+            operational_cert: conway::OperationalCert {
+                operational_cert_hot_vkey: vec![].into(),
+                operational_cert_sequence_number: 0,
+                operational_cert_kes_period: 0,
+                operational_cert_sigma: vec![].into(),
+            },
+            protocol_version: (9, 0),
+        },
+        body_signature: vec![].into(),
+    };
+
+    let witnesses = txs.iter().map(|_| witnesses()).collect();
+
+    let block = conway::Block {
+        header,
+        transaction_bodies: MaybeIndefArray::Def(txs),
+        transaction_witness_sets: MaybeIndefArray::Def(witnesses),
+        auxiliary_data_set: KeyValuePairs::Def(vec![]),
+        invalid_transactions: None,
+    };
+
+    let cbor = minicbor::to_vec((7u16, block)).unwrap();
+    let decoded = trv::MultiEraBlock::decode(&cbor).unwrap();
+    let point = Point::Specific(decoded.slot(), decoded.hash().to_vec());
+
+    (point, Record::CborBlock(cbor))
+}
@@ -0,0 +1,81 @@
+//! Keys of the objects that hold blocks in object storages (eg: S3)
+
+use pallas::network::miniprotocols::Point;
+use serde::Deserialize;
+
+/// Width of the slot in padded keys, enough for any u64
+const PADDED_SLOT_WIDTH: usize = 20;
+
+/// How the keys of the block objects are built from their point. Object
+/// storages list keys in lexicographic order, so only the padded layout lists
+/// blocks in slot order regardless of the number of digits of the slot. Plain
+/// is the default since it's the layout of the buckets written so far.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyLayout {
+    /// `{prefix}{slot}.{hash}`
+    #[default]
+    Plain,
+
+    /// `{prefix}{slot}.{hash}` with the slot zero-padded to 20 digits
+    Padded,
+}
+
+impl KeyLayout {
+    pub fn key(&self, prefix: &str, slot: u64, hash: &[u8]) -> String {
+        let hash = hex::encode(hash);
+
+        match self {
+            KeyLayout::Plain => format!("{prefix}{slot}.{hash}"),
+            KeyLayout::Padded => format!("{prefix}{slot:0PADDED_SLOT_WIDTH$}.{hash}"),
+        }
+    }
+}
+
+/// Reads the point back from a key written with any of the layouts
+pub fn point_from_key(prefix: &str, key: &str) -> Option<Point> {
+    let (slot, hash) = key.strip_prefix(prefix)?.split_once('.')?;
+
+    let slot = slot.parse().ok()?;
+    let hash = hex::decode(hash).ok()?;
+
+    Some(Point::Specific(slot, hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padded_keys_sort_by_slot() {
+        let layout = KeyLayout::Padded;
+
+        let mut keys = [
+            layout.key("mainnet/", 1000, &[0xaa]),
+            layout.key("mainnet/", 999, &[0xbb]),
+        ];
+
+        keys.sort();
+
+        assert_eq!(keys[0], "mainnet/00000000000000000999.bb");
+    }
+
+    #[test]
+    fn existing_keys_stay_plain_by_default() {
+        let key = KeyLayout::default().key("mainnet/", 4492800, &[0xaa]);
+        assert_eq!(key, "mainnet/4492800.aa");
+    }
+
+    #[test]
+    fn point_round_trips_through_keys() {
+        for layout in [KeyLayout::Plain, KeyLayout::Padded] {
+            let key = layout.key("mainnet/", 4492800, &[0xaa, 0x83]);
+            let point = point_from_key("mainnet/", &key);
+
+            assert_eq!(point, Some(Point::Specific(4492800, vec![0xaa, 0x83])));
+        }
+
+        assert_eq!(point_from_key("mainnet/", "testnet/1.aa"), None);
+        assert_eq!(point_from_key("", "block-1"), None);
+    }
+}
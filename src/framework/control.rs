@@ -0,0 +1,44 @@
+//! Runtime state shared between the stages of the pipeline and the admin api
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use pallas::network::miniprotocols::Point;
+
+use super::Breadcrumbs;
+
+#[derive(Default)]
+struct State {
+    paused: AtomicBool,
+    cursor: RwLock<Vec<Point>>,
+}
+
+/// A cheap-to-clone handle to the runtime state of the pipeline
+#[derive(Clone, Default)]
+pub struct Control(Arc<State>);
+
+impl Control {
+    pub fn pause(&self) {
+        self.0.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.0.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.paused.load(Ordering::SeqCst)
+    }
+
+    /// Publishes the latest breadcrumbs tracked by the cursor
+    pub fn publish_cursor(&self, breadcrumbs: &Breadcrumbs) {
+        if let Ok(mut cursor) = self.0.cursor.write() {
+            *cursor = breadcrumbs.points();
+        }
+    }
+
+    /// The latest breadcrumbs tracked by the cursor, newest first
+    pub fn cursor(&self) -> Vec<Point> {
+        self.0.cursor.read().map(|x| x.clone()).unwrap_or_default()
+    }
+}
@@ -0,0 +1,431 @@
+//! Bounded history of applied records, optionally persisted to disk
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use pallas::network::miniprotocols::{chainsync, Point};
+use serde::{Deserialize, Serialize};
+
+use super::journal::{point_from_data, point_to_data, Journal, PointData};
+use super::{Error, Record};
+
+/// A point of the chain together with the records applied at it
+pub type Block = (Point, Vec<Record>);
+
+/// Journal representation of a record, with the CBOR payloads hex-encoded to
+/// keep the entries compact
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RecordData {
+    CborBlock(#[serde(with = "hex_bytes")] Vec<u8>),
+    CborTx(#[serde(with = "hex_bytes")] Vec<u8>),
+    Other(Box<Record>),
+}
+
+impl From<Record> for RecordData {
+    fn from(value: Record) -> Self {
+        match value {
+            Record::CborBlock(x) => RecordData::CborBlock(x),
+            Record::CborTx(x) => RecordData::CborTx(x),
+            x => RecordData::Other(Box::new(x)),
+        }
+    }
+}
+
+impl From<RecordData> for Record {
+    fn from(value: RecordData) -> Self {
+        match value {
+            RecordData::CborBlock(x) => Record::CborBlock(x),
+            RecordData::CborTx(x) => Record::CborTx(x),
+            RecordData::Other(x) => *x,
+        }
+    }
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        hex::decode(hex).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Entry {
+    Apply(PointData, RecordData),
+    Release(PointData),
+    RollBack(PointData),
+    Clear,
+}
+
+/// The records applied at each of the latest points of the chain
+///
+/// Points are tracked using a `chainsync::RollbackBuffer`, in the same order
+/// they were applied. A single point might hold several records (eg: the txs of
+/// a split block).
+#[derive(Default)]
+pub struct History {
+    buffer: chainsync::RollbackBuffer,
+    records: HashMap<Point, Vec<Record>>,
+    released: Option<Point>,
+    journal: Option<Journal>,
+}
+
+impl History {
+    /// Opens a history persisted at the given path, creating it if necessary
+    pub fn open(path: PathBuf) -> Result<Self, Error> {
+        let mut history = Self::default();
+
+        let journal = Journal::open(path, |entry| history.replay(entry))?;
+
+        history.journal = Some(journal);
+        history.compact()?;
+
+        Ok(history)
+    }
+
+    fn replay(&mut self, entry: Entry) -> Result<(), Error> {
+        match entry {
+            Entry::Apply(point, record) => {
+                self.apply_record(point_from_data(point)?, record.into())
+            }
+            Entry::Release(point) => {
+                self.release_until(&point_from_data(point)?);
+            }
+            Entry::RollBack(point) => {
+                self.truncate_after(&point_from_data(point)?);
+            }
+            Entry::Clear => self.reset(),
+        }
+
+        Ok(())
+    }
+
+    fn apply_record(&mut self, point: Point, record: Record) {
+        if self.buffer.latest() != Some(&point) {
+            self.buffer.roll_forward(point.clone());
+        }
+
+        self.records.entry(point).or_default().push(record);
+    }
+
+    fn release_until(&mut self, point: &Point) -> Vec<Block> {
+        self.released = Some(point.clone());
+
+        let depth = match self.buffer.position(point) {
+            Some(x) => self.buffer.size() - x - 1,
+            None => return vec![],
+        };
+
+        self.buffer
+            .pop_with_depth(depth)
+            .into_iter()
+            .map(|p| {
+                let records = self.records.remove(&p).unwrap_or_default();
+                (p, records)
+            })
+            .collect()
+    }
+
+    fn truncate_after(&mut self, point: &Point) -> Option<Vec<Block>> {
+        let discarded: Vec<_> = match self.buffer.position(point) {
+            Some(x) => self.buffer.peek().skip(x + 1).cloned().collect(),
+            None => return None,
+        };
+
+        self.buffer.roll_back(point);
+
+        let discarded = discarded
+            .into_iter()
+            .map(|p| {
+                let records = self.records.remove(&p).unwrap_or_default();
+                (p, records)
+            })
+            .collect();
+
+        Some(discarded)
+    }
+
+    fn reset(&mut self) {
+        self.buffer = Default::default();
+        self.records.clear();
+    }
+
+    fn record(&mut self, entry: Entry) -> Result<(), Error> {
+        match self.journal.as_mut() {
+            Some(journal) => journal.append(&entry),
+            None => Ok(()),
+        }
+    }
+
+    /// Rewrites the journal if it grew too big compared to the current state
+    fn compact(&mut self) -> Result<(), Error> {
+        // cheap check first, this runs on every commit
+        if self.journal.as_ref().is_none_or(|x| x.lines() <= 1000) {
+            return Ok(());
+        }
+
+        let size = self.records.values().map(Vec::len).sum::<usize>();
+
+        let journal = match self.journal.as_mut() {
+            Some(x) if x.lines() > size * 2 => x,
+            _ => return Ok(()),
+        };
+
+        let released = self
+            .released
+            .iter()
+            .map(|point| Entry::Release(point_to_data(point)));
+
+        let entries = self.buffer.peek().flat_map(|point| {
+            self.records
+                .get(point)
+                .into_iter()
+                .flatten()
+                .map(|record| Entry::Apply(point_to_data(point), record.clone().into()))
+        });
+
+        let entries = released.chain(entries);
+
+        journal.rewrite(entries)
+    }
+
+    pub fn size(&self) -> usize {
+        self.buffer.size()
+    }
+
+    pub fn oldest(&self) -> Option<&Point> {
+        self.buffer.oldest()
+    }
+
+    pub fn latest(&self) -> Option<&Point> {
+        self.buffer.latest()
+    }
+
+    /// The latest point popped out of the history because it reached the
+    /// required depth
+    pub fn released(&self) -> Option<&Point> {
+        self.released.as_ref()
+    }
+
+    /// The points of the history, from oldest to newest
+    pub fn points(&self) -> impl DoubleEndedIterator<Item = &Point> {
+        self.buffer.peek()
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        self.buffer.position(point).is_some()
+    }
+
+    /// Adds a record applied at the given point
+    pub fn push(&mut self, point: Point, record: Record) -> Result<(), Error> {
+        self.record(Entry::Apply(point_to_data(&point), record.clone().into()))?;
+        self.apply_record(point, record);
+
+        Ok(())
+    }
+
+    /// Removes the points that are at least `depth` points behind the latest
+    /// one, returning them (with their records) from oldest to newest
+    pub fn pop_with_depth(&mut self, depth: usize) -> Result<Vec<Block>, Error> {
+        let last = match self.buffer.size().checked_sub(depth + 1) {
+            Some(x) => self.buffer.peek().nth(x).cloned(),
+            None => None,
+        };
+
+        match last {
+            Some(last) => {
+                self.record(Entry::Release(point_to_data(&last)))?;
+                Ok(self.release_until(&last))
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Removes the points after the given one, returning them (with their
+    /// records) from oldest to newest. Returns `None` if the point is not part
+    /// of the history, in which case the history is left untouched.
+    pub fn roll_back(&mut self, point: &Point) -> Result<Option<Vec<Block>>, Error> {
+        if !self.contains(point) {
+            return Ok(None);
+        }
+
+        self.record(Entry::RollBack(point_to_data(point)))?;
+
+        Ok(self.truncate_after(point))
+    }
+
+    /// Removes the points with a slot greater than the given one, returning
+    /// them (with their records) from oldest to newest. Useful when rolling
+    /// back to a point that is not part of the history.
+    pub fn roll_back_to_slot(&mut self, slot: u64) -> Result<Vec<Block>, Error> {
+        if !matches!(self.latest(), Some(x) if x.slot_or_default() > slot) {
+            return Ok(vec![]);
+        }
+
+        let last_kept = self
+            .buffer
+            .peek()
+            .rev()
+            .find(|x| x.slot_or_default() <= slot)
+            .cloned();
+
+        match last_kept {
+            Some(x) => Ok(self.roll_back(&x)?.unwrap_or_default()),
+            None => self.drain(),
+        }
+    }
+
+    /// Removes the latest point if it matches the given one, returning its
+    /// records
+    pub fn pop_latest(&mut self, point: &Point) -> Result<Option<Vec<Record>>, Error> {
+        if self.latest() != Some(point) {
+            return Ok(None);
+        }
+
+        let previous = self.buffer.peek().rev().nth(1).cloned();
+
+        let mut discarded = match previous {
+            Some(previous) => self.roll_back(&previous)?.unwrap_or_default(),
+            None => self.drain()?,
+        };
+
+        Ok(discarded.pop().map(|(_, records)| records))
+    }
+
+    /// Removes every point, returning them (with their records) from oldest
+    /// to newest
+    pub fn drain(&mut self) -> Result<Vec<Block>, Error> {
+        self.record(Entry::Clear)?;
+
+        let drained = self
+            .buffer
+            .peek()
+            .map(|p| (p.clone(), self.records.remove(p).unwrap_or_default()))
+            .collect();
+
+        self.reset();
+
+        Ok(drained)
+    }
+
+    /// Makes sure every change so far is persisted to disk
+    pub fn commit(&mut self) -> Result<(), Error> {
+        self.compact()?;
+
+        match self.journal.as_mut() {
+            Some(journal) => journal.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, slot.to_be_bytes().to_vec())
+    }
+
+    fn record(slot: u64) -> Record {
+        Record::CborTx(slot.to_be_bytes().to_vec())
+    }
+
+    fn filled(history: &mut History) {
+        for slot in 0..5 {
+            history.push(point(slot), record(slot)).unwrap();
+            history.push(point(slot), record(slot + 100)).unwrap();
+        }
+    }
+
+    #[test]
+    fn pop_keeps_depth() {
+        let mut history = History::default();
+        filled(&mut history);
+
+        let popped = history.pop_with_depth(3).unwrap();
+
+        assert_eq!(popped.len(), 2);
+        assert_eq!(popped[0].0, point(0));
+        assert_eq!(popped[0].1.len(), 2);
+        assert_eq!(history.oldest(), Some(&point(2)));
+        assert!(history.pop_with_depth(3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn roll_back_returns_discarded() {
+        let mut history = History::default();
+        filled(&mut history);
+
+        let discarded = history.roll_back(&point(2)).unwrap().unwrap();
+
+        assert_eq!(discarded.len(), 2);
+        assert_eq!(discarded[0].0, point(3));
+        assert_eq!(history.latest(), Some(&point(2)));
+        assert!(history.roll_back(&point(3)).unwrap().is_none());
+    }
+
+    #[test]
+    fn roll_back_to_slot_keeps_older_points() {
+        let mut history = History::default();
+        filled(&mut history);
+
+        let discarded = history.roll_back_to_slot(2).unwrap();
+
+        assert_eq!(discarded.len(), 2);
+        assert_eq!(discarded[0].0, point(3));
+        assert_eq!(history.oldest(), Some(&point(0)));
+        assert_eq!(history.latest(), Some(&point(2)));
+        assert!(history.roll_back_to_slot(7).unwrap().is_empty());
+    }
+
+    #[test]
+    fn journal_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+
+        let mut history = History::open(path.clone()).unwrap();
+        filled(&mut history);
+        history.pop_with_depth(3).unwrap();
+        history.roll_back(&point(3)).unwrap();
+        history.commit().unwrap();
+        drop(history);
+
+        let mut history = History::open(path).unwrap();
+
+        assert_eq!(history.oldest(), Some(&point(2)));
+        assert_eq!(history.latest(), Some(&point(3)));
+        assert_eq!(history.released(), Some(&point(1)));
+
+        let drained = history.drain().unwrap();
+        assert_eq!(drained[1].1.len(), 2);
+    }
+
+    #[test]
+    fn journal_stores_cbor_as_hex() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+
+        let mut history = History::open(path.clone()).unwrap();
+        history
+            .push(point(1), Record::CborBlock(vec![0xde, 0xad]))
+            .unwrap();
+        history.commit().unwrap();
+        drop(history);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("\"dead\""));
+
+        let mut history = History::open(path).unwrap();
+        let drained = history.drain().unwrap();
+
+        assert!(matches!(&drained[0].1[..], [Record::CborBlock(x)] if x == &[0xde, 0xad]));
+    }
+}
@@ -0,0 +1,257 @@
+//! Append-only files used to persist the state of some of the stages
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use pallas::network::miniprotocols::Point;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::warn;
+
+use super::Error;
+
+/// Minimum time between syncs of the journal to disk. Flushing after each
+/// event only hands the entries to the OS, syncing them every time would cap
+/// the throughput of the pipeline at the latency of the disk.
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Serializable representation of a point, as stored in journal entries
+pub type PointData = Option<(u64, String)>;
+
+pub fn point_to_data(point: &Point) -> PointData {
+    match point {
+        Point::Origin => None,
+        Point::Specific(slot, hash) => Some((*slot, hex::encode(hash))),
+    }
+}
+
+pub fn point_from_data(data: PointData) -> Result<Point, Error> {
+    match data {
+        None => Ok(Point::Origin),
+        Some((slot, hash)) => {
+            let hash = hex::decode(hash).map_err(Error::custom)?;
+            Ok(Point::Specific(slot, hash))
+        }
+    }
+}
+
+/// Append-only log of the operations applied to some state
+///
+/// Replaying the journal from the start rebuilds the state. Once the journal
+/// grows much bigger than the state itself, it can be rewritten from scratch
+/// with just the entries required to rebuild the current state.
+pub struct Journal {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    lines: usize,
+    last_sync: Instant,
+}
+
+impl Journal {
+    /// Opens the journal at the given path, replaying each of the existing
+    /// entries through the provided closure
+    ///
+    /// A crash while appending might leave a partial entry at the end of the
+    /// file. That entry is discarded and the file truncated to the last
+    /// complete one.
+    pub fn open<E, F>(path: PathBuf, mut replay: F) -> Result<Self, Error>
+    where
+        E: DeserializeOwned,
+        F: FnMut(E) -> Result<(), Error>,
+    {
+        let mut lines = 0;
+
+        if path.is_file() {
+            let file = File::open(&path).map_err(Error::custom)?;
+            let mut reader = BufReader::new(file);
+            let mut line = String::new();
+            let mut valid = 0;
+
+            loop {
+                line.clear();
+
+                let read = reader.read_line(&mut line).map_err(Error::custom)?;
+
+                if read == 0 {
+                    break;
+                }
+
+                let entry = match line.strip_suffix('\n') {
+                    Some(x) => serde_json::from_str(x).map_err(Error::parse),
+                    None => Err(Error::parse("journal entry is incomplete")),
+                };
+
+                match entry {
+                    Ok(entry) => replay(entry)?,
+                    Err(err) => {
+                        let is_last = reader.fill_buf().map_err(Error::custom)?.is_empty();
+
+                        if !is_last {
+                            return Err(err);
+                        }
+
+                        warn!(?path, "discarding partial entry at the end of journal");
+
+                        File::options()
+                            .write(true)
+                            .open(&path)
+                            .and_then(|file| file.set_len(valid))
+                            .map_err(Error::custom)?;
+
+                        break;
+                    }
+                }
+
+                valid += read as u64;
+                lines += 1;
+            }
+        }
+
+        let mut journal = Self::append_to(path)?;
+        journal.lines = lines;
+
+        Ok(journal)
+    }
+
+    fn append_to(path: PathBuf) -> Result<Self, Error> {
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(Error::custom)?;
+
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+            lines: 0,
+            last_sync: Instant::now(),
+        })
+    }
+
+    /// The number of entries in the journal
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    pub fn append(&mut self, entry: &impl Serialize) -> Result<(), Error> {
+        serde_json::to_writer(&mut self.writer, entry).map_err(Error::custom)?;
+        self.writer.write_all(b"\n").map_err(Error::custom)?;
+        self.lines += 1;
+
+        Ok(())
+    }
+
+    /// Writes the pending entries to the file, syncing them to disk if enough
+    /// time passed since the last sync
+    ///
+    /// A crash might lose the entries written since the last sync. Those are
+    /// always the latest ones, so the journal is still consistent.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush().map_err(Error::custom)?;
+
+        if self.last_sync.elapsed() >= SYNC_INTERVAL {
+            self.sync()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the pending entries and waits until they reach the disk
+    pub fn sync(&mut self) -> Result<(), Error> {
+        self.writer.flush().map_err(Error::custom)?;
+        self.writer.get_ref().sync_data().map_err(Error::custom)?;
+        self.last_sync = Instant::now();
+
+        Ok(())
+    }
+
+    /// Replaces the content of the journal with the given entries
+    pub fn rewrite<E: Serialize>(&mut self, entries: impl Iterator<Item = E>) -> Result<(), Error> {
+        let temp = self.path.with_extension("tmp");
+        let file = File::create(&temp).map_err(Error::custom)?;
+        let mut writer = BufWriter::new(file);
+        let mut lines = 0;
+
+        for entry in entries {
+            serde_json::to_writer(&mut writer, &entry).map_err(Error::custom)?;
+            writer.write_all(b"\n").map_err(Error::custom)?;
+            lines += 1;
+        }
+
+        writer.flush().map_err(Error::custom)?;
+        writer.get_ref().sync_data().map_err(Error::custom)?;
+        std::fs::rename(&temp, &self.path).map_err(Error::custom)?;
+
+        *self = Self::append_to(self.path.clone())?;
+        self.lines = lines;
+
+        Ok(())
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        if let Err(err) = self.sync() {
+            warn!(path = ?self.path, ?err, "failed to sync journal");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn replayed(path: &std::path::Path) -> Vec<Value> {
+        let mut entries = vec![];
+        Journal::open(path.to_path_buf(), |x: Value| {
+            entries.push(x);
+            Ok(())
+        })
+        .unwrap();
+
+        entries
+    }
+
+    #[test]
+    fn partial_entry_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal");
+
+        let mut journal = Journal::open(path.clone(), |_: Value| Ok(())).unwrap();
+        journal.append(&json!({ "slot": 1 })).unwrap();
+        journal.append(&json!({ "slot": 2 })).unwrap();
+        journal.flush().unwrap();
+        drop(journal);
+
+        // simulate a crash in the middle of an append
+        let mut file = File::options().append(true).open(&path).unwrap();
+        file.write_all(br#"{ "slo"#).unwrap();
+        drop(file);
+
+        assert_eq!(replayed(&path).len(), 2);
+
+        let mut journal = Journal::open(path.clone(), |_: Value| Ok(())).unwrap();
+        assert_eq!(journal.lines(), 2);
+        journal.append(&json!({ "slot": 3 })).unwrap();
+        journal.flush().unwrap();
+        drop(journal);
+
+        assert_eq!(replayed(&path)[2], json!({ "slot": 3 }));
+    }
+
+    #[test]
+    fn corrupt_entry_in_the_middle_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal");
+
+        std::fs::write(&path, "{}\nnot json\n{}\n").unwrap();
+
+        assert!(Journal::open(path, |_: Value| Ok(())).is_err());
+    }
+}
@@ -25,6 +25,7 @@ pub enum Era {
     Mary,
     Alonzo,
     Babbage,
+    Conway,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -207,6 +208,7 @@ pub struct EventContext {
     pub output_idx: Option<usize>,
     pub output_address: Option<String>,
     pub certificate_idx: Option<usize>,
+    pub proposal_idx: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -215,6 +217,59 @@ pub enum StakeCredential {
     Scripthash(String),
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DRep {
+    AddrKeyhash(String),
+    Scripthash(String),
+    Abstain,
+    NoConfidence,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Voter {
+    CommitteeKeyhash(String),
+    CommitteeScripthash(String),
+    DRepKeyhash(String),
+    DRepScripthash(String),
+    StakePoolKeyhash(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AnchorRecord {
+    pub url: String,
+    pub data_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GovProposalRecord {
+    pub action_id: String,
+    pub deposit: u64,
+    pub reward_account: String,
+    pub action_kind: String,
+    pub action: JsonValue,
+    pub anchor: AnchorRecord,
+}
+
+impl From<GovProposalRecord> for EventData {
+    fn from(x: GovProposalRecord) -> Self {
+        EventData::GovProposal(x)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VoteRecord {
+    pub voter: Voter,
+    pub action_id: String,
+    pub vote: String,
+    pub anchor: Option<AnchorRecord>,
+}
+
+impl From<VoteRecord> for EventData {
+    fn from(x: VoteRecord) -> Self {
+        EventData::Vote(x)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct VKeyWitnessRecord {
     pub vkey_hex: String,
@@ -364,6 +419,44 @@ pub enum EventData {
         to_stake_credentials: Option<Vec<(StakeCredential, i64)>>,
         to_other_pot: Option<u64>,
     },
+    VoteDelegation {
+        credential: StakeCredential,
+        drep: DRep,
+    },
+    StakeVoteDelegation {
+        credential: StakeCredential,
+        pool_hash: String,
+        drep: DRep,
+    },
+    StakeRegistrationDelegation {
+        credential: StakeCredential,
+        pool_hash: Option<String>,
+        drep: Option<DRep>,
+        deposit: u64,
+    },
+    DRepRegistration {
+        credential: StakeCredential,
+        deposit: u64,
+        anchor: Option<AnchorRecord>,
+    },
+    DRepUpdate {
+        credential: StakeCredential,
+        anchor: Option<AnchorRecord>,
+    },
+    DRepRetirement {
+        credential: StakeCredential,
+        refund: u64,
+    },
+    CommitteeHotKeyAuth {
+        cold_credential: StakeCredential,
+        hot_credential: StakeCredential,
+    },
+    CommitteeResign {
+        cold_credential: StakeCredential,
+        anchor: Option<AnchorRecord>,
+    },
+    GovProposal(GovProposalRecord),
+    Vote(VoteRecord),
     RollBack {
         block_slot: u64,
         block_hash: String,
@@ -1,7 +1,7 @@
 //! Internal pipeline framework
 
 use pallas::network::miniprotocols::Point;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 use std::collections::VecDeque;
 use std::fmt::Debug;
@@ -16,9 +16,14 @@ pub use pallas::interop::utxorpc::spec::cardano::Tx as ParsedTx;
 // we use GenesisValues from Pallas as our ChainConfig
 pub use pallas::ledger::traverse::wellknown::GenesisValues;
 
+pub mod block_keys;
+pub mod control;
 pub mod errors;
+pub mod history;
+pub mod journal;
 pub mod legacy_v1;
 
+pub use control::Control;
 pub use errors::*;
 
 #[derive(Clone)]
@@ -63,11 +68,23 @@ impl Breadcrumbs {
     pub fn points(&self) -> Vec<Point> {
         self.state.iter().map(Clone::clone).collect()
     }
+
+    /// Discards the points newer than the given one, returns true if any of
+    /// them was discarded
+    pub fn rollback(&mut self, point: &Point) -> bool {
+        let before = self.state.len();
+
+        self.state
+            .retain(|p| p.slot_or_default() <= point.slot_or_default());
+
+        self.state.len() < before
+    }
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Default)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum ChainConfig {
+    #[default]
     Mainnet,
     Testnet,
     PreProd,
@@ -75,12 +92,6 @@ pub enum ChainConfig {
     Custom(GenesisValues),
 }
 
-impl Default for ChainConfig {
-    fn default() -> Self {
-        Self::Mainnet
-    }
-}
-
 impl From<ChainConfig> for GenesisValues {
     fn from(other: ChainConfig) -> Self {
         match other {
@@ -99,9 +110,10 @@ pub struct Context {
     pub finalize: Option<FinalizeConfig>,
     pub current_dir: PathBuf,
     pub breadcrumbs: Breadcrumbs,
+    pub control: Control,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Record {
     CborBlock(Vec<u8>),
     CborTx(Vec<u8>),
@@ -129,6 +141,14 @@ pub enum ChainEvent {
     Apply(Point, Record),
     Undo(Point, Record),
     Reset(Point),
+
+    /// A tx that entered the mempool, the point holds the slot of the
+    /// mempool snapshot where it was seen and an empty hash
+    Pending(Point, Record),
+
+    /// A tx that left the mempool, either because it was included in a block
+    /// or because the node discarded it
+    Removed(Point, Record),
 }
 
 impl ChainEvent {
@@ -150,11 +170,25 @@ impl ChainEvent {
         }
     }
 
+    pub fn pending(point: Point, record: impl Into<Record>) -> gasket::messaging::Message<Self> {
+        gasket::messaging::Message {
+            payload: Self::Pending(point, record.into()),
+        }
+    }
+
+    pub fn removed(point: Point, record: impl Into<Record>) -> gasket::messaging::Message<Self> {
+        gasket::messaging::Message {
+            payload: Self::Removed(point, record.into()),
+        }
+    }
+
     pub fn point(&self) -> &Point {
         match self {
             Self::Apply(x, _) => x,
             Self::Undo(x, _) => x,
             Self::Reset(x) => x,
+            Self::Pending(x, _) => x,
+            Self::Removed(x, _) => x,
         }
     }
 
@@ -162,6 +196,8 @@ impl ChainEvent {
         match self {
             Self::Apply(_, x) => Some(x),
             Self::Undo(_, x) => Some(x),
+            Self::Pending(_, x) => Some(x),
+            Self::Removed(_, x) => Some(x),
             _ => None,
         }
     }
@@ -171,6 +207,8 @@ impl ChainEvent {
             Self::Apply(p, x) => Self::Apply(p, f(x)),
             Self::Undo(p, x) => Self::Undo(p, f(x)),
             Self::Reset(x) => Self::Reset(x),
+            Self::Pending(p, x) => Self::Pending(p, f(x)),
+            Self::Removed(p, x) => Self::Removed(p, f(x)),
         }
     }
 
@@ -182,6 +220,8 @@ impl ChainEvent {
             Self::Apply(p, x) => Self::Apply(p, f(x)?),
             Self::Undo(p, x) => Self::Undo(p, f(x)?),
             Self::Reset(x) => Self::Reset(x),
+            Self::Pending(p, x) => Self::Pending(p, f(x)?),
+            Self::Removed(p, x) => Self::Removed(p, f(x)?),
         };
 
         Ok(out)
@@ -201,13 +241,21 @@ impl ChainEvent {
                 .map(|i| Self::Undo(p.clone(), i))
                 .collect(),
             Self::Reset(x) => vec![Self::Reset(x)],
+            Self::Pending(p, x) => f(x)?
+                .into_iter()
+                .map(|i| Self::Pending(p.clone(), i))
+                .collect(),
+            Self::Removed(p, x) => f(x)?
+                .into_iter()
+                .map(|i| Self::Removed(p.clone(), i))
+                .collect(),
         };
 
         Ok(out)
     }
 }
 
-fn point_to_json(point: Point) -> JsonValue {
+pub fn point_to_json(point: Point) -> JsonValue {
     match &point {
         pallas::network::miniprotocols::Point::Origin => JsonValue::from("origin"),
         pallas::network::miniprotocols::Point::Specific(slot, hash) => {
@@ -239,6 +287,20 @@ impl From<ChainEvent> for JsonValue {
                     "point": point_to_json(point)
                 })
             }
+            ChainEvent::Pending(point, record) => {
+                json!({
+                    "event": "pending",
+                    "point": point_to_json(point),
+                    "record": JsonValue::from(record.clone())
+                })
+            }
+            ChainEvent::Removed(point, record) => {
+                json!({
+                    "event": "removed",
+                    "point": point_to_json(point),
+                    "record": JsonValue::from(record.clone())
+                })
+            }
         }
     }
 }
@@ -251,7 +313,7 @@ pub type MapperOutputPort = gasket::messaging::OutputPort<ChainEvent>;
 pub type SinkInputPort = gasket::messaging::InputPort<ChainEvent>;
 pub type SinkCursorPort = gasket::messaging::OutputPort<Point>;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "type", content = "value")]
 pub enum IntersectConfig {
     Tip,
@@ -286,18 +348,21 @@ impl IntersectConfig {
 /// Optional configuration to stop processing new blocks after processing:
 ///   1. a block with the given hash
 ///   2. the first block on or after a given absolute slot
-///   3. TODO: a total of X blocks
+///   3. a total of X blocks
+///   4. the first block on or after a given epoch
 #[derive(Deserialize, Debug, Clone)]
 pub struct FinalizeConfig {
     until_hash: Option<String>,
     max_block_slot: Option<u64>,
-    // max_block_quantity: Option<u64>,
+    max_block_quantity: Option<u64>,
+    max_block_epoch: Option<u64>,
 }
 
 pub fn should_finalize(
     config: &Option<FinalizeConfig>,
+    chain: &GenesisValues,
     last_point: &Point,
-    // block_count: u64,
+    block_count: u64,
 ) -> bool {
     let config = match config {
         Some(x) => x,
@@ -306,7 +371,9 @@ pub fn should_finalize(
 
     if let Some(expected) = &config.until_hash {
         if let Point::Specific(_, current) = last_point {
-            return expected == &hex::encode(current);
+            if expected == &hex::encode(current) {
+                return true;
+            }
         }
     }
 
@@ -316,11 +383,98 @@ pub fn should_finalize(
         }
     }
 
-    // if let Some(max) = config.max_block_quantity {
-    //     if block_count >= max {
-    //         return true;
-    //     }
-    // }
+    if let Some(max) = config.max_block_quantity {
+        if block_count >= max {
+            return true;
+        }
+    }
+
+    if let Some(max) = config.max_block_epoch {
+        let (epoch, _) = chain.absolute_slot_to_relative(last_point.slot_or_default());
+
+        if epoch >= max {
+            return true;
+        }
+    }
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finalize(
+        until_hash: Option<&str>,
+        max_block_slot: Option<u64>,
+        max_block_quantity: Option<u64>,
+        max_block_epoch: Option<u64>,
+    ) -> Option<FinalizeConfig> {
+        Some(FinalizeConfig {
+            until_hash: until_hash.map(String::from),
+            max_block_slot,
+            max_block_quantity,
+            max_block_epoch,
+        })
+    }
+
+    #[test]
+    fn finalize_disabled_by_default() {
+        let chain = GenesisValues::mainnet();
+        let point = Point::Specific(u64::MAX, vec![0xaa]);
+
+        assert!(!should_finalize(&None, &chain, &point, u64::MAX));
+    }
+
+    #[test]
+    fn finalize_on_hash_doesnt_shadow_other_conditions() {
+        let chain = GenesisValues::mainnet();
+        let config = finalize(Some("bbbb"), Some(100), None, None);
+
+        let matching_hash = Point::Specific(10, vec![0xbb, 0xbb]);
+        assert!(should_finalize(&config, &chain, &matching_hash, 1));
+
+        let other_hash = Point::Specific(10, vec![0xaa]);
+        assert!(!should_finalize(&config, &chain, &other_hash, 1));
+
+        let max_slot = Point::Specific(100, vec![0xaa]);
+        assert!(should_finalize(&config, &chain, &max_slot, 1));
+    }
+
+    #[test]
+    fn finalize_on_block_quantity() {
+        let chain = GenesisValues::mainnet();
+        let config = finalize(None, None, Some(3), None);
+        let point = Point::Specific(10, vec![0xaa]);
+
+        assert!(!should_finalize(&config, &chain, &point, 2));
+        assert!(should_finalize(&config, &chain, &point, 3));
+    }
+
+    #[test]
+    fn finalize_on_epoch() {
+        let chain = GenesisValues::mainnet();
+        let config = finalize(None, None, None, Some(208));
+
+        // last byron slot and first shelley slot on mainnet
+        let byron = Point::Specific(4492799, vec![]);
+        assert!(!should_finalize(&config, &chain, &byron, 1));
+
+        let shelley = Point::Specific(4492800, vec![]);
+        assert!(should_finalize(&config, &chain, &shelley, 1));
+    }
+
+    #[test]
+    fn breadcrumbs_rollback() {
+        let mut breadcrumbs = Breadcrumbs::new(5);
+        breadcrumbs.track(Point::Specific(10, vec![0xaa]));
+        breadcrumbs.track(Point::Specific(20, vec![0xbb]));
+
+        assert!(!breadcrumbs.rollback(&Point::Specific(20, vec![0xbb])));
+        assert!(breadcrumbs.rollback(&Point::Specific(15, vec![0xcc])));
+        assert_eq!(breadcrumbs.points(), vec![Point::Specific(10, vec![0xaa])]);
+
+        assert!(breadcrumbs.rollback(&Point::Origin));
+        assert!(breadcrumbs.is_empty());
+    }
+}
@@ -5,6 +5,7 @@ use gasket::framework::*;
 use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
 
+use crate::framework::block_keys::KeyLayout;
 use crate::framework::*;
 
 pub struct Worker {
@@ -46,23 +47,24 @@ impl gasket::framework::Worker<Stage> for Worker {
         }
         .or_panic()?;
 
-        let key = match &point {
-            Point::Specific(slot, hash) => Ok(format!(
-                "{}{}.{}",
-                stage.config.prefix,
-                slot,
-                hex::encode(hash)
-            )),
+        let (slot, hash) = match &point {
+            Point::Specific(slot, hash) => Ok((*slot, hash)),
             Point::Origin => Err(Error::Config(String::from("Invalid chain point"))),
         }
         .or_panic()?;
 
+        let key = stage
+            .config
+            .key_layout
+            .key(&stage.config.prefix, slot, hash);
+
         self.client
             .put_object()
             .bucket(&stage.config.bucket)
             .key(key)
             .body(ByteStream::from(cbor))
-            .metadata("slot", point.slot_or_default().to_string())
+            .metadata("slot", slot.to_string())
+            .metadata("hash", hex::encode(hash))
             .content_type("application/cbor")
             .send()
             .await
@@ -97,6 +99,8 @@ pub struct Config {
     pub bucket: String,
     #[serde(default)]
     pub prefix: String,
+    #[serde(default)]
+    pub key_layout: KeyLayout,
 }
 
 impl Config {
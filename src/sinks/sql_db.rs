@@ -56,6 +56,19 @@ impl gasket::framework::Worker<Stage> for Worker {
                 let data = hbs_data(p.clone(), None);
                 stage.templates.render("reset", &data)
             }
+            ChainEvent::Pending(p, r) if stage.templates.has_template("pending") => {
+                let data = hbs_data(p.clone(), Some(r.clone()));
+                stage.templates.render("pending", &data)
+            }
+            ChainEvent::Removed(p, r) if stage.templates.has_template("removed") => {
+                let data = hbs_data(p.clone(), Some(r.clone()));
+                stage.templates.render("removed", &data)
+            }
+            // mempool events are ignored unless there's a template for them
+            ChainEvent::Pending(..) | ChainEvent::Removed(..) => {
+                stage.cursor.send(point.into()).await.or_panic()?;
+                return Ok(());
+            }
         };
 
         let statement = template.or_panic()?;
@@ -94,6 +107,8 @@ pub struct Config {
     pub apply_template: String,
     pub undo_template: String,
     pub reset_template: String,
+    pub pending_template: Option<String>,
+    pub removed_template: Option<String>,
 }
 
 impl Config {
@@ -114,6 +129,18 @@ impl Config {
             .register_template_string("reset", &self.reset_template)
             .map_err(Error::config)?;
 
+        if let Some(template) = &self.pending_template {
+            templates
+                .register_template_string("pending", template)
+                .map_err(Error::config)?;
+        }
+
+        if let Some(template) = &self.removed_template {
+            templates
+                .register_template_string("removed", template)
+                .map_err(Error::config)?;
+        }
+
         let stage = Stage {
             config: self,
             templates,
@@ -100,6 +100,20 @@ impl LogLine {
         }
     }
 
+    /// Formats a record of the mempool, labeled so it's not mistaken for a
+    /// confirmed one
+    pub fn mempool(
+        source: &Record,
+        label: &'static str,
+        max_width: Option<usize>,
+        adahandle_policy: &Option<String>,
+    ) -> LogLine {
+        let mut log = LogLine::handle(source, max_width, adahandle_policy);
+        log.prefix = label;
+        log.color = Color::DarkYellow;
+        log
+    }
+
     pub fn reset(point: Point) -> LogLine {
         let mut log = LogLine::new("RESET", Color::DarkRed);
 
@@ -383,6 +397,106 @@ impl LogLine {
                 format!(
                     "{{ reserves: {from_reserves}, treasury: {from_treasury}, to_credentials: {to_stake_credentials:?}, to_other_pot: {to_other_pot:?} }}"),
             ),
+            EventData::VoteDelegation { credential, drep } => LogLine::from_legacy_v1(
+                source,
+                "VOTE>",
+                Color::Magenta,
+                max_width,
+                format!("{{ credential: {credential:?}, drep: {drep:?} }}"),
+            ),
+            EventData::StakeVoteDelegation {
+                credential,
+                pool_hash,
+                drep,
+            } => LogLine::from_legacy_v1(
+                source,
+                "DELE",
+                Color::Magenta,
+                max_width,
+                format!("{{ credential: {credential:?}, pool: {pool_hash}, drep: {drep:?} }}"),
+            ),
+            EventData::StakeRegistrationDelegation {
+                credential,
+                pool_hash,
+                drep,
+                deposit,
+            } => LogLine::from_legacy_v1(
+                source,
+                "STAKE+",
+                Color::Magenta,
+                max_width,
+                format!(
+                    "{{ credential: {credential:?}, pool: {pool_hash:?}, drep: {drep:?}, deposit: {deposit} }}"),
+            ),
+            EventData::DRepRegistration {
+                credential,
+                deposit,
+                anchor,
+            } => LogLine::from_legacy_v1(
+                source,
+                "DREP+",
+                Color::Magenta,
+                max_width,
+                format!("{{ credential: {credential:?}, deposit: {deposit}, anchor: {anchor:?} }}"),
+            ),
+            EventData::DRepUpdate { credential, anchor } => LogLine::from_legacy_v1(
+                source,
+                "DREP",
+                Color::Magenta,
+                max_width,
+                format!("{{ credential: {credential:?}, anchor: {anchor:?} }}"),
+            ),
+            EventData::DRepRetirement { credential, refund } => LogLine::from_legacy_v1(
+                source,
+                "DREP-",
+                Color::DarkMagenta,
+                max_width,
+                format!("{{ credential: {credential:?}, refund: {refund} }}"),
+            ),
+            EventData::CommitteeHotKeyAuth {
+                cold_credential,
+                hot_credential,
+            } => LogLine::from_legacy_v1(
+                source,
+                "CMTE+",
+                Color::Magenta,
+                max_width,
+                format!("{{ cold: {cold_credential:?}, hot: {hot_credential:?} }}"),
+            ),
+            EventData::CommitteeResign {
+                cold_credential,
+                anchor,
+            } => LogLine::from_legacy_v1(
+                source,
+                "CMTE-",
+                Color::DarkMagenta,
+                max_width,
+                format!("{{ cold: {cold_credential:?}, anchor: {anchor:?} }}"),
+            ),
+            EventData::GovProposal(GovProposalRecord {
+                action_id,
+                action_kind,
+                deposit,
+                ..
+            }) => LogLine::from_legacy_v1(
+                source,
+                "PROPOSAL",
+                Color::DarkYellow,
+                max_width,
+                format!("{{ id: {action_id}, kind: {action_kind}, deposit: {deposit} }}"),
+            ),
+            EventData::Vote(VoteRecord {
+                voter,
+                action_id,
+                vote,
+                ..
+            }) => LogLine::from_legacy_v1(
+                source,
+                "VOTE",
+                Color::DarkYellow,
+                max_width,
+                format!("{{ voter: {voter:?}, action: {action_id}, vote: {vote} }}"),
+            ),
             EventData::RollBack {
                 block_slot,
                 block_hash,
@@ -71,6 +71,12 @@ impl gasket::framework::Worker<Stage> for Worker {
                 LogLine::handle(record, width, &stage.config.adahandle_policy)
             }
             ChainEvent::Reset(point) => LogLine::reset(point.clone()),
+            ChainEvent::Pending(_, record) => {
+                LogLine::mempool(record, "PENDING", width, &stage.config.adahandle_policy)
+            }
+            ChainEvent::Removed(_, record) => {
+                LogLine::mempool(record, "REMOVED", width, &stage.config.adahandle_policy)
+            }
         };
 
         self.throttle.wait_turn();
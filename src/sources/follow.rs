@@ -0,0 +1,140 @@
+//! Live chain-sync for sources that replay historical data first (such as
+//! mithril) and then keep following the chain from the last replayed point.
+
+use gasket::framework::*;
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+
+use pallas::ledger::traverse::MultiEraHeader;
+use pallas::network::facades::PeerClient;
+use pallas::network::miniprotocols::chainsync::{BlockContent, HeaderContent, NextResponse};
+use pallas::network::miniprotocols::Point;
+
+#[cfg(target_family = "unix")]
+use pallas::network::facades::NodeClient;
+
+use crate::framework::*;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type")]
+pub enum Config {
+    N2N {
+        peers: Vec<String>,
+    },
+
+    #[cfg(target_family = "unix")]
+    N2C {
+        socket_path: std::path::PathBuf,
+    },
+}
+
+pub enum Session {
+    N2N(PeerClient),
+
+    #[cfg(target_family = "unix")]
+    N2C(NodeClient),
+}
+
+fn header_point(header: &HeaderContent) -> Result<Point, WorkerError> {
+    let header = match header.byron_prefix {
+        Some((subtag, _)) => MultiEraHeader::decode(header.variant, Some(subtag), &header.cbor),
+        None => MultiEraHeader::decode(header.variant, None, &header.cbor),
+    }
+    .or_panic()?;
+
+    Ok(Point::Specific(header.slot(), header.hash().to_vec()))
+}
+
+impl Session {
+    /// Connects to the first reachable upstream of the config
+    pub async fn connect(config: &Config, magic: u64) -> Result<Self, WorkerError> {
+        match config {
+            Config::N2N { peers } => {
+                for address in peers {
+                    debug!(address, "connecting");
+
+                    match PeerClient::connect(address, magic).await {
+                        Ok(x) => {
+                            info!(address, "connected to peer");
+                            return Ok(Self::N2N(x));
+                        }
+                        Err(err) => warn!(address, %err, "failed to connect to peer"),
+                    }
+                }
+
+                warn!("couldn't connect to any of the upstream peers");
+
+                Err(WorkerError::Retry)
+            }
+            #[cfg(target_family = "unix")]
+            Config::N2C { socket_path } => {
+                debug!(?socket_path, "connecting");
+
+                let client = NodeClient::connect(socket_path, magic).await.or_retry()?;
+
+                Ok(Self::N2C(client))
+            }
+        }
+    }
+
+    /// Finds the intersection with the given points, newest first. Following
+    /// the chain from somewhere else would repeat or skip blocks, so failing
+    /// to intersect is an error.
+    pub async fn intersect(&mut self, points: Vec<Point>) -> Result<Point, WorkerError> {
+        let (intersect, _) = match self {
+            Self::N2N(x) => x.chainsync().find_intersect(points).await.or_restart()?,
+            #[cfg(target_family = "unix")]
+            Self::N2C(x) => x.chainsync().find_intersect(points).await.or_restart()?,
+        };
+
+        let Some(intersect) = intersect else {
+            return Err(Error::custom(
+                "upstream doesn't know any of the replayed points",
+            ))
+            .or_panic();
+        };
+
+        info!(?intersect, "intersected");
+
+        Ok(intersect)
+    }
+
+    /// Requests the next chain-sync response, waiting for a new block when the
+    /// upstream is at the tip. Blocks announced by node-to-node peers are
+    /// fetched so both kinds of session yield the full block.
+    pub async fn next(&mut self) -> Result<NextResponse<BlockContent>, WorkerError> {
+        match self {
+            Self::N2N(peer) => {
+                let client = peer.chainsync();
+
+                let next = match client.has_agency() {
+                    true => client.request_next().await.or_restart()?,
+                    false => client.recv_while_must_reply().await.or_restart()?,
+                };
+
+                match next {
+                    NextResponse::RollForward(header, tip) => {
+                        let point = header_point(&header)?;
+
+                        let block = peer.blockfetch().fetch_single(point).await.or_restart()?;
+
+                        Ok(NextResponse::RollForward(BlockContent(block), tip))
+                    }
+                    NextResponse::RollBackward(point, tip) => {
+                        Ok(NextResponse::RollBackward(point, tip))
+                    }
+                    NextResponse::Await => Ok(NextResponse::Await),
+                }
+            }
+            #[cfg(target_family = "unix")]
+            Self::N2C(node) => {
+                let client = node.chainsync();
+
+                match client.has_agency() {
+                    true => client.request_next().await.or_restart(),
+                    false => client.recv_while_must_reply().await.or_restart(),
+                }
+            }
+        }
+    }
+}
@@ -118,8 +118,14 @@ type HydraConnection = WebSocketStream<MaybeTlsStream<TcpStream>>;
 pub struct Stage {
     config: Config,
 
+    chain: GenesisValues,
+
     intersect: IntersectConfig,
 
+    finalize: Option<FinalizeConfig>,
+
+    block_count: u64,
+
     pub output: SourceOutputPort,
 
     #[metric]
@@ -132,6 +138,7 @@ pub struct Stage {
 pub struct Worker {
     socket: HydraConnection,
     intersect: WorkerIntersect,
+    finalized: bool,
 }
 
 /// Worker state for finding the right intersection point
@@ -190,6 +197,13 @@ impl Worker {
             stage.ops_count.inc(1);
         }
 
+        stage.block_count += 1;
+
+        if should_finalize(&stage.finalize, &stage.chain, &point, stage.block_count) {
+            info!(?point, "finalize condition reached");
+            self.finalized = true;
+        }
+
         Ok(())
     }
 }
@@ -224,12 +238,17 @@ impl gasket::framework::Worker<Stage> for Worker {
         let worker = Self {
             socket,
             intersect: intersect_from_config(&stage.intersect),
+            finalized: false,
         };
 
         Ok(worker)
     }
 
     async fn schedule(&mut self, _stage: &mut Stage) -> Result<WorkSchedule<Message>, WorkerError> {
+        if self.finalized {
+            return Ok(WorkSchedule::Done);
+        }
+
         let next_msg = self.socket.next().await.transpose().or_restart()?;
 
         Ok(match next_msg {
@@ -264,7 +283,10 @@ impl Config {
     pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
             config: self,
+            chain: ctx.chain.clone().into(),
             intersect: ctx.intersect.clone(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
             output: Default::default(),
             ops_count: Default::default(),
             current_slot: Default::default(),
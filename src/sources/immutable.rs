@@ -0,0 +1,381 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use gasket::framework::*;
+use itertools::Itertools;
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use pallas::ledger::traverse::MultiEraBlock;
+use pallas::network::miniprotocols::Point;
+use pallas::storage::hardano::immutable::{self, FallibleBlock};
+
+use crate::framework::*;
+
+type BlockIter = Box<dyn Iterator<Item = FallibleBlock> + Send + Sync>;
+
+const DEFAULT_POLL_INTERVAL: u64 = 10;
+
+const BATCH_SIZE: usize = 100;
+
+/// Translates the intersect config into points of the immutable db, newest
+/// first
+pub(crate) fn get_starting_points(
+    dir: &Path,
+    config: &IntersectConfig,
+) -> Result<Vec<Point>, Box<dyn std::error::Error>> {
+    match config {
+        IntersectConfig::Tip => {
+            immutable::get_tip(dir)?.map_or(Ok(vec![Point::Origin]), |point| Ok(vec![point]))
+        }
+        IntersectConfig::Origin => Ok(vec![Point::Origin]),
+        IntersectConfig::Point(slot, hash) => {
+            let hash_bytes = hex::decode(hash)?;
+            Ok(vec![Point::Specific(*slot, hash_bytes)])
+        }
+        IntersectConfig::Breadcrumbs(points) => points
+            .iter()
+            .map(|(slot, hash)| {
+                let hash_bytes = hex::decode(hash)?;
+                Ok(Point::Specific(*slot, hash_bytes))
+            })
+            .collect(),
+    }
+}
+
+/// Reads the blocks that follow the first of the points found in the db. As
+/// with chain-sync, the block of the point itself is not included.
+pub(crate) fn read_blocks_after(dir: &Path, points: &[Point]) -> Result<BlockIter, WorkerError> {
+    for point in points {
+        match immutable::read_blocks_from_point(dir, point.clone()) {
+            Ok(iter) => {
+                debug!(?point, "intersected");
+
+                let skip = match point {
+                    Point::Origin => 0,
+                    Point::Specific(..) => 1,
+                };
+
+                return Ok(Box::new(iter.skip(skip)));
+            }
+            Err(err) => debug!(?point, %err, "point not found in immutable db"),
+        }
+    }
+
+    Err(Error::custom(
+        "none of the intersect points is in the immutable db",
+    ))
+    .or_panic()
+}
+
+#[derive(Stage)]
+#[stage(name = "source", unit = "Vec<Vec<u8>>", worker = "Worker")]
+pub struct Stage {
+    config: Config,
+
+    chain: GenesisValues,
+
+    intersect: IntersectConfig,
+
+    finalize: Option<FinalizeConfig>,
+
+    breadcrumbs: Breadcrumbs,
+
+    block_count: u64,
+
+    pub output: SourceOutputPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    current_slot: gasket::metrics::Gauge,
+}
+
+pub struct Worker {
+    /// points where reading started, used to read again when nothing was
+    /// sent downstream yet
+    start: Vec<Point>,
+
+    blocks: BlockIter,
+
+    finalized: bool,
+}
+
+impl Worker {
+    /// Reads the db again from the last block sent downstream, which makes
+    /// the chunks written since the previous read visible
+    fn reopen(&mut self, stage: &Stage) -> Result<(), WorkerError> {
+        let points = match stage.breadcrumbs.is_empty() {
+            true => self.start.clone(),
+            false => stage.breadcrumbs.points(),
+        };
+
+        self.blocks = read_blocks_after(&stage.config.path, &points)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        let start = match stage.breadcrumbs.is_empty() {
+            true => get_starting_points(&stage.config.path, &stage.intersect)
+                .map_err(Error::custom)
+                .or_panic()?,
+            false => stage.breadcrumbs.points(),
+        };
+
+        let blocks = read_blocks_after(&stage.config.path, &start)?;
+
+        Ok(Self {
+            start,
+            blocks,
+            finalized: false,
+        })
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<Vec<Vec<u8>>>, WorkerError> {
+        if self.finalized {
+            return Ok(WorkSchedule::Done);
+        }
+
+        let batch: Vec<_> = self
+            .blocks
+            .by_ref()
+            .take(BATCH_SIZE)
+            .try_collect()
+            .or_panic()?;
+
+        if !batch.is_empty() {
+            return Ok(WorkSchedule::Unit(batch));
+        }
+
+        if !stage.config.follow {
+            info!("reached the tip of the immutable db");
+            return Ok(WorkSchedule::Done);
+        }
+
+        let interval = stage.config.poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL);
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        self.reopen(stage)?;
+
+        Ok(WorkSchedule::Idle)
+    }
+
+    async fn execute(&mut self, unit: &Vec<Vec<u8>>, stage: &mut Stage) -> Result<(), WorkerError> {
+        for cbor in unit {
+            let block = MultiEraBlock::decode(cbor).or_panic()?;
+            let point = Point::Specific(block.slot(), block.hash().to_vec());
+
+            let evt = ChainEvent::Apply(point.clone(), Record::CborBlock(cbor.clone()));
+            stage.output.send(evt.into()).await.or_panic()?;
+
+            stage.breadcrumbs.track(point.clone());
+
+            stage.current_slot.set(point.slot_or_default() as i64);
+            stage.ops_count.inc(1);
+
+            stage.block_count += 1;
+
+            if should_finalize(&stage.finalize, &stage.chain, &point, stage.block_count) {
+                info!(?point, "finalize condition reached");
+                self.finalized = true;
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    /// Path of the `immutable` directory of the node database
+    pub path: PathBuf,
+
+    /// Keep watching the directory for new chunks once all the existing ones
+    /// were read, instead of stopping
+    #[serde(default)]
+    pub follow: bool,
+
+    /// Seconds between checks for new chunks while following
+    pub poll_interval: Option<u64>,
+}
+
+impl Config {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
+        let stage = Stage {
+            config: self,
+            breadcrumbs: ctx.breadcrumbs.clone(),
+            chain: ctx.chain.clone().into(),
+            intersect: ctx.intersect.clone(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
+            output: Default::default(),
+            ops_count: Default::default(),
+            current_slot: Default::default(),
+        };
+
+        Ok(stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gasket::framework::Worker as _;
+    use gasket::messaging::InputPort;
+
+    use super::*;
+    use crate::filters::testing::block;
+
+    /// Writes a chunk of empty blocks at the given slots, along with the
+    /// indexes needed to read it, and returns the points of its blocks
+    fn write_chunk(dir: &Path, name: &str, slots: &[u64]) -> Vec<Point> {
+        let mut points = vec![];
+        let mut chunk = vec![];
+
+        // a version byte followed by the offsets of the secondary entries
+        let mut primary = vec![1];
+        primary.extend(0u32.to_be_bytes());
+
+        let mut secondary = vec![];
+
+        for (idx, slot) in slots.iter().enumerate() {
+            let (point, record) = block(*slot, vec![]);
+
+            let Record::CborBlock(cbor) = record else {
+                unreachable!()
+            };
+
+            // only the offset of the block in the chunk is used by the reader,
+            // the remaining 48 bytes of the entry describe the header
+            secondary.extend((chunk.len() as u64).to_be_bytes());
+            secondary.extend([0; 48]);
+
+            primary.extend((((idx + 1) * 56) as u32).to_be_bytes());
+
+            chunk.extend(cbor);
+            points.push(point);
+        }
+
+        let path = dir.join(name);
+        std::fs::write(path.with_extension("chunk"), chunk).unwrap();
+        std::fs::write(path.with_extension("primary"), primary).unwrap();
+        std::fs::write(path.with_extension("secondary"), secondary).unwrap();
+
+        points
+    }
+
+    /// An immutable db with blocks at slots 0 to 4, the last chunk (slot 5) is
+    /// still being written so it isn't read
+    fn fixture() -> (tempfile::TempDir, Vec<Point>) {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut points = write_chunk(dir.path(), "00000", &[0, 1, 2]);
+        points.extend(write_chunk(dir.path(), "00001", &[3, 4]));
+        write_chunk(dir.path(), "00002", &[5]);
+
+        (dir, points)
+    }
+
+    fn slots(blocks: BlockIter) -> Vec<u64> {
+        blocks
+            .map(|x| MultiEraBlock::decode(&x.unwrap()).unwrap().slot())
+            .collect()
+    }
+
+    #[test]
+    fn origin_includes_first_block() {
+        let (dir, _) = fixture();
+
+        let blocks = read_blocks_after(dir.path(), &[Point::Origin]).unwrap();
+        assert_eq!(slots(blocks), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn point_skips_its_own_block() {
+        let (dir, points) = fixture();
+
+        let blocks = read_blocks_after(dir.path(), &[points[1].clone()]).unwrap();
+        assert_eq!(slots(blocks), vec![2, 3, 4]);
+
+        // the last block of a chunk
+        let blocks = read_blocks_after(dir.path(), &[points[2].clone()]).unwrap();
+        assert_eq!(slots(blocks), vec![3, 4]);
+    }
+
+    #[test]
+    fn breadcrumbs_start_after_first_known_point() {
+        let (dir, points) = fixture();
+
+        let unknown = Point::Specific(4, vec![9; 32]);
+
+        let breadcrumbs = [unknown.clone(), points[3].clone(), points[0].clone()];
+        let blocks = read_blocks_after(dir.path(), &breadcrumbs).unwrap();
+        assert_eq!(slots(blocks), vec![4]);
+
+        // there's no fallback to origin
+        assert!(read_blocks_after(dir.path(), &[unknown]).is_err());
+    }
+
+    #[tokio::test]
+    async fn follow_reopens_after_last_sent_block() {
+        let (dir, _) = fixture();
+
+        let config = Config {
+            path: dir.path().to_owned(),
+            follow: true,
+            poll_interval: Some(0),
+        };
+
+        let mut stage = config
+            .bootstrapper(&Context {
+                chain: Default::default(),
+                intersect: IntersectConfig::Origin,
+                finalize: None,
+                current_dir: ".".into(),
+                breadcrumbs: Breadcrumbs::new(10),
+                control: Default::default(),
+            })
+            .unwrap();
+
+        let mut events = InputPort::default();
+        gasket::messaging::tokio::connect_ports(&mut stage.output, &mut events, 100);
+
+        let mut worker = Worker::bootstrap(&stage).await.unwrap();
+
+        let WorkSchedule::Unit(batch) = worker.schedule(&mut stage).await.unwrap() else {
+            panic!("expected the blocks of the db");
+        };
+        worker.execute(&batch, &mut stage).await.unwrap();
+
+        // the chunk of slot 5 becomes immutable once the next one is written,
+        // the db is read again from the last block that was sent
+        write_chunk(dir.path(), "00003", &[6]);
+
+        let schedule = worker.schedule(&mut stage).await.unwrap();
+        assert!(matches!(schedule, WorkSchedule::Idle));
+
+        let WorkSchedule::Unit(batch) = worker.schedule(&mut stage).await.unwrap() else {
+            panic!("expected the blocks of the new chunk");
+        };
+        worker.execute(&batch, &mut stage).await.unwrap();
+
+        let mut sent = vec![];
+
+        for _ in 0..6 {
+            match events.recv().await.unwrap().payload {
+                ChainEvent::Apply(point, _) => sent.push(point.slot_or_default()),
+                _ => panic!("expected an apply event"),
+            }
+        }
+
+        assert_eq!(sent, vec![0, 1, 2, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,349 @@
+//! Emits the txs of the mempool of a local node, using the local-tx-monitor
+//! mini-protocol. Txs that enter the mempool are sent as pending events and
+//! txs that leave it (because they were included in a block or discarded) are
+//! sent as removed events.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use gasket::framework::*;
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+
+use pallas::crypto::hash::Hash;
+use pallas::ledger::traverse::MultiEraTx;
+use pallas::network::facades::NodeClient;
+use pallas::network::miniprotocols::Point;
+
+use crate::framework::*;
+
+/// The txs of the mempool at a given slot
+pub struct Snapshot {
+    slot: u64,
+    txs: Vec<Vec<u8>>,
+}
+
+#[derive(Stage)]
+#[stage(name = "source", unit = "Snapshot", worker = "Worker")]
+pub struct Stage {
+    config: Config,
+
+    chain: GenesisValues,
+
+    /// txs in the latest snapshot of the mempool, kept in the stage so a
+    /// restart of the worker only reports what changed in the meantime
+    known: HashMap<Hash<32>, Vec<u8>>,
+
+    pub output: SourceOutputPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    mempool_size: gasket::metrics::Gauge,
+
+    #[metric]
+    current_slot: gasket::metrics::Gauge,
+}
+
+pub struct Worker {
+    peer_session: NodeClient,
+
+    /// true once the first snapshot was acquired, the following acquires
+    /// wait until the mempool changes
+    acquired: bool,
+}
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        debug!("connecting");
+
+        let peer_session = NodeClient::connect(&stage.config.socket_path, stage.chain.magic)
+            .await
+            .or_retry()?;
+
+        let worker = Self {
+            peer_session,
+            acquired: false,
+        };
+
+        Ok(worker)
+    }
+
+    async fn schedule(
+        &mut self,
+        _stage: &mut Stage,
+    ) -> Result<WorkSchedule<Snapshot>, WorkerError> {
+        let monitor = self.peer_session.monitor();
+
+        if self.acquired {
+            info!("awaiting mempool changes (blocking)");
+        }
+
+        let slot = monitor.acquire().await.or_restart()?;
+        self.acquired = true;
+
+        let mut txs = vec![];
+
+        while let Some((_, tx)) = monitor.query_next_tx().await.or_restart()? {
+            txs.push(tx.0.to_vec());
+        }
+
+        debug!(slot, txs = txs.len(), "acquired mempool snapshot");
+
+        Ok(WorkSchedule::Unit(Snapshot { slot, txs }))
+    }
+
+    async fn execute(&mut self, unit: &Snapshot, stage: &mut Stage) -> Result<(), WorkerError> {
+        let point = Point::Specific(unit.slot, vec![]);
+
+        let mut current = HashMap::with_capacity(unit.txs.len());
+        let mut added = vec![];
+
+        for cbor in unit.txs.iter() {
+            let hash = match MultiEraTx::decode(cbor) {
+                Ok(tx) => tx.hash(),
+                Err(err) => {
+                    warn!(%err, "can't decode mempool tx, skipping");
+                    continue;
+                }
+            };
+
+            if !stage.known.contains_key(&hash) {
+                added.push(cbor.clone());
+            }
+
+            current.insert(hash, cbor.clone());
+        }
+
+        let known = std::mem::replace(&mut stage.known, current);
+
+        for (hash, cbor) in known {
+            if stage.known.contains_key(&hash) {
+                continue;
+            }
+
+            debug!(%hash, "tx left the mempool");
+
+            let evt = ChainEvent::removed(point.clone(), Record::CborTx(cbor));
+            stage.output.send(evt).await.or_panic()?;
+            stage.ops_count.inc(1);
+        }
+
+        for cbor in added {
+            let evt = ChainEvent::pending(point.clone(), Record::CborTx(cbor));
+            stage.output.send(evt).await.or_panic()?;
+            stage.ops_count.inc(1);
+        }
+
+        stage.mempool_size.set(stage.known.len() as i64);
+        stage.current_slot.set(unit.slot as i64);
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub socket_path: PathBuf,
+}
+
+impl Config {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
+        let stage = Stage {
+            config: self,
+            chain: ctx.chain.clone().into(),
+            known: Default::default(),
+            output: Default::default(),
+            ops_count: Default::default(),
+            mempool_size: Default::default(),
+            current_slot: Default::default(),
+        };
+
+        Ok(stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gasket::framework::Worker as _;
+    use gasket::messaging::InputPort;
+    use pallas::codec::minicbor;
+    use pallas::codec::utils::{Nullable, TagWrap};
+    use pallas::ledger::primitives::conway;
+    use pallas::network::miniprotocols::handshake::{self, n2c};
+    use pallas::network::miniprotocols::txmonitor::Message;
+    use pallas::network::miniprotocols::{PROTOCOL_N2C_HANDSHAKE, PROTOCOL_N2C_TX_MONITOR};
+    use pallas::network::multiplexer::{Bearer, ChannelBuffer, Plexer};
+    use tokio::net::UnixListener;
+
+    use super::*;
+    use crate::filters::testing::{output, tx, witnesses};
+
+    fn mempool_tx(coin: u64) -> Vec<u8> {
+        let tx = conway::Tx {
+            transaction_body: tx(vec![], vec![output(coin)]),
+            transaction_witness_set: witnesses(),
+            success: true,
+            auxiliary_data: Nullable::Null,
+        };
+
+        minicbor::to_vec(tx).unwrap()
+    }
+
+    /// Starts a node that serves the given mempool snapshots through
+    /// local-tx-monitor, one per acquire. The snapshots are shared by the
+    /// successive connections, so a reconnecting client gets the next one.
+    async fn mock_node(snapshots: Vec<(u64, Vec<Vec<u8>>)>) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node.socket");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        tokio::spawn(async move {
+            let mut snapshots = snapshots.into_iter();
+
+            while let Ok((bearer, _)) = Bearer::accept_unix(&listener).await {
+                let mut plexer = Plexer::new(bearer);
+                let handshake = plexer.subscribe_server(PROTOCOL_N2C_HANDSHAKE);
+                let monitor = plexer.subscribe_server(PROTOCOL_N2C_TX_MONITOR);
+                let plexer = plexer.spawn();
+
+                handshake::N2CServer::new(handshake)
+                    .handshake(n2c::VersionTable::v10_and_above(
+                        GenesisValues::mainnet().magic,
+                    ))
+                    .await
+                    .unwrap();
+
+                let mut monitor = ChannelBuffer::new(monitor);
+                let mut txs = vec![].into_iter();
+
+                // the client keeps waiting once there are no snapshots left
+                while let Ok(msg) = monitor.recv_full_msg::<Message>().await {
+                    let reply = match msg {
+                        Message::Acquire => match snapshots.next() {
+                            Some((slot, snapshot)) => {
+                                txs = snapshot.into_iter();
+                                Message::Acquired(slot)
+                            }
+                            None => continue,
+                        },
+                        Message::RequestNextTx => {
+                            let tx = txs.next().map(|x| (6, TagWrap(x.into())));
+                            Message::ResponseNextTx(tx)
+                        }
+                        _ => continue,
+                    };
+
+                    monitor.send_msg_chunks(&reply).await.unwrap();
+                }
+
+                plexer.abort().await;
+            }
+        });
+
+        (dir, path)
+    }
+
+    fn stage(socket_path: PathBuf) -> (Stage, InputPort<ChainEvent>) {
+        let config = Config { socket_path };
+
+        let mut stage = config
+            .bootstrapper(&Context {
+                chain: Default::default(),
+                intersect: IntersectConfig::Origin,
+                finalize: None,
+                current_dir: ".".into(),
+                breadcrumbs: Breadcrumbs::new(10),
+                control: Default::default(),
+            })
+            .unwrap();
+
+        let mut events = InputPort::default();
+        gasket::messaging::tokio::connect_ports(&mut stage.output, &mut events, 100);
+
+        (stage, events)
+    }
+
+    async fn next_snapshot(worker: &mut Worker, stage: &mut Stage) {
+        let WorkSchedule::Unit(snapshot) = worker.schedule(stage).await.unwrap() else {
+            panic!("expected a mempool snapshot");
+        };
+
+        worker.execute(&snapshot, stage).await.unwrap();
+    }
+
+    /// The kind, slot and tx of the next event
+    async fn recv(events: &mut InputPort<ChainEvent>) -> (&'static str, u64, Vec<u8>) {
+        match events.recv().await.unwrap().payload {
+            ChainEvent::Pending(point, Record::CborTx(tx)) => {
+                ("pending", point.slot_or_default(), tx)
+            }
+            ChainEvent::Removed(point, Record::CborTx(tx)) => {
+                ("removed", point.slot_or_default(), tx)
+            }
+            _ => panic!("expected a mempool event"),
+        }
+    }
+
+    #[tokio::test]
+    async fn snapshots_are_diffed() {
+        let (a, b, c) = (mempool_tx(1), mempool_tx(2), mempool_tx(3));
+
+        let (_dir, path) = mock_node(vec![
+            (10, vec![a.clone(), b.clone()]),
+            (11, vec![b.clone(), c.clone()]),
+            (12, vec![b.clone(), c.clone()]),
+            (13, vec![]),
+        ])
+        .await;
+
+        let (mut stage, mut events) = stage(path);
+        let mut worker = Worker::bootstrap(&stage).await.unwrap();
+
+        next_snapshot(&mut worker, &mut stage).await;
+        assert_eq!(recv(&mut events).await, ("pending", 10, a.clone()));
+        assert_eq!(recv(&mut events).await, ("pending", 10, b.clone()));
+
+        next_snapshot(&mut worker, &mut stage).await;
+        assert_eq!(recv(&mut events).await, ("removed", 11, a));
+        assert_eq!(recv(&mut events).await, ("pending", 11, c.clone()));
+
+        // nothing changed at slot 12
+        next_snapshot(&mut worker, &mut stage).await;
+        next_snapshot(&mut worker, &mut stage).await;
+
+        let mut removed = vec![recv(&mut events).await, recv(&mut events).await];
+        removed.sort();
+        assert_eq!(removed, vec![("removed", 13, b), ("removed", 13, c)]);
+    }
+
+    #[tokio::test]
+    async fn restarted_worker_reports_changes_only() {
+        let (a, b) = (mempool_tx(1), mempool_tx(2));
+
+        let (_dir, path) = mock_node(vec![
+            (10, vec![a.clone(), b.clone()]),
+            (11, vec![b.clone()]),
+        ])
+        .await;
+
+        let (mut stage, mut events) = stage(path);
+
+        let mut worker = Worker::bootstrap(&stage).await.unwrap();
+        next_snapshot(&mut worker, &mut stage).await;
+        assert_eq!(recv(&mut events).await, ("pending", 10, a.clone()));
+        assert_eq!(recv(&mut events).await, ("pending", 10, b));
+
+        // the known txs are kept in the stage, so a new worker only reports
+        // what changed while reconnecting
+        worker.peer_session.abort().await;
+
+        let mut worker = Worker::bootstrap(&stage).await.unwrap();
+        next_snapshot(&mut worker, &mut stage).await;
+        assert_eq!(recv(&mut events).await, ("removed", 11, a));
+
+        assert_eq!(stage.known.len(), 1);
+    }
+}
@@ -1,20 +1,28 @@
 use gasket::framework::*;
 use itertools::Itertools;
 use miette::{Context as _, IntoDiagnostic as _};
-use mithril_client::{ClientBuilder, MessageBuilder, MithrilError, MithrilResult};
+use mithril_client::{
+    Client, ClientBuilder, MessageBuilder, MithrilCertificate, MithrilError, MithrilResult,
+    Snapshot,
+};
 use pallas::{
     ledger::traverse::MultiEraBlock,
-    network::miniprotocols::Point::{self, *},
+    network::miniprotocols::{
+        chainsync::{BlockContent, NextResponse},
+        Point::{self, *},
+    },
 };
 use serde::Deserialize;
 use std::{
-    path::Path,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
-use tracing::{info, warn};
+use tracing::{debug, error, info, warn};
 
 use crate::framework::*;
+use crate::sources::follow;
+use crate::sources::immutable::{get_starting_points, read_blocks_after};
 
 struct Feedback {
     progress_logger: Arc<Mutex<ProgressLogger>>,
@@ -125,177 +133,301 @@ impl mithril_client::feedback::FeedbackReceiver for Feedback {
     }
 }
 
-async fn fetch_snapshot(config: &Config, feedback: Arc<Feedback>) -> MithrilResult<()> {
-    let client = ClientBuilder::aggregator(&config.aggregator, &config.genesis_key)
-        .add_feedback_receiver(feedback)
-        .build()?;
+/// File written next to the snapshot data once it's verified, it records the
+/// digest of the snapshot that was downloaded
+const DIGEST_MARKER: &str = "snapshot_digest";
+
+/// Entries of the snapshot archive, removed before downloading it again
+const SNAPSHOT_ENTRIES: &[&str] = &["immutable", "ledger", "volatile", "protocolMagicId"];
+
+const DEFAULT_DOWNLOAD_ATTEMPTS: usize = 3;
+
+fn read_digest_marker(dir: &Path) -> Option<String> {
+    std::fs::read_to_string(dir.join(DIGEST_MARKER))
+        .ok()
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+}
+
+fn write_digest_marker(dir: &Path, digest: &str) -> MithrilResult<()> {
+    std::fs::write(dir.join(DIGEST_MARKER), digest)?;
+    Ok(())
+}
+
+/// Removes the data of a previous (possibly partial) download
+fn clear_snapshot(dir: &Path) -> MithrilResult<()> {
+    let _ = std::fs::remove_file(dir.join(DIGEST_MARKER));
+
+    for entry in SNAPSHOT_ENTRIES {
+        let path = dir.join(entry);
+
+        if path.is_dir() {
+            std::fs::remove_dir_all(&path)?;
+        } else if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_dir_empty(dir: &Path) -> MithrilResult<bool> {
+    Ok(dir.read_dir()?.next().is_none())
+}
+
+/// Picks the snapshot to use: the one in the config, the one that was
+/// already downloaded or the latest one, in that order
+///
+/// Returns `None` if the downloaded snapshot is no longer available from the
+/// aggregator. Its data was verified when it was downloaded, so it's used as
+/// is instead of being replaced.
+async fn select_snapshot(
+    client: &Client,
+    config: &Config,
+    dir: &Path,
+) -> MithrilResult<Option<Snapshot>> {
+    let recorded = read_digest_marker(dir);
+
+    if let Some(digest) = config.snapshot_digest.as_ref().or(recorded.as_ref()) {
+        return match client.snapshot().get(digest).await? {
+            Some(snapshot) => Ok(Some(snapshot)),
+            None if recorded.as_ref() == Some(digest) => {
+                warn!(
+                    digest,
+                    "downloaded snapshot is no longer available, using existing data"
+                );
+                Ok(None)
+            }
+            None => Err(MithrilError::msg(format!("snapshot {digest} not found"))),
+        };
+    }
 
     let snapshots = client.snapshot().list().await?;
 
-    let last_digest = snapshots
+    let latest = snapshots
         .first()
-        .ok_or(MithrilError::msg("no snapshot available"))?
-        .digest
-        .as_ref();
-
-    let snapshot = client
-        .snapshot()
-        .get(last_digest)
-        .await?
         .ok_or(MithrilError::msg("no snapshot available"))?;
 
-    let target_directory = Path::new(&config.snapshot_download_dir);
-
     client
         .snapshot()
-        .download_unpack(&snapshot, target_directory)
-        .await?;
-
-    if let Err(e) = client.snapshot().add_statistics(&snapshot).await {
-        warn!("failed incrementing snapshot download statistics: {:?}", e);
-    }
+        .get(&latest.digest)
+        .await?
+        .map(Some)
+        .ok_or(MithrilError::msg("no snapshot available"))
+}
 
-    let certificate = if config.skip_validation {
+async fn fetch_certificate(
+    client: &Client,
+    config: &Config,
+    snapshot: &Snapshot,
+) -> MithrilResult<MithrilCertificate> {
+    if config.skip_validation {
         client
             .certificate()
             .get(&snapshot.certificate_hash)
             .await?
-            .ok_or(MithrilError::msg("certificate for snapshot not found"))?
+            .ok_or(MithrilError::msg("certificate for snapshot not found"))
     } else {
         client
             .certificate()
             .verify_chain(&snapshot.certificate_hash)
-            .await?
-    };
+            .await
+    }
+}
 
+/// Checks that the data in the directory is the one signed by the certificate
+async fn matches_certificate(certificate: &MithrilCertificate, dir: &Path) -> MithrilResult<bool> {
     let message = MessageBuilder::new()
-        .compute_snapshot_message(&certificate, target_directory)
+        .compute_snapshot_message(certificate, dir)
         .await?;
 
-    assert!(certificate.match_message(&message));
-
-    Ok(())
+    Ok(certificate.match_message(&message))
 }
 
-fn get_starting_points(
+/// Downloads and unpacks the snapshot archive. The archive is unpacked while
+/// it's streamed, so a failed attempt can't be resumed and the next one starts
+/// over from an empty directory.
+async fn download_snapshot(
+    client: &Client,
+    config: &Config,
+    snapshot: &Snapshot,
     dir: &Path,
-    config: &IntersectConfig,
-) -> Result<Vec<Point>, Box<dyn std::error::Error>> {
-    match config {
-        IntersectConfig::Tip => pallas::storage::hardano::immutable::get_tip(dir)?
-            .map_or(Ok(vec![Point::Origin]), |point| Ok(vec![point])),
-        IntersectConfig::Origin => Ok(vec![Point::Origin]),
-        IntersectConfig::Point(slot, hash) => {
-            let hash_bytes = hex::decode(hash)?;
-            Ok(vec![Point::Specific(*slot, hash_bytes)])
+) -> MithrilResult<()> {
+    let attempts = config
+        .download_attempts
+        .unwrap_or(DEFAULT_DOWNLOAD_ATTEMPTS)
+        .max(1);
+
+    let mut attempt = 1;
+
+    loop {
+        match client.snapshot().download_unpack(snapshot, dir).await {
+            Ok(_) => break,
+            Err(err) if attempt < attempts => {
+                warn!(attempt, "snapshot download failed: {:?}", err);
+                clear_snapshot(dir)?;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
         }
-        IntersectConfig::Breadcrumbs(points) => points
-            .iter()
-            .map(|(slot, hash)| {
-                let hash_bytes = hex::decode(hash)?;
-                Ok(Point::Specific(*slot, hash_bytes))
-            })
-            .collect(),
     }
+
+    if let Err(e) = client.snapshot().add_statistics(snapshot).await {
+        warn!("failed incrementing snapshot download statistics: {:?}", e);
+    }
+
+    Ok(())
 }
 
-fn read_blocks_with_config(
-    immutable_path: &Path,
-    config: &IntersectConfig,
-) -> Result<
-    Box<dyn Iterator<Item = pallas::storage::hardano::immutable::FallibleBlock> + Send + Sync>,
-    WorkerError,
-> {
-    let starting_points =
-        get_starting_points(immutable_path, config).map_err(|_| WorkerError::Panic)?;
-
-    for point in starting_points {
-        match pallas::storage::hardano::immutable::read_blocks_from_point(immutable_path, point) {
-            Ok(iter) => return Ok(iter),
-            Err(_) => continue,
+/// Makes sure the directory holds the data of a certified snapshot. Existing
+/// data is verified against the certificate of its snapshot and downloaded
+/// again if it doesn't match, which is the case of interrupted downloads.
+async fn prepare_snapshot(config: &Config, feedback: Arc<Feedback>) -> MithrilResult<()> {
+    let client = ClientBuilder::aggregator(&config.aggregator, &config.genesis_key)
+        .add_feedback_receiver(feedback)
+        .build()?;
+
+    let target_directory = Path::new(&config.snapshot_download_dir);
+
+    let Some(snapshot) = select_snapshot(&client, config, target_directory).await? else {
+        return Ok(());
+    };
+
+    let digest = snapshot.digest.clone();
+
+    let certificate = fetch_certificate(&client, config, &snapshot).await?;
+
+    if !is_dir_empty(target_directory)? {
+        info!(digest, "verifying existing snapshot data");
+
+        // data without a marker comes from an interrupted download or from a
+        // previous version, it's verified against the selected snapshot
+        let recorded = read_digest_marker(target_directory);
+
+        if recorded.is_none() || recorded.as_deref() == Some(digest.as_str()) {
+            let valid = matches_certificate(&certificate, target_directory)
+                .await
+                .unwrap_or_else(|err| {
+                    warn!("failed to verify existing snapshot data: {:?}", err);
+                    false
+                });
+
+            if valid {
+                write_digest_marker(target_directory, &digest)?;
+                info!(digest, "existing snapshot data is valid");
+                return Ok(());
+            }
         }
+
+        warn!(
+            digest,
+            "existing snapshot data is incomplete or doesn't match, downloading again"
+        );
+
+        clear_snapshot(target_directory)?;
+    }
+
+    download_snapshot(&client, config, &snapshot, target_directory).await?;
+
+    if !matches_certificate(&certificate, target_directory).await? {
+        return Err(MithrilError::msg(format!(
+            "downloaded data doesn't match the certificate of snapshot {digest}"
+        )));
     }
 
-    // If all points fail (or if the list was empty), try from Origin
-    pallas::storage::hardano::immutable::read_blocks_from_point(immutable_path, Point::Origin)
-        .map_err(|_| WorkerError::Panic)
+    write_digest_marker(target_directory, &digest)?;
+
+    info!(digest, "snapshot downloaded and verified");
+
+    Ok(())
 }
 
 #[derive(Stage)]
-#[stage(name = "source", unit = "()", worker = "Worker")]
+#[stage(name = "source", unit = "Unit", worker = "Worker")]
 pub struct Stage {
     config: Config,
+    chain: GenesisValues,
     intersect: IntersectConfig,
+    finalize: Option<FinalizeConfig>,
+    breadcrumbs: Breadcrumbs,
+    block_count: u64,
+
+    /// true once the blocks of the snapshot were sent downstream, kept in the
+    /// stage so a restart of the worker doesn't replay them again
+    replayed: bool,
+
     pub output: SourceOutputPort,
 }
 
-pub struct Worker {
-    config: Config,
-    is_bootstrapped: bool,
+impl Stage {
+    fn immutable_path(&self) -> PathBuf {
+        Path::new(&self.config.snapshot_download_dir).join("immutable")
+    }
 }
 
-impl Worker {}
+pub enum Unit {
+    /// Sends the blocks of the snapshot downstream
+    Replay,
 
-#[async_trait::async_trait(?Send)]
-impl gasket::framework::Worker<Stage> for Worker {
-    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
-        let feedback = Arc::new(Feedback::default());
-        let target_directory = Path::new(&stage.config.snapshot_download_dir);
+    /// A chain-sync response received after the replay
+    Next(NextResponse<BlockContent>),
+}
 
-        if !target_directory.exists() {
-            std::fs::create_dir_all(target_directory)
-                .map_err(|err| miette::miette!(err.to_string()))
-                .context(format!(
-                    "Failed to create directory: {}",
-                    target_directory.display()
-                ))
-                .map_err(|_| WorkerError::Panic)?;
-        }
+pub struct Worker {
+    finalized: bool,
 
-        // Check if the directory is empty
-        let is_dir_empty = target_directory
-            .read_dir()
-            .map_err(|err| miette::miette!(err.to_string()))
-            .context("Failed to read target directory")
-            .map_err(|_| WorkerError::Panic)?
-            .next()
-            .is_none();
-
-        if is_dir_empty {
-            // Directory is empty, fetch the snapshot
-            fetch_snapshot(&stage.config, feedback.clone())
-                .await
-                .map_err(|err| miette::miette!(err.to_string()))
-                .context("fetching and validating mithril snapshot")
-                .map_err(|_| WorkerError::Panic)?;
-        } else {
-            println!("Snapshot directory is not empty. Assuming existing snapshot data.");
-            // @TODO add data validation (?) or just assume it's correct
-        }
+    /// the chain-sync session that follows the chain after the replay
+    session: Option<follow::Session>,
+}
 
-        Ok(Self {
-            config: stage.config.clone(),
-            is_bootstrapped: false, // Set to true since we either fetched or found existing data
-        })
-    }
+impl Worker {
+    /// Starts following the chain from the last replayed blocks, or from the
+    /// intersect config if there was nothing to replay
+    async fn start_session(
+        stage: &Stage,
+        config: &follow::Config,
+    ) -> Result<follow::Session, WorkerError> {
+        let mut session = follow::Session::connect(config, stage.chain.magic).await?;
+
+        let points = match stage.breadcrumbs.is_empty() {
+            true => get_starting_points(&stage.immutable_path(), &stage.intersect)
+                .map_err(|_| WorkerError::Panic)?,
+            false => stage.breadcrumbs.points(),
+        };
 
-    async fn schedule(&mut self, _stage: &mut Stage) -> Result<WorkSchedule<()>, WorkerError> {
-        if self.is_bootstrapped {
-            Ok(WorkSchedule::Done)
-        } else {
-            Ok(WorkSchedule::Unit(()))
-        }
+        session.intersect(points).await?;
+
+        info!("snapshot replay done, following the chain");
+
+        Ok(session)
     }
 
-    async fn execute(&mut self, _unit: &(), stage: &mut Stage) -> Result<(), WorkerError> {
-        let immutable_path = Path::new(&self.config.snapshot_download_dir).join("immutable");
+    async fn replay(&mut self, stage: &mut Stage) -> Result<(), WorkerError> {
+        let points = match stage.breadcrumbs.is_empty() {
+            true => get_starting_points(&stage.immutable_path(), &stage.intersect)
+                .map_err(|_| WorkerError::Panic)?,
+            // a previous run already went through the snapshot, the replay
+            // continues after the last point that was sent downstream
+            false => stage.breadcrumbs.points(),
+        };
 
-        let iter = read_blocks_with_config(&immutable_path, &stage.intersect)
-            .into_diagnostic()
-            .context("reading immutable db")
-            .map_err(|_| WorkerError::Panic)?;
+        let tip =
+            pallas::storage::hardano::immutable::get_tip(&stage.immutable_path()).or_panic()?;
 
-        for chunk in iter.chunks(100).into_iter() {
+        let newest = points.first().map(|x| x.slot_or_default());
+        let tip = tip.map(|x| x.slot_or_default());
+
+        if newest >= tip {
+            info!("intersect is past the snapshot tip, skipping replay");
+            stage.replayed = true;
+            return Ok(());
+        }
+
+        // fails if none of the points is in the snapshot, like the immutable
+        // db source, instead of replaying it from origin
+        let iter = read_blocks_after(&stage.immutable_path(), &points)?;
+
+        'replay: for chunk in iter.chunks(100).into_iter() {
             let bodies: Vec<_> = chunk
                 .try_collect()
                 .into_diagnostic()
@@ -314,15 +446,139 @@ impl gasket::framework::Worker<Stage> for Worker {
                 .collect();
 
             for (point, block) in blocks {
-                let event = ChainEvent::Apply(point, Record::CborBlock(block));
+                let event = ChainEvent::Apply(point.clone(), Record::CborBlock(block));
                 stage.output.send(event.into()).await.or_panic()?;
+
+                stage.breadcrumbs.track(point.clone());
+                stage.block_count += 1;
+
+                if should_finalize(&stage.finalize, &stage.chain, &point, stage.block_count) {
+                    info!(?point, "finalize condition reached");
+                    self.finalized = true;
+                    break 'replay;
+                }
             }
         }
 
-        self.is_bootstrapped = true;
+        stage.replayed = true;
 
         Ok(())
     }
+
+    async fn process_next(
+        &mut self,
+        stage: &mut Stage,
+        next: &NextResponse<BlockContent>,
+    ) -> Result<(), WorkerError> {
+        match next {
+            NextResponse::RollForward(cbor, _) => {
+                let block = MultiEraBlock::decode(cbor).or_panic()?;
+                let point = Specific(block.slot(), block.hash().to_vec());
+
+                debug!(?point, "chain sync roll forward");
+
+                let event = ChainEvent::Apply(point.clone(), Record::CborBlock(cbor.to_vec()));
+                stage.output.send(event.into()).await.or_panic()?;
+
+                stage.breadcrumbs.track(point.clone());
+                stage.block_count += 1;
+
+                if should_finalize(&stage.finalize, &stage.chain, &point, stage.block_count) {
+                    info!(?point, "finalize condition reached");
+                    self.finalized = true;
+                }
+
+                Ok(())
+            }
+            NextResponse::RollBackward(point, _) => {
+                debug!(?point, "chain sync rollback");
+
+                stage
+                    .output
+                    .send(ChainEvent::reset(point.clone()))
+                    .await
+                    .or_panic()?;
+
+                stage.breadcrumbs.track(point.clone());
+
+                Ok(())
+            }
+            NextResponse::Await => {
+                info!("chain-sync reached the tip of the chain");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        // the snapshot is only needed until its blocks are replayed
+        if stage.replayed {
+            return Ok(Self {
+                finalized: false,
+                session: None,
+            });
+        }
+
+        let feedback = Arc::new(Feedback::default());
+        let target_directory = Path::new(&stage.config.snapshot_download_dir);
+
+        if !target_directory.exists() {
+            std::fs::create_dir_all(target_directory)
+                .map_err(|err| miette::miette!(err.to_string()))
+                .context(format!(
+                    "Failed to create directory: {}",
+                    target_directory.display()
+                ))
+                .map_err(|_| WorkerError::Panic)?;
+        }
+
+        prepare_snapshot(&stage.config, feedback)
+            .await
+            .map_err(|err| miette::miette!(err.to_string()))
+            .context("fetching and validating mithril snapshot")
+            .map_err(|err| {
+                error!("{err:?}");
+                WorkerError::Panic
+            })?;
+
+        Ok(Self {
+            finalized: false,
+            session: None,
+        })
+    }
+
+    async fn schedule(&mut self, stage: &mut Stage) -> Result<WorkSchedule<Unit>, WorkerError> {
+        if self.finalized {
+            return Ok(WorkSchedule::Done);
+        }
+
+        if !stage.replayed {
+            return Ok(WorkSchedule::Unit(Unit::Replay));
+        }
+
+        let Some(config) = stage.config.follow.clone() else {
+            return Ok(WorkSchedule::Done);
+        };
+
+        if self.session.is_none() {
+            self.session = Some(Self::start_session(stage, &config).await?);
+        }
+
+        let session = self.session.as_mut().unwrap();
+        let next = session.next().await?;
+
+        Ok(WorkSchedule::Unit(Unit::Next(next)))
+    }
+
+    async fn execute(&mut self, unit: &Unit, stage: &mut Stage) -> Result<(), WorkerError> {
+        match unit {
+            Unit::Replay => self.replay(stage).await,
+            Unit::Next(next) => self.process_next(stage, next).await,
+        }
+    }
 }
 
 #[derive(Deserialize, Clone)]
@@ -331,13 +587,29 @@ pub struct Config {
     genesis_key: String,
     snapshot_download_dir: String,
     skip_validation: bool,
+
+    /// Digest of the snapshot to download, the latest one if omitted
+    snapshot_digest: Option<String>,
+
+    /// Times the download is attempted before giving up, each attempt starts
+    /// over from scratch
+    download_attempts: Option<usize>,
+
+    /// Keeps following the chain through chain-sync once the snapshot is
+    /// replayed, instead of stopping at the tip of the snapshot
+    follow: Option<follow::Config>,
 }
 
 impl Config {
     pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
             config: self,
+            chain: ctx.chain.clone().into(),
             intersect: ctx.intersect.clone(),
+            finalize: ctx.finalize.clone(),
+            breadcrumbs: ctx.breadcrumbs.clone(),
+            block_count: 0,
+            replayed: false,
             output: Default::default(),
         };
 
@@ -9,6 +9,12 @@ use crate::framework::*;
 pub mod n2c;
 pub mod n2n;
 
+pub mod follow;
+pub mod immutable;
+
+#[cfg(target_family = "unix")]
+pub mod mempool;
+
 #[cfg(feature = "hydra")]
 pub mod hydra;
 
@@ -38,6 +44,11 @@ pub enum Bootstrapper {
 
     #[cfg(feature = "mithril")]
     Mithril(mithril::Stage),
+
+    ImmutableDb(immutable::Stage),
+
+    #[cfg(target_family = "unix")]
+    Mempool(mempool::Stage),
 }
 
 impl Bootstrapper {
@@ -59,6 +70,11 @@ impl Bootstrapper {
 
             #[cfg(feature = "mithril")]
             Bootstrapper::Mithril(p) => &mut p.output,
+
+            Bootstrapper::ImmutableDb(p) => &mut p.output,
+
+            #[cfg(target_family = "unix")]
+            Bootstrapper::Mempool(p) => &mut p.output,
         }
     }
 
@@ -80,6 +96,11 @@ impl Bootstrapper {
 
             #[cfg(feature = "mithril")]
             Bootstrapper::Mithril(x) => gasket::runtime::spawn_stage(x, policy),
+
+            Bootstrapper::ImmutableDb(x) => gasket::runtime::spawn_stage(x, policy),
+
+            #[cfg(target_family = "unix")]
+            Bootstrapper::Mempool(x) => gasket::runtime::spawn_stage(x, policy),
         }
     }
 }
@@ -103,6 +124,11 @@ pub enum Config {
 
     #[cfg(feature = "mithril")]
     Mithril(mithril::Config),
+
+    ImmutableDb(immutable::Config),
+
+    #[cfg(target_family = "unix")]
+    Mempool(mempool::Config),
 }
 
 impl Config {
@@ -124,6 +150,11 @@ impl Config {
 
             #[cfg(feature = "mithril")]
             Config::Mithril(c) => Ok(Bootstrapper::Mithril(c.bootstrapper(ctx)?)),
+
+            Config::ImmutableDb(c) => Ok(Bootstrapper::ImmutableDb(c.bootstrapper(ctx)?)),
+
+            #[cfg(target_family = "unix")]
+            Config::Mempool(c) => Ok(Bootstrapper::Mempool(c.bootstrapper(ctx)?)),
         }
     }
 }
@@ -24,8 +24,12 @@ pub struct Stage {
 
     intersect: IntersectConfig,
 
+    finalize: Option<FinalizeConfig>,
+
     breadcrumbs: Breadcrumbs,
 
+    block_count: u64,
+
     pub output: SourceOutputPort,
 
     #[metric]
@@ -85,6 +89,7 @@ async fn intersect_from_breadcrumbs(
 }
 
 pub struct Worker {
+    finalized: bool,
     peer_session: NodeClient,
 }
 
@@ -113,6 +118,13 @@ impl Worker {
                 stage.current_slot.set(slot as i64);
                 stage.ops_count.inc(1);
 
+                stage.block_count += 1;
+
+                if should_finalize(&stage.finalize, &stage.chain, &point, stage.block_count) {
+                    info!(slot, %hash, "finalize condition reached");
+                    self.finalized = true;
+                }
+
                 Ok(())
             }
             NextResponse::RollBackward(point, tip) => {
@@ -159,7 +171,10 @@ impl gasket::framework::Worker<Stage> for Worker {
             intersect_from_breadcrumbs(&mut peer_session, &stage.breadcrumbs).await?;
         }
 
-        let worker = Self { peer_session };
+        let worker = Self {
+            finalized: false,
+            peer_session,
+        };
 
         Ok(worker)
     }
@@ -168,6 +183,10 @@ impl gasket::framework::Worker<Stage> for Worker {
         &mut self,
         _stage: &mut Stage,
     ) -> Result<WorkSchedule<NextResponse<BlockContent>>, WorkerError> {
+        if self.finalized {
+            return Ok(WorkSchedule::Done);
+        }
+
         let client = self.peer_session.chainsync();
 
         let next = match client.has_agency() {
@@ -205,6 +224,8 @@ impl Config {
             breadcrumbs: ctx.breadcrumbs.clone(),
             chain: ctx.chain.clone().into(),
             intersect: ctx.intersect.clone(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
             output: Default::default(),
             ops_count: Default::default(),
             chain_tip: Default::default(),
@@ -1,20 +1,19 @@
+use std::time::{Duration, Instant};
+
 use gasket::framework::*;
 use serde::Deserialize;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use pallas::ledger::traverse::MultiEraHeader;
 use pallas::network::facades::PeerClient;
-use pallas::network::miniprotocols::chainsync::{self, HeaderContent, NextResponse};
+use pallas::network::miniprotocols::blockfetch::{self, Body};
+use pallas::network::miniprotocols::chainsync::{self, HeaderContent, NextResponse, Tip};
 use pallas::network::miniprotocols::Point;
 
 use crate::framework::*;
 
 #[derive(Stage)]
-#[stage(
-    name = "source",
-    unit = "NextResponse<HeaderContent>",
-    worker = "Worker"
-)]
+#[stage(name = "source", unit = "Unit", worker = "Worker")]
 pub struct Stage {
     config: Config,
 
@@ -22,13 +21,26 @@ pub struct Stage {
 
     intersect: IntersectConfig,
 
+    finalize: Option<FinalizeConfig>,
+
     breadcrumbs: Breadcrumbs,
 
+    block_count: u64,
+
+    /// index of the peer to try first when the worker (re)connects
+    next_peer: usize,
+
     pub output: SourceOutputPort,
 
     #[metric]
     ops_count: gasket::metrics::Counter,
 
+    #[metric]
+    active_peer: gasket::metrics::Gauge,
+
+    #[metric]
+    peer_rotation_count: gasket::metrics::Counter,
+
     #[metric]
     chain_tip: gasket::metrics::Gauge,
 
@@ -48,6 +60,101 @@ fn to_traverse(header: &HeaderContent) -> Result<MultiEraHeader<'_>, WorkerError
     out.or_panic()
 }
 
+pub enum Unit {
+    /// A chain-sync response, its block is fetched on its own
+    Next(NextResponse<HeaderContent>),
+
+    /// A contiguous range of blocks fetched in a single request while
+    /// catching up
+    Blocks(Vec<(Point, Body)>, Option<Tip>),
+}
+
+/// Headers received from chain-sync while catching up
+#[derive(Default)]
+struct HeaderBatch {
+    points: Vec<Point>,
+    tip: Option<Tip>,
+
+    /// a response that ended the batch early, it needs to be processed after
+    /// the blocks of the batch are sent downstream
+    interrupt: Option<NextResponse<HeaderContent>>,
+
+    near_tip: bool,
+}
+
+impl HeaderBatch {
+    fn is_empty(&self) -> bool {
+        self.points.is_empty() && self.interrupt.is_none()
+    }
+}
+
+/// Requests headers until the batch is full, the tip is near or chain-sync
+/// replies with something other than a roll forward
+///
+/// Requests are not pipelined, the chain-sync client only allows one of them
+/// in flight, so each header costs a round trip.
+async fn collect_headers(
+    client: &mut chainsync::N2NClient,
+    config: &CatchUpConfig,
+) -> Result<HeaderBatch, WorkerError> {
+    let batch_size = config.batch_size.unwrap_or(DEFAULT_CATCH_UP_BATCH_SIZE);
+    let min_tip_distance = config
+        .min_tip_distance
+        .unwrap_or(DEFAULT_CATCH_UP_MIN_TIP_DISTANCE);
+
+    let mut batch = HeaderBatch::default();
+
+    while batch.points.len() < batch_size {
+        match client.request_next().await.or_restart()? {
+            NextResponse::RollForward(header, tip) => {
+                let header = to_traverse(&header)?;
+                let point = Point::Specific(header.slot(), header.hash().to_vec());
+                let distance = tip.1.saturating_sub(header.number());
+
+                batch.points.push(point);
+                batch.tip = Some(tip);
+
+                if distance <= min_tip_distance {
+                    batch.near_tip = true;
+                    break;
+                }
+            }
+            other => {
+                batch.interrupt = Some(other);
+                break;
+            }
+        }
+    }
+
+    Ok(batch)
+}
+
+async fn fetch_blocks(
+    client: &mut blockfetch::Client,
+    points: &[Point],
+) -> Result<Vec<(Point, Body)>, WorkerError> {
+    let range = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (first.clone(), last.clone()),
+        _ => return Ok(vec![]),
+    };
+
+    debug!(?range, "fetching block range");
+
+    let bodies = client.fetch_range(range).await.or_restart()?;
+
+    if bodies.len() != points.len() {
+        warn!(
+            expected = points.len(),
+            received = bodies.len(),
+            "block range doesn't match requested headers"
+        );
+
+        return Err(WorkerError::Restart);
+    }
+
+    Ok(points.iter().cloned().zip(bodies).collect())
+}
+
 async fn intersect_from_config(
     peer: &mut PeerClient,
     intersect: &IntersectConfig,
@@ -91,11 +198,183 @@ async fn intersect_from_breadcrumbs(
     Ok(())
 }
 
+async fn connect_peer(
+    address: &str,
+    stage: &Stage,
+) -> Result<PeerClient, pallas::network::facades::Error> {
+    debug!(address, "connecting");
+
+    PeerClient::connect(address, stage.chain.magic).await
+}
+
+async fn intersect_peer(peer: &mut PeerClient, stage: &Stage) -> Result<(), WorkerError> {
+    if stage.breadcrumbs.is_empty() {
+        intersect_from_config(peer, &stage.intersect).await
+    } else {
+        intersect_from_breadcrumbs(peer, &stage.breadcrumbs).await
+    }
+}
+
+/// Defines the order in which peers should be tried, starting from the one
+/// after the last peer that failed.
+fn rotation_order(stage: &Stage) -> Vec<usize> {
+    let count = stage.config.peers.len();
+
+    (0..count).map(|i| (stage.next_peer + i) % count).collect()
+}
+
+/// Defines the order in which peers should be tried, fastest to slowest
+/// according to the time it takes to connect and complete the handshake.
+/// Unreachable peers are left out.
+async fn latency_order(stage: &Stage) -> Vec<usize> {
+    let mut measured: Vec<(usize, Duration)> = vec![];
+
+    for index in rotation_order(stage) {
+        let address = &stage.config.peers[index];
+        let start = Instant::now();
+
+        match connect_peer(address, stage).await {
+            Ok(peer) => {
+                let latency = start.elapsed();
+                debug!(address, ?latency, "measured peer latency");
+                peer.abort().await;
+                measured.push((index, latency));
+            }
+            Err(err) => warn!(address, %err, "peer is unreachable"),
+        }
+    }
+
+    measured.sort_by_key(|(_, latency)| *latency);
+
+    measured.into_iter().map(|(index, _)| index).collect()
+}
+
 pub struct Worker {
+    finalized: bool,
+    peer_index: usize,
     peer_session: PeerClient,
+
+    /// true while the source is far from the tip and fetches blocks in ranges
+    catching_up: bool,
+
+    /// headers collected while catching up whose blocks haven't been fetched
+    unfetched: HeaderBatch,
 }
 
 impl Worker {
+    /// Makes sure the next bootstrap starts with the peer that follows the
+    /// current one, so that a misbehaving peer isn't retried right away.
+    fn rotate_peer(&self, stage: &mut Stage) {
+        stage.next_peer = (self.peer_index + 1) % stage.config.peers.len();
+        stage.peer_rotation_count.inc(1);
+    }
+
+    /// Rotates the peer if the result signals that the connection with the
+    /// current one needs to be restarted
+    fn check_peer<T>(
+        &self,
+        stage: &mut Stage,
+        result: Result<T, WorkerError>,
+    ) -> Result<T, WorkerError> {
+        if let Err(WorkerError::Restart) = &result {
+            warn!("peer connection failed, switching peer");
+            self.rotate_peer(stage);
+        }
+
+        result
+    }
+
+    async fn apply_block(
+        &mut self,
+        stage: &mut Stage,
+        point: Point,
+        block: Body,
+        tip: Option<&Tip>,
+    ) -> Result<(), WorkerError> {
+        let evt = ChainEvent::Apply(point.clone(), Record::CborBlock(block));
+
+        stage.output.send(evt.into()).await.or_panic()?;
+
+        stage.breadcrumbs.track(point.clone());
+
+        if let Some(tip) = tip {
+            stage.chain_tip.set(tip.0.slot_or_default() as i64);
+        }
+
+        stage.current_slot.set(point.slot_or_default() as i64);
+        stage.ops_count.inc(1);
+
+        stage.block_count += 1;
+
+        if should_finalize(&stage.finalize, &stage.chain, &point, stage.block_count) {
+            info!(?point, "finalize condition reached");
+            self.finalized = true;
+        }
+
+        Ok(())
+    }
+
+    /// Fetches the blocks for the headers collected on the previous call while
+    /// requesting the next batch of headers, both mini-protocols run
+    /// concurrently on the same connection.
+    async fn schedule_catch_up(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<Unit>, WorkerError> {
+        let config = stage.config.catch_up.clone().unwrap_or_default();
+        let mut previous = std::mem::take(&mut self.unfetched);
+
+        // once something interrupted the flow of headers, we stop requesting
+        // more until the interruption is processed
+        let collect = self.catching_up && previous.interrupt.is_none();
+
+        let PeerClient {
+            chainsync,
+            blockfetch,
+            ..
+        } = &mut self.peer_session;
+
+        let (headers, blocks) = tokio::join!(
+            async {
+                match collect {
+                    true => collect_headers(chainsync, &config).await.map(Some),
+                    false => Ok(None),
+                }
+            },
+            fetch_blocks(blockfetch, &previous.points),
+        );
+
+        let blocks = self.check_peer(stage, blocks)?;
+        let headers = self.check_peer(stage, headers)?;
+
+        self.unfetched = match headers {
+            Some(collected) => collected,
+            None => HeaderBatch {
+                interrupt: previous.interrupt.take(),
+                ..Default::default()
+            },
+        };
+
+        let at_tip = matches!(self.unfetched.interrupt, Some(NextResponse::Await));
+
+        if self.catching_up && (self.unfetched.near_tip || at_tip) {
+            info!("close to the tip, switching to single-block fetch");
+            self.catching_up = false;
+        }
+
+        if !blocks.is_empty() {
+            return Ok(WorkSchedule::Unit(Unit::Blocks(blocks, previous.tip)));
+        }
+
+        if self.unfetched.points.is_empty() {
+            if let Some(next) = self.unfetched.interrupt.take() {
+                return Ok(WorkSchedule::Unit(Unit::Next(next)));
+            }
+        }
+
+        Ok(WorkSchedule::Idle)
+    }
+
     async fn process_next(
         &mut self,
         stage: &mut Stage,
@@ -115,22 +394,11 @@ impl Worker {
                     .blockfetch()
                     .fetch_single(point.clone())
                     .await
-                    .or_retry()?;
-
-                let evt = ChainEvent::Apply(
-                    pallas::network::miniprotocols::Point::Specific(slot, hash.to_vec()),
-                    Record::CborBlock(block),
-                );
+                    .or_restart();
 
-                stage.output.send(evt.into()).await.or_panic()?;
+                let block = self.check_peer(stage, block)?;
 
-                stage.breadcrumbs.track(point);
-
-                stage.chain_tip.set(tip.0.slot_or_default() as i64);
-                stage.current_slot.set(slot as i64);
-                stage.ops_count.inc(1);
-
-                Ok(())
+                self.apply_block(stage, point, block, Some(tip)).await
             }
             chainsync::NextResponse::RollBackward(point, tip) => {
                 match &point {
@@ -164,74 +432,147 @@ impl Worker {
 #[async_trait::async_trait(?Send)]
 impl gasket::framework::Worker<Stage> for Worker {
     async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
-        debug!("connecting");
+        if stage.config.peers.is_empty() {
+            return Err(Error::config("at least one upstream peer is required")).or_panic();
+        }
 
-        let peer_address = stage
-            .config
-            .peers
-            .first()
-            .cloned()
-            .ok_or_else(|| Error::config("at least one upstream peer is required"))
-            .or_panic()?;
+        let candidates = match stage.config.prefer_low_latency {
+            true => latency_order(stage).await,
+            false => rotation_order(stage),
+        };
+
+        for peer_index in candidates {
+            let address = &stage.config.peers[peer_index];
+
+            let mut peer_session = match connect_peer(address, stage).await {
+                Ok(x) => x,
+                Err(err) => {
+                    warn!(address, %err, "failed to connect to peer");
+                    continue;
+                }
+            };
+
+            if intersect_peer(&mut peer_session, stage).await.is_err() {
+                warn!(address, "failed to intersect with peer");
+                peer_session.abort().await;
+                continue;
+            }
 
-        let mut peer_session = PeerClient::connect(&peer_address, stage.chain.magic)
-            .await
-            .or_retry()?;
+            info!(address, "connected to peer");
+            stage.active_peer.set(peer_index as i64);
 
-        if stage.breadcrumbs.is_empty() {
-            intersect_from_config(&mut peer_session, &stage.intersect).await?;
-        } else {
-            intersect_from_breadcrumbs(&mut peer_session, &stage.breadcrumbs).await?;
+            let worker = Self {
+                finalized: false,
+                peer_index,
+                peer_session,
+                catching_up: stage.config.catch_up.is_some(),
+                unfetched: Default::default(),
+            };
+
+            return Ok(worker);
         }
 
-        let worker = Self { peer_session };
+        warn!("couldn't connect to any of the upstream peers");
 
-        Ok(worker)
+        Err(WorkerError::Retry)
     }
 
-    async fn schedule(
-        &mut self,
-        _stage: &mut Stage,
-    ) -> Result<WorkSchedule<NextResponse<HeaderContent>>, WorkerError> {
+    async fn schedule(&mut self, stage: &mut Stage) -> Result<WorkSchedule<Unit>, WorkerError> {
+        if self.finalized {
+            return Ok(WorkSchedule::Done);
+        }
+
+        if self.catching_up || !self.unfetched.is_empty() {
+            return self.schedule_catch_up(stage).await;
+        }
+
         let client = self.peer_session.chainsync();
 
         let next = match client.has_agency() {
             true => {
                 info!("requesting next block");
-                client.request_next().await.or_restart()?
+                client.request_next().await
             }
             false => {
                 info!("awaiting next block (blocking)");
-                client.recv_while_must_reply().await.or_restart()?
+                client.recv_while_must_reply().await
             }
         };
 
-        Ok(WorkSchedule::Unit(next))
+        let next = self.check_peer(stage, next.or_restart())?;
+
+        Ok(WorkSchedule::Unit(Unit::Next(next)))
     }
 
-    async fn execute(
-        &mut self,
-        unit: &NextResponse<HeaderContent>,
-        stage: &mut Stage,
-    ) -> Result<(), WorkerError> {
-        self.process_next(stage, unit).await
+    async fn execute(&mut self, unit: &Unit, stage: &mut Stage) -> Result<(), WorkerError> {
+        match unit {
+            Unit::Next(next) => self.process_next(stage, next).await,
+            Unit::Blocks(blocks, tip) => {
+                for (point, block) in blocks {
+                    if self.finalized {
+                        break;
+                    }
+
+                    self.apply_block(stage, point.clone(), block.clone(), tip.as_ref())
+                        .await?;
+                }
+
+                Ok(())
+            }
+        }
     }
 }
 
+const DEFAULT_CATCH_UP_BATCH_SIZE: usize = 100;
+const DEFAULT_CATCH_UP_MIN_TIP_DISTANCE: u64 = 100;
+
+#[derive(Deserialize, Clone, Default)]
+pub struct CatchUpConfig {
+    /// Max number of headers to accumulate before fetching their blocks in a
+    /// single block-fetch request, must be greater than 0
+    pub batch_size: Option<usize>,
+
+    /// Distance to the tip (in blocks) below which the source goes back to
+    /// fetching one block at a time
+    pub min_tip_distance: Option<u64>,
+}
+
 #[derive(Deserialize)]
 pub struct Config {
     pub peers: Vec<String>,
+
+    /// Connect first to the peer that takes the least time to open a
+    /// connection and complete the handshake, instead of following the order
+    /// in which peers are defined
+    #[serde(default)]
+    pub prefer_low_latency: bool,
+
+    /// Fetch blocks in ranges while the source is far from the tip
+    pub catch_up: Option<CatchUpConfig>,
 }
 
 impl Config {
     pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
+        if let Some(CatchUpConfig {
+            batch_size: Some(0),
+            ..
+        }) = &self.catch_up
+        {
+            return Err(Error::config("catch-up batch size must be greater than 0"));
+        }
+
         let stage = Stage {
             config: self,
             breadcrumbs: ctx.breadcrumbs.clone(),
             chain: ctx.chain.clone().into(),
             intersect: ctx.intersect.clone(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
+            next_peer: 0,
             output: Default::default(),
             ops_count: Default::default(),
+            active_peer: Default::default(),
+            peer_rotation_count: Default::default(),
             rollback_count: Default::default(),
             chain_tip: Default::default(),
             current_slot: Default::default(),
@@ -240,3 +581,271 @@ impl Config {
         Ok(stage)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use gasket::framework::Worker as _;
+    use gasket::messaging::tokio::connect_ports;
+    use pallas::codec::minicbor;
+    use pallas::crypto::hash::Hash;
+    use pallas::ledger::primitives::babbage;
+    use pallas::network::facades::PeerServer;
+    use pallas::network::miniprotocols::blockfetch::BlockRequest;
+    use pallas::network::miniprotocols::chainsync::ClientRequest;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn stage(peers: Vec<String>) -> Stage {
+        stage_with_catch_up(peers, None)
+    }
+
+    fn stage_with_catch_up(peers: Vec<String>, catch_up: Option<CatchUpConfig>) -> Stage {
+        let config = Config {
+            peers,
+            prefer_low_latency: false,
+            catch_up,
+        };
+
+        let ctx = Context {
+            chain: Default::default(),
+            intersect: IntersectConfig::Origin,
+            finalize: None,
+            current_dir: ".".into(),
+            breadcrumbs: Breadcrumbs::new(10),
+            control: Default::default(),
+        };
+
+        config.bootstrapper(&ctx).unwrap()
+    }
+
+    /// Starts a peer that accepts a single connection and completes the
+    /// handshake after the given delay
+    async fn mock_peer(delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = PeerServer::accept(&listener, GenesisValues::mainnet().magic).await;
+        });
+
+        address
+    }
+
+    fn header(slot: u64) -> (Point, HeaderContent) {
+        let header = babbage::Header {
+            header_body: babbage::HeaderBody {
+                block_number: slot,
+                slot,
+                prev_hash: None,
+                issuer_vkey: vec![].into(),
+                vrf_vkey: vec![].into(),
+                vrf_result: babbage::VrfCert(vec![].into(), vec![].into()),
+                block_body_size: 0,
+                block_body_hash: Hash::new([0; 32]),
+                operational_cert: babbage::OperationalCert {
+                    operational_cert_hot_vkey: vec![].into(),
+                    operational_cert_sequence_number: 0,
+                    operational_cert_kes_period: 0,
+                    operational_cert_sigma: vec![].into(),
+                },
+                protocol_version: (9, 0),
+            },
+            body_signature: vec![].into(),
+        };
+
+        let content = HeaderContent {
+            variant: 6,
+            byron_prefix: None,
+            cbor: minicbor::to_vec(&header).unwrap(),
+        };
+
+        let header = to_traverse(&content).unwrap();
+        let point = Point::Specific(header.slot(), header.hash().to_vec());
+
+        (point, content)
+    }
+
+    enum Scripted {
+        Forward(HeaderContent),
+        Backward(Point),
+    }
+
+    /// Starts a peer that replies to chain-sync with the scripted responses
+    /// and serves the given blocks through block-fetch
+    async fn mock_chain(script: Vec<Scripted>, blocks: Vec<(Point, Body)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            let mut server = PeerServer::accept(&listener, GenesisValues::mainnet().magic)
+                .await
+                .unwrap();
+
+            let tip = Tip(Point::Specific(1000, vec![]), 1000);
+
+            let PeerServer {
+                chainsync,
+                blockfetch,
+                ..
+            } = &mut server;
+
+            let chainsync = async {
+                let mut script = script.into_iter();
+
+                while let Ok(Some(request)) = chainsync.recv_while_idle().await {
+                    if let ClientRequest::Intersect(_) = request {
+                        chainsync
+                            .send_intersect_found(Point::Origin, tip.clone())
+                            .await
+                            .unwrap();
+
+                        continue;
+                    }
+
+                    match script.next() {
+                        Some(Scripted::Forward(header)) => chainsync
+                            .send_roll_forward(header, tip.clone())
+                            .await
+                            .unwrap(),
+                        Some(Scripted::Backward(point)) => chainsync
+                            .send_roll_backward(point, tip.clone())
+                            .await
+                            .unwrap(),
+                        None => {
+                            chainsync.send_await_reply().await.unwrap();
+                            std::future::pending::<()>().await;
+                        }
+                    }
+                }
+            };
+
+            let blockfetch = async {
+                while let Ok(Some(BlockRequest((from, to)))) = blockfetch.recv_while_idle().await {
+                    let range = from.slot_or_default()..=to.slot_or_default();
+
+                    let bodies = blocks
+                        .iter()
+                        .filter(|(point, _)| range.contains(&point.slot_or_default()))
+                        .map(|(_, body)| body.clone())
+                        .collect();
+
+                    blockfetch.send_block_range(bodies).await.unwrap();
+                }
+            };
+
+            tokio::join!(chainsync, blockfetch);
+        });
+
+        address
+    }
+
+    async fn unreachable_peer() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn rotation_starts_at_next_peer() {
+        let peers = vec!["a:1".into(), "b:1".into(), "c:1".into()];
+        let mut stage = stage(peers);
+
+        assert_eq!(rotation_order(&stage), vec![0, 1, 2]);
+
+        stage.next_peer = 2;
+        assert_eq!(rotation_order(&stage), vec![2, 0, 1]);
+    }
+
+    #[tokio::test]
+    async fn latency_order_skips_unreachable_peers() {
+        let slow = mock_peer(Duration::from_millis(300)).await;
+        let unreachable = unreachable_peer().await;
+        let fast = mock_peer(Duration::ZERO).await;
+
+        let stage = stage(vec![slow, unreachable, fast]);
+
+        assert_eq!(latency_order(&stage).await, vec![2, 0]);
+    }
+
+    #[test]
+    fn zero_batch_size_is_rejected() {
+        let config = Config {
+            peers: vec!["a:1".into()],
+            prefer_low_latency: false,
+            catch_up: Some(CatchUpConfig {
+                batch_size: Some(0),
+                min_tip_distance: None,
+            }),
+        };
+
+        let ctx = Context {
+            chain: Default::default(),
+            intersect: IntersectConfig::Origin,
+            finalize: None,
+            current_dir: ".".into(),
+            breadcrumbs: Breadcrumbs::new(10),
+            control: Default::default(),
+        };
+
+        assert!(config.bootstrapper(&ctx).is_err());
+    }
+
+    #[tokio::test]
+    async fn interrupted_batch_is_sent_before_rollback() {
+        let (p1, h1) = header(10);
+        let (p2, h2) = header(20);
+
+        let script = vec![
+            Scripted::Forward(h1),
+            Scripted::Forward(h2),
+            Scripted::Backward(p1.clone()),
+        ];
+
+        let blocks = vec![(p1.clone(), vec![1]), (p2.clone(), vec![2])];
+
+        let address = mock_chain(script, blocks).await;
+
+        let catch_up = CatchUpConfig {
+            batch_size: Some(10),
+            min_tip_distance: Some(0),
+        };
+
+        let mut stage = stage_with_catch_up(vec![address], Some(catch_up));
+
+        let mut events = gasket::messaging::InputPort::default();
+        connect_ports(&mut stage.output, &mut events, 10);
+
+        let mut worker = Worker::bootstrap(&stage).await.unwrap();
+
+        // headers are collected until the rollback interrupts the batch
+        let schedule = worker.schedule(&mut stage).await.unwrap();
+        assert!(matches!(schedule, WorkSchedule::Idle));
+
+        // the blocks of the interrupted batch are fetched as a single range
+        let WorkSchedule::Unit(unit) = worker.schedule(&mut stage).await.unwrap() else {
+            panic!("expected a unit of blocks");
+        };
+        assert!(matches!(&unit, Unit::Blocks(blocks, _) if blocks.len() == 2));
+        worker.execute(&unit, &mut stage).await.unwrap();
+
+        // the rollback is only processed after the blocks
+        let WorkSchedule::Unit(unit) = worker.schedule(&mut stage).await.unwrap() else {
+            panic!("expected the rollback");
+        };
+        assert!(matches!(&unit, Unit::Next(NextResponse::RollBackward(..))));
+        worker.execute(&unit, &mut stage).await.unwrap();
+
+        let evt = events.recv().await.unwrap().payload;
+        assert!(matches!(evt, ChainEvent::Apply(p, Record::CborBlock(b)) if p == p1 && b == [1]));
+
+        let evt = events.recv().await.unwrap().payload;
+        assert!(matches!(evt, ChainEvent::Apply(p, Record::CborBlock(b)) if p == p2 && b == [2]));
+
+        let evt = events.recv().await.unwrap().payload;
+        assert!(matches!(evt, ChainEvent::Reset(p) if p == p1));
+
+        assert_eq!(stage.block_count, 2);
+        assert_eq!(stage.breadcrumbs.points(), vec![p1]);
+    }
+}
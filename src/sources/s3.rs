@@ -1,69 +1,160 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use aws_config::BehaviorVersion;
 use aws_sdk_s3::Client as S3Client;
 use gasket::framework::*;
+use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
+use tracing::{debug, info, warn};
 
+use crate::framework::block_keys::{point_from_key, KeyLayout};
 use crate::framework::*;
 
+const DEFAULT_POLL_INTERVAL: u64 = 10;
+
+/// Reads the point from the metadata of objects whose key doesn't follow any
+/// of the layouts
+fn point_from_metadata(metadata: Option<HashMap<String, String>>) -> Result<Point, WorkerError> {
+    let metadata = metadata.ok_or("S3 object is missing metadata").or_panic()?;
+    let slot = metadata
+        .get("slot")
+        .ok_or("S3 object is missing block slot")
+        .or_panic()?;
+    let hash = metadata
+        .get("hash")
+        .ok_or("S3 object is missing block hash")
+        .or_panic()?;
+
+    Ok(Point::Specific(
+        slot.parse().or_panic()?,
+        hex::decode(hash).or_panic()?,
+    ))
+}
+
 #[derive(Stage)]
 #[stage(name = "source", unit = "KeyBatch", worker = "Worker")]
 #[stage(name = "source-s3")]
 pub struct Stage {
-    bucket: String,
-    items_per_batch: u32,
+    config: Config,
+
+    chain: GenesisValues,
 
     intersect: IntersectConfig,
 
+    finalize: Option<FinalizeConfig>,
+
     breadcrumbs: Breadcrumbs,
 
+    block_count: u64,
+
     pub output: SourceOutputPort,
 
     #[metric]
     ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    current_slot: gasket::metrics::Gauge,
 }
 
 pub struct Worker {
     s3_client: S3Client,
-    last_key: String,
+
+    /// key of the last block sent downstream, none when reading from origin
+    last_key: Option<String>,
+
+    finalized: bool,
 }
 
 pub struct KeyBatch {
     keys: Vec<String>,
 }
 
+impl Worker {
+    async fn key_exists(&self, stage: &Stage, key: &str) -> Result<bool, WorkerError> {
+        let result = self
+            .s3_client
+            .list_objects_v2()
+            .bucket(&stage.config.bucket)
+            .prefix(key)
+            .max_keys(1)
+            .send()
+            .await
+            .or_retry()?;
+
+        let exists = result
+            .contents
+            .unwrap_or_default()
+            .iter()
+            .any(|obj| obj.key.as_deref() == Some(key));
+
+        Ok(exists)
+    }
+
+    /// Finds the key of the first of the points that is in the bucket, newest
+    /// first. As with chain-sync, reading starts after that block.
+    async fn intersect(&self, stage: &Stage, points: &[Point]) -> Result<String, WorkerError> {
+        for point in points {
+            let Point::Specific(slot, hash) = point else {
+                continue;
+            };
+
+            let key = stage
+                .config
+                .key_layout
+                .key(&stage.config.prefix, *slot, hash);
+
+            if self.key_exists(stage, &key).await? {
+                info!(?point, key, "intersected");
+                return Ok(key);
+            }
+
+            debug!(?point, key, "point not found in bucket");
+        }
+
+        Err(Error::custom(
+            "none of the intersect points is in the bucket",
+        ))
+        .or_panic()
+    }
+}
+
 #[async_trait::async_trait(?Send)]
 impl gasket::framework::Worker<Stage> for Worker {
     async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
         let sdk_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
         let s3_client = aws_sdk_s3::Client::new(&sdk_config);
 
-        let breadcrumbs = stage.breadcrumbs.points();
-        let intersect = stage.intersect.points();
-
-        let point = breadcrumbs
-            .last()
-            .cloned()
-            .or_else(|| intersect.and_then(|p| p.last().cloned()))
-            .unwrap_or(pallas::network::miniprotocols::Point::Origin);
+        let mut worker = Self {
+            s3_client,
+            last_key: None,
+            finalized: false,
+        };
 
-        let key = match point {
-            pallas::network::miniprotocols::Point::Origin => "origin".to_owned(),
-            pallas::network::miniprotocols::Point::Specific(slot, _) => format!("{slot}"),
+        let points = match stage.breadcrumbs.is_empty() {
+            true => stage.intersect.points(),
+            false => Some(stage.breadcrumbs.points()),
         };
 
-        Ok(Self {
-            s3_client,
-            last_key: key,
-        })
+        if let Some(points) = points {
+            worker.last_key = Some(worker.intersect(stage, &points).await?);
+        }
+
+        Ok(worker)
     }
 
     async fn schedule(&mut self, stage: &mut Stage) -> Result<WorkSchedule<KeyBatch>, WorkerError> {
+        if self.finalized {
+            return Ok(WorkSchedule::Done);
+        }
+
         let result = self
             .s3_client
             .list_objects_v2()
-            .bucket(&stage.bucket)
-            .max_keys(stage.items_per_batch as i32)
-            .start_after(self.last_key.clone())
+            .bucket(&stage.config.bucket)
+            .prefix(&stage.config.prefix)
+            .max_keys(stage.config.items_per_batch as i32)
+            .set_start_after(self.last_key.clone())
             .send()
             .await
             .or_retry()?;
@@ -75,7 +166,19 @@ impl gasket::framework::Worker<Stage> for Worker {
             .filter_map(|obj| obj.key)
             .collect::<Vec<_>>();
 
-        Ok(WorkSchedule::Unit(KeyBatch { keys }))
+        if !keys.is_empty() {
+            return Ok(WorkSchedule::Unit(KeyBatch { keys }));
+        }
+
+        if !stage.config.follow {
+            info!("reached the last object of the bucket");
+            return Ok(WorkSchedule::Done);
+        }
+
+        let interval = stage.config.poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL);
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        Ok(WorkSchedule::Idle)
     }
 
     async fn execute(&mut self, unit: &KeyBatch, stage: &mut Stage) -> Result<(), WorkerError> {
@@ -83,35 +186,37 @@ impl gasket::framework::Worker<Stage> for Worker {
             let object = self
                 .s3_client
                 .get_object()
-                .bucket(&stage.bucket)
+                .bucket(&stage.config.bucket)
                 .key(key)
                 .send()
                 .await
                 .or_retry()?;
 
-            let metadata = object
-                .metadata
-                .ok_or("S3 object is missing metadata")
-                .or_panic()?;
-            let slot = metadata
-                .get("slot")
-                .ok_or("S3 object is missing block slot")
-                .or_panic()?;
-            let hash = metadata
-                .get("hash")
-                .ok_or("S3 object is missing block hash")
-                .or_panic()?;
-
-            let point = pallas::network::miniprotocols::Point::Specific(
-                slot.parse().or_panic()?,
-                hex::decode(hash).or_panic()?,
-            );
+            let point = match point_from_key(&stage.config.prefix, key) {
+                Some(x) => x,
+                None => point_from_metadata(object.metadata)?,
+            };
 
             let body = object.body.collect().await.or_retry()?;
 
-            let event = ChainEvent::Apply(point, Record::CborBlock(body.into_bytes().to_vec()));
+            let event =
+                ChainEvent::Apply(point.clone(), Record::CborBlock(body.into_bytes().to_vec()));
 
             stage.output.send(event.into()).await.or_panic()?;
+
+            self.last_key = Some(key.clone());
+            stage.breadcrumbs.track(point.clone());
+
+            stage.current_slot.set(point.slot_or_default() as i64);
+            stage.ops_count.inc(1);
+
+            stage.block_count += 1;
+
+            if should_finalize(&stage.finalize, &stage.chain, &point, stage.block_count) {
+                info!(?point, "finalize condition reached");
+                self.finalized = true;
+                break;
+            }
         }
 
         Ok(())
@@ -122,17 +227,55 @@ impl gasket::framework::Worker<Stage> for Worker {
 pub struct Config {
     bucket: String,
     items_per_batch: u32,
+
+    /// Only the keys that start with this prefix are read
+    #[serde(default)]
+    prefix: String,
+
+    #[serde(default)]
+    key_layout: KeyLayout,
+
+    /// Keep polling the bucket for new objects once all the existing ones
+    /// were read, instead of stopping
+    #[serde(default)]
+    follow: bool,
+
+    /// Seconds between listings of the bucket while following
+    poll_interval: Option<u64>,
 }
 
 impl Config {
     pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
+        // the breadcrumbs of the cursor take precedence over the intersect
+        // config, so the tip is only a problem on the first run
+        if matches!(ctx.intersect, IntersectConfig::Tip) && ctx.breadcrumbs.is_empty() {
+            return Err(Error::config(
+                "the S3 source can't intersect at the tip, use a point instead",
+            ));
+        }
+
+        if self.key_layout == KeyLayout::Plain {
+            // new keys might be listed before the last one that was read, in
+            // which case they'd be skipped
+            if self.follow {
+                return Err(Error::config(
+                    "the S3 source can't follow a bucket with plain keys, set key_layout = \"padded\" instead",
+                ));
+            }
+
+            warn!("plain keys are listed in lexicographic order, slots with a different number of digits won't be read in slot order");
+        }
+
         let stage = Stage {
-            bucket: self.bucket,
-            items_per_batch: self.items_per_batch,
+            config: self,
+            chain: ctx.chain.clone().into(),
             breadcrumbs: ctx.breadcrumbs.clone(),
             intersect: ctx.intersect.clone(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
             output: Default::default(),
             ops_count: Default::default(),
+            current_slot: Default::default(),
         };
 
         Ok(stage)
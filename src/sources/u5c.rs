@@ -4,7 +4,7 @@ use gasket::framework::*;
 use pallas::interop::utxorpc::spec::sync::BlockRef;
 use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
-use tracing::debug;
+use tracing::{debug, info};
 use utxorpc::{CardanoSyncClient, ChainBlock, ClientBuilder, TipEvent};
 
 use crate::framework::*;
@@ -21,6 +21,7 @@ fn point_to_blockref(point: Point) -> Option<BlockRef> {
 
 pub struct Worker {
     stream: utxorpc::LiveTip<utxorpc::Cardano>,
+    finalized: bool,
 }
 
 impl Worker {
@@ -47,7 +48,7 @@ impl Worker {
     }
 
     async fn process_next(
-        &self,
+        &mut self,
         stage: &mut Stage,
         unit: &TipEvent<utxorpc::Cardano>,
     ) -> Result<(), WorkerError> {
@@ -59,6 +60,13 @@ impl Worker {
 
                 stage.output.send(evt.into()).await.or_panic()?;
                 stage.chain_tip.set(point.slot_or_default() as i64);
+
+                stage.block_count += 1;
+
+                if should_finalize(&stage.finalize, &stage.chain, &point, stage.block_count) {
+                    info!(?point, "finalize condition reached");
+                    self.finalized = true;
+                }
             }
             TipEvent::Undo(block) => {
                 let (point, record) = self.block_to_record(stage, block)?;
@@ -117,13 +125,20 @@ impl gasket::framework::Worker<Stage> for Worker {
             .await
             .or_restart()?;
 
-        Ok(Self { stream })
+        Ok(Self {
+            stream,
+            finalized: false,
+        })
     }
 
     async fn schedule(
         &mut self,
         _: &mut Stage,
     ) -> Result<WorkSchedule<TipEvent<utxorpc::Cardano>>, WorkerError> {
+        if self.finalized {
+            return Ok(WorkSchedule::Done);
+        }
+
         let event = self.stream.event().await.or_restart()?;
 
         Ok(WorkSchedule::Unit(event))
@@ -148,8 +163,11 @@ impl gasket::framework::Worker<Stage> for Worker {
 )]
 pub struct Stage {
     config: Config,
+    chain: GenesisValues,
     breadcrumbs: Breadcrumbs,
     intersect: IntersectConfig,
+    finalize: Option<FinalizeConfig>,
+    block_count: u64,
 
     pub output: SourceOutputPort,
 
@@ -175,8 +193,11 @@ impl Config {
     pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
             config: self,
+            chain: ctx.chain.clone().into(),
             breadcrumbs: ctx.breadcrumbs.clone(),
             intersect: ctx.intersect.clone(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
             output: Default::default(),
             ops_count: Default::default(),
             chain_tip: Default::default(),
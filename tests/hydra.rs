@@ -10,6 +10,7 @@ use futures_util::SinkExt;
 use gasket::daemon::Daemon;
 use goldenfile::Mint;
 use oura::daemon::{run_daemon, ConfigRoot};
+use oura::framework::Control;
 use oura::framework::IntersectConfig;
 use oura::sinks::Config::FileRotate;
 use oura::sources::hydra::{HydraMessage, HydraMessagePayload};
@@ -630,7 +631,7 @@ async fn mock_hydra_node(server: TcpListener, mock_data: String) {
 fn test_config(tmp_output_file: &NamedTempFile, ws_url: &String) -> ConfigRoot {
     let mut config = ConfigRoot::new(&Some(PathBuf::from("tests/daemon.toml"))).unwrap();
 
-    if let FileRotate(ref mut file_rotate) = config.sink {
+    if let Some(FileRotate(ref mut file_rotate)) = config.sink {
         file_rotate.output_path = Some(tmp_output_file.path().to_string_lossy().to_string());
     } else {
         panic!("assumed config template to use file_rotate sink");
@@ -646,5 +647,5 @@ fn test_config(tmp_output_file: &NamedTempFile, ws_url: &String) -> ConfigRoot {
 }
 
 fn run_oura(config: ConfigRoot) -> Result<Daemon> {
-    run_daemon(config).map_err(|e| anyhow::anyhow!(e))
+    run_daemon(config, Control::default()).map_err(|e| anyhow::anyhow!(e))
 }